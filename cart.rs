@@ -0,0 +1,308 @@
+use serenity::builder::*;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+
+use crate::commands::{member_roles, reply, string_option, truncate};
+use crate::config::{ShopButton, ShopItem};
+use crate::database::{PurchaseLine, PurchaseOutcome, Voucher};
+use crate::promo_codes::apply_voucher;
+use crate::promotions::quote;
+use crate::loot::{append_draws, delivery_commands};
+use crate::purchase::{find_shop_button, get_vip_tier, purchase_line, rejection_message, send_receipt, template_names, ReceiptLine};
+use crate::shared_state::SharedState;
+use crate::shop::refresh_item_messages;
+use crate::stash::stash_until;
+use crate::template::TemplateContext;
+
+/// A cart row resolved against the current shop catalog and priced for the caller.
+struct PricedLine {
+    cart_item_id: i64,
+    trigger: String,
+    item: ShopItem,
+    button: ShopButton,
+    original_price: u32,
+    price: u32,
+    on_sale: bool,
+}
+
+pub fn cart_command() -> CreateCommand {
+    CreateCommand::new("cart")
+        .description("ตะกร้าสินค้า")
+        .add_option(CreateCommandOption::new(CommandOptionType::SubCommand, "view", "ดูสินค้าในตะกร้าและราคารวม"))
+        .add_option(CreateCommandOption::new(CommandOptionType::SubCommand, "checkout", "ชำระเงินสินค้าทั้งหมดในตะกร้า"))
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "remove", "ลบสินค้าออกจากตะกร้า")
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::String, "item", "สินค้าที่ต้องการลบ")
+                        .required(true)
+                        .set_autocomplete(true)
+                )
+        )
+        .add_option(CreateCommandOption::new(CommandOptionType::SubCommand, "clear", "ล้างตะกร้า"))
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "mode", "เปิด/ปิดโหมดตะกร้า (กดปุ่มร้านค้าเพื่อเพิ่มลงตะกร้า)")
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::Boolean, "enabled", "เปิดโหมดตะกร้า")
+                        .required(true)
+                )
+        )
+}
+
+pub async fn handle_cart_command(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState) {
+    let options = command.data.options();
+    let (subcommand, sub_options) = match options.first() {
+        Some(ResolvedOption { name, value: ResolvedValue::SubCommand(sub_options), .. }) => (*name, sub_options),
+        _ => return,
+    };
+
+    let user_id = command.user.id.to_string();
+
+    match subcommand {
+        "view" => handle_view(ctx, command, shared_state).await,
+        "checkout" => handle_checkout(ctx, command, shared_state).await,
+        "remove" => {
+            let id = string_option(sub_options, "item").and_then(|value| value.parse::<i64>().ok());
+            match id.map(|id| shared_state.db.remove_cart_item(&user_id, id)) {
+                Some(Ok(true)) => reply(ctx, command, "🗑️ ลบสินค้าออกจากตะกร้าแล้ว").await,
+                Some(Err(e)) => reply(ctx, command, format!("❌ เกิดข้อผิดพลาด: {:?}", e)).await,
+                _ => reply(ctx, command, "❌ ไม่พบสินค้านี้ในตะกร้า").await,
+            }
+        },
+        "clear" => match shared_state.db.clear_cart(&user_id) {
+            Ok(()) => reply(ctx, command, "🗑️ ล้างตะกร้าแล้ว").await,
+            Err(e) => reply(ctx, command, format!("❌ เกิดข้อผิดพลาด: {:?}", e)).await,
+        },
+        "mode" => {
+            let enabled = sub_options.iter().any(|option| {
+                option.name == "enabled" && matches!(option.value, ResolvedValue::Boolean(true))
+            });
+            match shared_state.db.set_cart_mode(&user_id, enabled) {
+                Ok(()) if enabled => reply(ctx, command, "🛒 เปิดโหมดตะกร้าแล้ว กดปุ่มในร้านค้าเพื่อเพิ่มสินค้า แล้วใช้ /cart checkout").await,
+                Ok(()) => reply(ctx, command, "🛒 ปิดโหมดตะกร้าแล้ว กดปุ่มในร้านค้าเพื่อซื้อทันที").await,
+                Err(e) => reply(ctx, command, format!("❌ เกิดข้อผิดพลาด: {:?}", e)).await,
+            }
+        },
+        _ => {}
+    }
+}
+
+pub async fn handle_cart_autocomplete(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState) {
+    let query = command.data.autocomplete()
+        .map(|focused| focused.value.to_lowercase())
+        .unwrap_or_default();
+
+    let mut response = CreateAutocompleteResponse::new();
+    let items = shared_state.db.get_cart_items(&command.user.id.to_string()).unwrap_or_default();
+    for cart_item in items.iter().take(25) {
+        if let Some((item, button)) = find_shop_button(&shared_state.shop_file().shops, &cart_item.trigger) {
+            let label = format!("{} ({})", item.name, button.text);
+            if query.is_empty() || label.to_lowercase().contains(&query) {
+                response = response.add_string_choice(truncate(&label, 100), cart_item.id.to_string());
+            }
+        }
+    }
+
+    let _ = command.create_response(&ctx.http, CreateInteractionResponse::Autocomplete(response)).await;
+}
+
+async fn handle_view(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState) {
+    let user_id = command.user.id.to_string();
+    let (lines, missing, voucher) = price_cart(shared_state, command);
+
+    if lines.is_empty() {
+        reply(ctx, command, "🛒 ตะกร้าของคุณว่างอยู่").await;
+        return;
+    }
+
+    let original_total: u32 = lines.iter().map(|line| line.original_price).sum();
+    let total: u32 = lines.iter().map(|line| line.price).sum();
+    let balance = shared_state.db.get_player_by_discord_id(&user_id)
+        .ok()
+        .flatten()
+        .map(|player| player.coin);
+
+    let list = lines.iter()
+        .enumerate()
+        .map(|(index, line)| format!(
+            "{}. **{}** ({}) — {} coin{}",
+            index + 1, line.item.name, line.button.text, line.price, if line.on_sale { " 🔥" } else { "" }
+        ))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let vip_text = match get_vip_tier(&shared_state.config, member_roles(command)) {
+        Some(vip) => vip.name.clone(),
+        None => "-".to_string(),
+    };
+
+    let mut embed = CreateEmbed::new()
+        .title("🛒 ตะกร้าสินค้า")
+        .color(0x9900cc)
+        .description(truncate(&list, 4000))
+        .field("💷 ราคาปกติ", format!("{} coin", original_total), true)
+        .field("👑 VIP", vip_text, true)
+        .field("🏷️ ส่วนลด", format!("-{} coin", original_total - total), true)
+        .field("💰 ราคาสุทธิ", format!("**{}** coin", total), true)
+        .footer(CreateEmbedFooter::new("ใช้ /cart checkout เพื่อชำระเงิน • © powered by TimeSkip"));
+
+    if let Some(voucher) = &voucher {
+        embed = embed.field("🎟️ โค้ดส่วนลด", format!("{} (ลด {}%)", voucher.code, voucher.percent), true);
+    }
+    if let Some(balance) = balance {
+        embed = embed.field("💷 Coin คงเหลือหลังซื้อ", format!("**{}** coin", balance - total as i32), true);
+    }
+    if missing > 0 {
+        embed = embed.field("⚠️", format!("มีสินค้า {} รายการที่ไม่มีในร้านแล้ว จะถูกข้ามตอนชำระเงิน", missing), false);
+    }
+
+    let response = CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .embed(embed)
+            .ephemeral(true)
+    );
+    let _ = command.create_response(&ctx.http, response).await;
+}
+
+/// Charges the whole cart in one transaction and queues every command set as a single
+/// delivery job, so the items arrive together and the player gets one receipt.
+async fn handle_checkout(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState) {
+    if !shared_state.is_bot_active().await {
+        reply(ctx, command, "❌ ระบบ BOTSHOP หยุดทำงานชั่วคราวเนื่องจาก SERVER กำลังจะ RESTART").await;
+        return;
+    }
+    if shared_state.destroy_lock.try_lock().is_err() {
+        reply(ctx, command, "❌ บอทกำลังลบขยะ กรุณาลองใหม่อีกครั้งภายหลัง").await;
+        return;
+    }
+
+    let user_id = command.user.id.to_string();
+    let player = match shared_state.db.get_player_by_discord_id(&user_id) {
+        Ok(Some(player)) => player,
+        _ => {
+            reply(ctx, command, "ไม่พบข้อมูลผู้เล่นของคุณในระบบ! กรุณาลงทะเบียนด้วย /register").await;
+            return;
+        }
+    };
+
+    let (lines, _, voucher) = price_cart(shared_state, command);
+    if lines.is_empty() {
+        reply(ctx, command, "🛒 ตะกร้าของคุณว่างอยู่").await;
+        return;
+    }
+
+    for (index, line) in lines.iter().enumerate() {
+        if let Some(remaining) = shared_state.check_cooldown(&user_id, &line.trigger) {
+            reply(ctx, command, format!("{} อยู่ในช่วง cooldown กรุณารออีก {} วินาที", line.item.name, remaining.as_secs())).await;
+            return;
+        }
+        // The first copy would start the cooldown, so later copies in the same cart are on it.
+        let cooldown = line.button.cooldown();
+        if !cooldown.is_zero() && lines[..index].iter().any(|earlier| earlier.trigger == line.trigger) {
+            reply(ctx, command, format!(
+                "{} มี cooldown {} วินาที ชำระได้ครั้งละ 1 รายการ กรุณาลบรายการที่ซ้ำออกจากตะกร้าด้วย /cart remove",
+                line.item.name, cooldown.as_secs()
+            )).await;
+            return;
+        }
+    }
+
+    let total: u32 = lines.iter().map(|line| line.price).sum();
+    let stash = stash_until(shared_state, &user_id);
+    let purchase_lines: Vec<PurchaseLine> = lines.iter()
+        .map(|line| PurchaseLine {
+            stash_until: stash.clone(),
+            ..purchase_line(&line.item, &line.button, line.price)
+        })
+        .collect();
+    let cart_item_ids: Vec<i64> = lines.iter().map(|line| line.cart_item_id).collect();
+
+    let (remaining_coin, purchase_ids) = match shared_state.db.checkout_cart(&user_id, &player.steam_id, &purchase_lines, &cart_item_ids, voucher.map(|voucher| voucher.redemption_id)) {
+        Ok(PurchaseOutcome::Charged { remaining, purchase_ids }) => (remaining, purchase_ids),
+        Ok(outcome) => {
+            reply(ctx, command, rejection_message(&outcome, total, player.coin)).await;
+            return;
+        },
+        Err(_) => {
+            reply(ctx, command, "เกิดข้อผิดพลาดในการหัก coin!").await;
+            return;
+        }
+    };
+
+    let mut draws = Vec::new();
+    if stash.is_none() {
+        let (discord_name, player_name) = template_names(&command.user, command.member.as_deref());
+        let mut commands = Vec::new();
+        for (line, purchase_id) in lines.iter().zip(&purchase_ids) {
+            let context = TemplateContext {
+                steam_id: &player.steam_id,
+                discord_name: &discord_name,
+                player_name: &player_name,
+                quantity: line.button.quantity,
+                purchase_id: *purchase_id,
+            };
+            let (line_commands, line_draws) = delivery_commands(shared_state, &line.item, &line.button, &context, &user_id);
+            commands.extend(line_commands);
+            draws.extend(line_draws);
+        }
+        shared_state.command_queue.lock().await.push(commands);
+    }
+
+    for line in &lines {
+        shared_state.set_cooldown(&user_id, &line.trigger, line.button.cooldown());
+    }
+
+    let content = if stash.is_some() {
+        format!("📦 ชำระเงิน {} รายการสำเร็จ! เก็บไว้ในคลังแล้ว ใช้ /claim เพื่อรับของ (เหลือ {} coins)", lines.len(), remaining_coin)
+    } else {
+        format!("✅ ชำระเงิน {} รายการสำเร็จ! หัก {} coins (เหลือ {} coins)", lines.len(), total, remaining_coin)
+    };
+    let content = append_draws(content, &draws);
+    reply(ctx, command, content).await;
+
+    let receipt: Vec<ReceiptLine> = lines.iter()
+        .map(|line| ReceiptLine { item_name: line.item.name.clone(), quantity: line.button.quantity, price: line.price })
+        .collect();
+    send_receipt(&ctx.http, &command.user, &receipt, remaining_coin).await;
+
+    let limited: Vec<String> = lines.iter()
+        .filter(|line| line.button.stock.is_some())
+        .map(|line| line.trigger.clone())
+        .collect();
+    if !limited.is_empty() {
+        refresh_item_messages(&ctx.http, shared_state, &limited).await;
+    }
+}
+
+/// Returns priced lines plus the number of cart rows whose button no longer exists. A pending
+/// discount code counts the whole checkout as the next purchase and applies to every line.
+fn price_cart(shared_state: &SharedState, command: &CommandInteraction) -> (Vec<PricedLine>, usize, Option<Voucher>) {
+    let user_id = command.user.id.to_string();
+    let cart = shared_state.db.get_cart_items(&user_id).unwrap_or_default();
+    let vip = get_vip_tier(&shared_state.config, member_roles(command));
+    let voucher = shared_state.db.get_pending_voucher(&user_id).ok().flatten();
+
+    let mut lines = Vec::new();
+    let mut missing = 0;
+    for cart_item in cart {
+        match find_shop_button(&shared_state.shop_file().shops, &cart_item.trigger) {
+            Some((item, button)) => {
+                let price = quote(shared_state, &item, &button, vip);
+                lines.push(PricedLine {
+                    cart_item_id: cart_item.id,
+                    trigger: cart_item.trigger,
+                    item,
+                    button,
+                    original_price: price.original,
+                    price: match &voucher {
+                        Some(voucher) => apply_voucher(price.total, voucher),
+                        None => price.total,
+                    },
+                    on_sale: price.sale.is_some(),
+                });
+            },
+            None => missing += 1,
+        }
+    }
+
+    (lines, missing, voucher)
+}
@@ -0,0 +1,151 @@
+use serenity::builder::*;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+
+use crate::commands::truncate;
+use crate::config::{ShopConfig, ShopItem};
+use crate::shared_state::SharedState;
+use crate::shop::prepare_item;
+
+/// `shop_messages` key of the single message posted for a catalog shop.
+pub const CATALOG_MESSAGE_KEY: &str = "__catalog__";
+
+const UNCATEGORIZED: &str = "อื่นๆ";
+
+/// Item buttons get at most four rows; the fifth holds the page navigation.
+const MAX_ITEM_ROWS: usize = 4;
+
+/// Categories in order of first appearance, each with its listed items.
+pub fn categories(shop: &ShopConfig) -> Vec<(&str, Vec<&ShopItem>)> {
+    let mut categories: Vec<(&str, Vec<&ShopItem>)> = Vec::new();
+    for item in shop.items.iter().filter(|item| item.is_listed()) {
+        let name = item.category.as_deref().unwrap_or(UNCATEGORIZED);
+        match categories.iter_mut().find(|(category, _)| *category == name) {
+            Some((_, items)) => items.push(item),
+            None => categories.push((name, vec![item])),
+        }
+    }
+    categories
+}
+
+/// Everything the public catalog message shows, for change detection.
+pub fn catalog_fingerprint(shop: &ShopConfig) -> String {
+    let categories: Vec<String> = categories(shop).iter()
+        .map(|(name, items)| format!("{}={}", name, items.len()))
+        .collect();
+    format!("{}|{}", shop.title.as_deref().unwrap_or(""), categories.join(","))
+}
+
+/// The public message of a catalog shop: a category overview and the select menu.
+pub fn render_catalog(shop: &ShopConfig) -> (CreateEmbed, Vec<CreateActionRow>) {
+    let categories = categories(shop);
+    let overview = categories.iter()
+        .map(|(name, items)| format!("• **{}** ({} รายการ)", name, items.len()))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let embed = CreateEmbed::new()
+        .title(shop.title.as_deref().unwrap_or("🛒 ร้านค้า"))
+        .color(0xFF00FF)
+        .description(truncate(&format!("เลือกหมวดหมู่จากเมนูด้านล่างเพื่อดูสินค้า\n\n{}", overview), 4000))
+        .footer(CreateEmbedFooter::new("© powered by TimeSkip"));
+
+    let options: Vec<CreateSelectMenuOption> = categories.iter()
+        .enumerate()
+        .take(25)
+        .map(|(index, (name, items))| {
+            CreateSelectMenuOption::new(truncate(name, 100), index.to_string())
+                .description(format!("{} รายการ", items.len()))
+        })
+        .collect();
+
+    let menu = CreateSelectMenu::new(format!("shop_category:{}", shop.channel), CreateSelectMenuKind::String { options })
+        .placeholder("เลือกหมวดหมู่");
+
+    (embed, vec![CreateActionRow::SelectMenu(menu)])
+}
+
+/// Category picked from the public menu: opens an ephemeral view on the first item.
+pub async fn handle_category_select(ctx: &Context, component: &ComponentInteraction, shared_state: &SharedState, channel: &str) {
+    let category = match &component.data.kind {
+        ComponentInteractionDataKind::StringSelect { values } => values.first().and_then(|value| value.parse::<usize>().ok()),
+        _ => None,
+    };
+
+    if let Some(category) = category {
+        show_page(ctx, component, shared_state, channel, category, 0, false).await;
+    }
+}
+
+/// Prev/next inside the ephemeral view. `data` is `channel:category:page:direction`.
+pub async fn handle_page(ctx: &Context, component: &ComponentInteraction, shared_state: &SharedState, data: &str) {
+    let parts: Vec<&str> = data.split(':').collect();
+    if let [channel, category, page, _direction] = parts.as_slice() {
+        if let (Ok(category), Ok(page)) = (category.parse::<usize>(), page.parse::<usize>()) {
+            show_page(ctx, component, shared_state, channel, category, page, true).await;
+        }
+    }
+}
+
+async fn show_page(
+    ctx: &Context,
+    component: &ComponentInteraction,
+    shared_state: &SharedState,
+    channel: &str,
+    category: usize,
+    page: usize,
+    update: bool,
+) {
+    let shop_file = shared_state.shop_file();
+    let shop = shop_file.shops.iter().find(|shop| shop.channel == channel);
+    let categories = shop.map(categories).unwrap_or_default();
+    let (shop, (name, items)) = match (shop, categories.get(category)) {
+        (Some(shop), Some(entry)) if !entry.1.is_empty() => (shop, entry),
+        _ => {
+            let response = CreateInteractionResponseMessage::new()
+                .content("❌ ไม่พบหมวดหมู่นี้แล้ว กรุณาเลือกใหม่จากเมนูร้านค้า")
+                .ephemeral(true);
+            let _ = component.create_response(&ctx.http, CreateInteractionResponse::Message(response)).await;
+            return;
+        }
+    };
+
+    let page = page.min(items.len() - 1);
+    let item = items[page];
+    let stock = shared_state.db.get_stock_levels().unwrap_or_default();
+    let prepared = prepare_item(shared_state, shop, item, &stock, None).await;
+
+    let embed = prepared.embed.footer(CreateEmbedFooter::new(format!(
+        "{} • {}/{} • © powered by TimeSkip",
+        name, page + 1, items.len()
+    )));
+
+    let mut components: Vec<CreateActionRow> = prepared.components.into_iter().take(MAX_ITEM_ROWS).collect();
+    let page_id = |target: usize, direction: &str| format!("shop_page:{}:{}:{}:{}", channel, category, target, direction);
+    components.push(CreateActionRow::Buttons(vec![
+        CreateButton::new(page_id(page.saturating_sub(1), "prev"))
+            .label("◀ ก่อนหน้า")
+            .style(ButtonStyle::Secondary)
+            .disabled(page == 0),
+        CreateButton::new(page_id(page + 1, "next"))
+            .label("ถัดไป ▶")
+            .style(ButtonStyle::Secondary)
+            .disabled(page + 1 >= items.len()),
+    ]));
+
+    // Uploads aren't cached here: ephemeral messages disappear and take their attachments along.
+    let mut message = CreateInteractionResponseMessage::new()
+        .embed(embed)
+        .components(components)
+        .ephemeral(true);
+    if let Some(upload) = prepared.upload {
+        message = message.add_file(upload.attachment);
+    }
+
+    let response = if update {
+        CreateInteractionResponse::UpdateMessage(message)
+    } else {
+        CreateInteractionResponse::Message(message)
+    };
+    let _ = component.create_response(&ctx.http, response).await;
+}
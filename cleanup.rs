@@ -0,0 +1,242 @@
+use chrono::{DateTime, Duration as ChronoDuration, Local, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Asia::Bangkok;
+use chrono_tz::Tz;
+use serenity::builder::{CreateEmbed, CreateEmbedFooter, CreateMessage};
+use serenity::http::Http;
+use serenity::model::id::ChannelId;
+use tokio::time::{sleep, Duration};
+use std::sync::Arc;
+use log::{info, error};
+
+use crate::config::{CleanupProfile, MaintenancePolicy};
+use crate::database::DB_TIME_FORMAT;
+use crate::maintenance::maintenance_conflict;
+use crate::shared_state::SharedState;
+use crate::utils::send_commands_to_game;
+
+/// Upper bound on how long the scheduler sleeps before re-checking due times.
+const SCHEDULER_TICK_SECS: u64 = 60;
+
+/// Who started a cleanup run; stored with each run in `cleanup_runs`.
+pub enum CleanupTrigger {
+    Automatic,
+    Manual(String),
+    Vote(Vec<String>),
+}
+
+impl CleanupTrigger {
+    pub fn is_automatic(&self) -> bool {
+        matches!(self, CleanupTrigger::Automatic)
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CleanupTrigger::Automatic => "auto",
+            CleanupTrigger::Manual(_) => "manual",
+            CleanupTrigger::Vote(_) => "vote",
+        }
+    }
+
+    pub fn triggered_by(&self) -> String {
+        match self {
+            CleanupTrigger::Automatic => "scheduler".to_string(),
+            CleanupTrigger::Manual(user_id) => user_id.clone(),
+            CleanupTrigger::Vote(user_ids) => user_ids.join(","),
+        }
+    }
+}
+
+/// Runs one cleanup profile in game. Holds `destroy_lock` for the whole run so the shop
+/// stays frozen until the end announcement has been sent.
+///
+/// Manual and vote runs re-check the profile cooldown once the lock is held, so presses
+/// queued behind another run are refused with the remaining time instead of running twice.
+pub async fn run_cleanup(
+    http: &Http,
+    shared_state: &SharedState,
+    profile: &CleanupProfile,
+    trigger: CleanupTrigger,
+) -> Result<(), ChronoDuration> {
+    let _guard = shared_state.destroy_lock.lock().await;
+
+    if !trigger.is_automatic() {
+        if let Some(remaining) = shared_state.cleanup_cooldown_remaining(profile) {
+            return Err(remaining);
+        }
+    }
+
+    let automatic = trigger.is_automatic();
+    let started_at = Utc::now();
+    shared_state.set_cleanup_last_run(&profile.id, started_at);
+
+    info!("🧹 Starting {} cleanup '{}' (by {})", trigger.kind(), profile.id, trigger.triggered_by());
+
+    let commands = profile.commands();
+    let command_count = commands.len();
+
+    send_commands_to_game(vec![profile.start_announcement(automatic)], "destroy").await;
+    sleep(Duration::from_secs(1)).await;
+    send_commands_to_game(commands, "destroy").await;
+    send_commands_to_game(vec![profile.end_announcement(automatic)], "destroy").await;
+
+    let finished_at = Utc::now();
+    if let Err(e) = shared_state.db.log_cleanup_run(
+        &profile.id,
+        trigger.kind(),
+        &trigger.triggered_by(),
+        command_count,
+        &started_at.format(DB_TIME_FORMAT).to_string(),
+        &finished_at.format(DB_TIME_FORMAT).to_string(),
+    ) {
+        error!("Failed to record cleanup run '{}': {:?}", profile.id, e);
+    }
+
+    info!("✅ Cleanup '{}' completed", profile.id);
+
+    post_cleanup_summary(http, shared_state, profile, &trigger, finished_at - started_at, command_count).await;
+    Ok(())
+}
+
+async fn post_cleanup_summary(
+    http: &Http,
+    shared_state: &SharedState,
+    profile: &CleanupProfile,
+    trigger: &CleanupTrigger,
+    duration: ChronoDuration,
+    command_count: usize,
+) {
+    if shared_state.config.admin_channel_id == 0 {
+        return;
+    }
+
+    let triggered_by = match trigger {
+        CleanupTrigger::Automatic => "ระบบอัตโนมัติ".to_string(),
+        CleanupTrigger::Manual(user_id) => format!("<@{}>", user_id),
+        CleanupTrigger::Vote(user_ids) => user_ids.iter()
+            .map(|id| format!("<@{}>", id))
+            .collect::<Vec<_>>()
+            .join(", "),
+    };
+
+    let embed = CreateEmbed::new()
+        .title(format!("🧹 สรุปการลบ: {}", profile.name))
+        .color(0x00ff00)
+        .field("ประเภท", trigger.kind(), true)
+        .field("ผู้สั่ง", triggered_by, true)
+        .field("เวลาที่ใช้", format!("{} วินาที", duration.num_seconds()), true)
+        .field("จำนวนคำสั่ง", command_count.to_string(), true)
+        .footer(CreateEmbedFooter::new("© powered by TimeSkip"));
+
+    let channel_id = ChannelId::new(shared_state.config.admin_channel_id);
+    if let Err(e) = channel_id.send_message(http, CreateMessage::new().embed(embed)).await {
+        error!("Failed to post cleanup summary: {:?}", e);
+    }
+}
+
+/// Single scheduler for every profile that has an interval or wall-clock schedule.
+///
+/// Due times are recomputed from `cleanup_last_run` on every tick, so button and vote
+/// runs push the next automatic run back, and timestamps restored from `cleanup_runs`
+/// keep intervals running across bot restarts.
+pub async fn start_cleanup_scheduler(http: Arc<Http>, shared_state: Arc<SharedState>) {
+    restore_last_runs(&shared_state);
+
+    let started = Local::now().with_timezone(&Bangkok);
+
+    loop {
+        let now = Local::now().with_timezone(&Bangkok);
+
+        let next = shared_state.config.cleanup_profiles.iter()
+            .filter_map(|profile| next_due(&shared_state, profile, started).map(|at| (profile, at)))
+            .min_by_key(|(_, at)| *at);
+
+        let (profile, due) = match next {
+            Some(next) => next,
+            None => {
+                info!("No scheduled cleanup profiles configured");
+                return;
+            }
+        };
+
+        if due > now {
+            let wait = (due - now).to_std().unwrap_or(Duration::ZERO);
+            info!("Next cleanup '{}' at {} (in {} seconds)", profile.id, due.format("%H:%M"), wait.as_secs());
+            sleep(wait.min(Duration::from_secs(SCHEDULER_TICK_SECS))).await;
+            continue;
+        }
+
+        if !shared_state.is_bot_active().await {
+            info!("Cleanup '{}' is due but the bot is paused, waiting", profile.id);
+            sleep(Duration::from_secs(SCHEDULER_TICK_SECS)).await;
+            continue;
+        }
+
+        let _ = run_cleanup(&http, &shared_state, profile, CleanupTrigger::Automatic).await;
+    }
+}
+
+fn restore_last_runs(shared_state: &SharedState) {
+    match shared_state.db.get_last_cleanup_runs() {
+        Ok(runs) => {
+            for (profile_id, started_at) in runs {
+                if let Ok(at) = NaiveDateTime::parse_from_str(&started_at, DB_TIME_FORMAT) {
+                    shared_state.set_cleanup_last_run(&profile_id, Utc.from_utc_datetime(&at));
+                }
+            }
+        },
+        Err(e) => error!("Failed to load cleanup history: {:?}", e),
+    }
+}
+
+/// Next automatic run for a profile, moved out of restart windows according to its
+/// `maintenance_policy`.
+fn next_due(shared_state: &SharedState, profile: &CleanupProfile, started: DateTime<Tz>) -> Option<DateTime<Tz>> {
+    let last_run = shared_state.cleanup_last_run.get(&profile.id)
+        .map(|at| at.with_timezone(&Bangkok));
+
+    // Intervals continue from the last recorded run; wall-clock schedules only look at
+    // slots after startup so a long downtime doesn't trigger a catch-up run.
+    let base = match (profile.interval_minutes, last_run) {
+        (Some(_), Some(last_run)) => last_run,
+        (None, Some(last_run)) => last_run.max(started),
+        (_, None) => started,
+    };
+
+    let margin = ChronoDuration::minutes(profile.maintenance_margin_minutes as i64);
+    let mut due = next_run(profile, base)?;
+
+    // Bounded so a misconfigured schedule that always overlaps a window can't spin forever.
+    for _ in 0..48 {
+        let resume_at = match maintenance_conflict(&shared_state.config, due, margin) {
+            Some(resume_at) => resume_at,
+            None => return Some(due),
+        };
+
+        due = match profile.maintenance_policy {
+            MaintenancePolicy::Shift => resume_at,
+            MaintenancePolicy::Skip => next_run(profile, due)?,
+        };
+    }
+
+    Some(due)
+}
+
+/// Next time a profile is due after `last_run`, or `None` for manual-only profiles.
+pub fn next_run(profile: &CleanupProfile, last_run: DateTime<Tz>) -> Option<DateTime<Tz>> {
+    if let Some(minutes) = profile.interval_minutes {
+        return Some(last_run + ChronoDuration::minutes(minutes as i64));
+    }
+
+    let first_day = last_run.date_naive();
+    for day in [first_day, first_day + ChronoDuration::days(1)] {
+        for time in &profile.schedule_times {
+            if let Some(at) = Bangkok.from_local_datetime(&day.and_time(*time)).single() {
+                if at > last_run {
+                    return Some(at);
+                }
+            }
+        }
+    }
+
+    None
+}
@@ -1,32 +1,727 @@
 use serenity::model::prelude::*;
 use serenity::prelude::*;
 use serenity::builder::*;
+use log::info;
+
+use crate::config::CleanupProfile;
+use crate::database::{LedgerReason, PermissionGrant};
+use crate::permissions::{has_permission, is_guild_admin, Permission};
+use crate::shared_state::SharedState;
+use crate::stash::discord_time;
+use crate::shop::{sync_shop, SyncSummary};
+use crate::purchase::find_button;
+use crate::utils::format_wait_time;
+
+/// Application commands registered for the configured guild on `ready`.
+pub fn guild_commands() -> Vec<CreateCommand> {
+    vec![
+        CreateCommand::new("register")
+            .description("ลงทะเบียน Steam ID เพื่อใช้งาน bot")
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::String, "steam_id", "Steam ID 64 ของคุณ")
+                    .required(true)
+            ),
+        CreateCommand::new("coin")
+            .description("เช็คจำนวน coin ที่มี"),
+        CreateCommand::new("settings")
+            .description("ตั้งค่าการซื้อของคุณ")
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::Integer, "confirm_below", "ไม่ต้องยืนยันเมื่อราคาต่ำกว่านี้ (0 = ยืนยันทุกครั้ง)")
+                    .min_int_value(0)
+            ),
+        crate::cart::cart_command(),
+        crate::search::shop_command(),
+        crate::gift::gift_command(),
+        crate::stash::stash_command(),
+        crate::stash::claim_command(),
+        crate::promo_codes::redeem_command(),
+        CreateCommand::new("addcoin")
+            .description("เพิ่ม coin ให้ผู้เล่น (Admin)")
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::User, "user", "ผู้เล่นที่ต้องการเพิ่ม coin")
+                    .required(true)
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::Integer, "amount", "จำนวน coin (ติดลบเพื่อหัก)")
+                    .required(true)
+            ),
+        CreateCommand::new("updateshop")
+            .description("อัปเดตร้านค้า (Admin)")
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::String, "shop", "อัปเดตเฉพาะร้านนี้")
+                    .set_autocomplete(true)
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::Boolean, "full", "แก้ไขทุกข้อความแม้สินค้าไม่มีการเปลี่ยนแปลง")
+            ),
+        crate::promo_codes::promocode_command(),
+        crate::promo_codes::ledger_command(),
+        crate::loot::lootlog_command(),
+        crate::shopedit::shopedit_command(),
+        crate::shop_versions::shopversions_command(),
+        CreateCommand::new("destroy")
+            .description("แสดงปุ่มลบไอเทมในช่องนี้ (Admin)"),
+        CreateCommand::new("sendcommand")
+            .description("ส่งคำสั่งเข้าเกมโดยตรง (Admin)")
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::String, "command", "คำสั่ง เช่น #Announce ...")
+                    .required(true)
+            ),
+        CreateCommand::new("purchases")
+            .description("ดูประวัติการซื้อล่าสุด (Admin)")
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::User, "user", "ดูเฉพาะผู้เล่นคนนี้")
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::Integer, "limit", "จำนวนรายการ (สูงสุด 25)")
+                    .min_int_value(1)
+                    .max_int_value(25)
+            ),
+        CreateCommand::new("cooldowns")
+            .description("ดูหรือล้าง cooldown การซื้อของผู้เล่น (Admin)")
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::SubCommand, "view", "ดู cooldown ที่ยังไม่หมดเวลา")
+                    .add_sub_option(
+                        CreateCommandOption::new(CommandOptionType::User, "user", "ผู้เล่น")
+                            .required(true)
+                    )
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::SubCommand, "clear", "ล้าง cooldown ของผู้เล่น")
+                    .add_sub_option(
+                        CreateCommandOption::new(CommandOptionType::User, "user", "ผู้เล่น")
+                            .required(true)
+                    )
+                    .add_sub_option(
+                        CreateCommandOption::new(CommandOptionType::String, "item", "ล้างเฉพาะสินค้านี้ (ไม่ระบุ = ทั้งหมด)")
+                            .set_autocomplete(true)
+                    )
+            ),
+        CreateCommand::new("permission")
+            .description("จัดการสิทธิ์แอดมิน")
+            .add_option(permission_subcommand("grant", "ให้สิทธิ์กับผู้ใช้หรือ role"))
+            .add_option(permission_subcommand("revoke", "ถอนสิทธิ์จากผู้ใช้หรือ role"))
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::SubCommand, "list", "แสดงสิทธิ์ทั้งหมด")
+            ),
+        CreateCommand::new("help")
+            .description("แสดงคำสั่งที่ใช้ได้"),
+    ]
+}
+
+pub async fn handle_command(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState) {
+    match command.data.name.as_str() {
+        "register" => handle_register(ctx, command, shared_state).await,
+        "coin" => handle_coin(ctx, command, shared_state).await,
+        "settings" => handle_settings(ctx, command, shared_state).await,
+        "cart" => crate::cart::handle_cart_command(ctx, command, shared_state).await,
+        "shop" => crate::search::handle_shop_command(ctx, command, shared_state).await,
+        "gift" => crate::gift::handle_gift_command(ctx, command, shared_state).await,
+        "stash" => crate::stash::handle_stash_command(ctx, command, shared_state).await,
+        "claim" => crate::stash::handle_claim_command(ctx, command, shared_state).await,
+        "redeem" => crate::promo_codes::handle_redeem_command(ctx, command, shared_state).await,
+        "addcoin" => handle_addcoin(ctx, command, shared_state).await,
+        "promocode" => crate::promo_codes::handle_promocode_command(ctx, command, shared_state).await,
+        "ledger" => crate::promo_codes::handle_ledger_command(ctx, command, shared_state).await,
+        "lootlog" => crate::loot::handle_lootlog_command(ctx, command, shared_state).await,
+        "updateshop" => handle_updateshop(ctx, command, shared_state).await,
+        "shopedit" => crate::shopedit::handle_shopedit_command(ctx, command, shared_state).await,
+        "shopversions" => crate::shop_versions::handle_shopversions_command(ctx, command, shared_state).await,
+        "destroy" => handle_destroy_command(ctx, command, shared_state).await,
+        "sendcommand" => handle_sendcommand(ctx, command, shared_state).await,
+        "purchases" => handle_purchases(ctx, command, shared_state).await,
+        "cooldowns" => handle_cooldowns(ctx, command, shared_state).await,
+        "permission" => handle_permission(ctx, command, shared_state).await,
+        "help" => handle_help(ctx, command).await,
+        _ => {}
+    }
+}
+
+pub async fn handle_autocomplete(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState) {
+    let focused = match command.data.autocomplete() {
+        Some(focused) => focused,
+        None => return,
+    };
+
+    if command.data.name == "cart" {
+        crate::cart::handle_cart_autocomplete(ctx, command, shared_state).await;
+        return;
+    }
+
+    if command.data.name == "shop" {
+        crate::search::handle_shop_autocomplete(ctx, command, shared_state).await;
+        return;
+    }
+
+    if command.data.name == "gift" {
+        crate::gift::handle_gift_autocomplete(ctx, command, shared_state).await;
+        return;
+    }
+
+    if command.data.name == "claim" {
+        crate::stash::handle_claim_autocomplete(ctx, command, shared_state).await;
+        return;
+    }
+
+    if command.data.name == "shopedit" {
+        crate::shopedit::handle_shopedit_autocomplete(ctx, command, shared_state).await;
+        return;
+    }
+
+    if command.data.name == "promocode" {
+        crate::promo_codes::handle_promocode_autocomplete(ctx, command, shared_state).await;
+        return;
+    }
+
+    if command.data.name == "shopversions" {
+        crate::shop_versions::handle_shopversions_autocomplete(ctx, command, shared_state).await;
+        return;
+    }
+
+    let mut response = CreateAutocompleteResponse::new();
+    let shop_file = shared_state.shop_file();
+
+    if command.data.name == "updateshop" && focused.name == "shop" {
+        let query = focused.value.to_lowercase();
+        for (index, shop) in shop_file.shops.iter().enumerate().take(25) {
+            let first_item = shop.items.first().map(|item| item.name.as_str()).unwrap_or("-");
+            let label = format!("ร้าน {} ({} รายการ) เช่น {}", index + 1, shop.items.len(), first_item);
+            if query.is_empty()
+                || shop.channel.contains(&query)
+                || shop.items.iter().any(|item| item.name.to_lowercase().contains(&query))
+            {
+                response = response.add_string_choice(truncate(&label, 100), shop.channel.clone());
+            }
+        }
+    }
+
+    if command.data.name == "cooldowns" && focused.name == "item" {
+        let query = focused.value.to_lowercase();
+        let choices = shop_file.shops.iter()
+            .flat_map(|shop| &shop.items)
+            .flat_map(|item| item.buttons.iter().map(move |button| (format!("{} ({})", item.name, button.text), &button.trigger)))
+            .filter(|(label, _)| query.is_empty() || label.to_lowercase().contains(&query))
+            .take(25);
+        for (label, trigger) in choices {
+            response = response.add_string_choice(truncate(&label, 100), trigger.clone());
+        }
+    }
+
+    let _ = command.create_response(&ctx.http, CreateInteractionResponse::Autocomplete(response)).await;
+}
+
+async fn handle_register(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState) {
+    let options = command.data.options();
+    let steam_id = match string_option(&options, "steam_id") {
+        Some(steam_id) => steam_id.trim(),
+        None => return,
+    };
+
+    if steam_id.is_empty() || !steam_id.chars().all(|c| c.is_ascii_digit()) {
+        reply(ctx, command, "❌ Steam ID ต้องเป็นตัวเลขเท่านั้น").await;
+        return;
+    }
+
+    let discord_id = command.user.id.to_string();
+    match shared_state.db.add_or_update_player(&discord_id, steam_id, 0) {
+        Ok(_) => reply(ctx, command, format!("✅ ลงทะเบียนสำเร็จ! Steam ID: {}", steam_id)).await,
+        Err(e) => reply(ctx, command, format!("❌ ลงทะเบียนไม่สำเร็จ: {:?}", e)).await,
+    }
+}
+
+async fn handle_coin(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState) {
+    let discord_id = command.user.id.to_string();
+    match shared_state.db.get_player_by_discord_id(&discord_id) {
+        Ok(Some(player)) => reply(ctx, command, format!("💰 คุณมี {} coins", player.coin)).await,
+        Ok(None) => reply(ctx, command, "❌ คุณยังไม่ได้ลงทะเบียน ใช้คำสั่ง /register").await,
+        Err(e) => reply(ctx, command, format!("❌ เกิดข้อผิดพลาด: {:?}", e)).await,
+    }
+}
+
+async fn handle_settings(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState) {
+    let discord_id = command.user.id.to_string();
+    let options = command.data.options();
+
+    if let Some(threshold) = integer_option(&options, "confirm_below") {
+        if let Err(e) = shared_state.db.set_confirm_threshold(&discord_id, threshold) {
+            reply(ctx, command, format!("❌ เกิดข้อผิดพลาด: {:?}", e)).await;
+            return;
+        }
+    }
+
+    let threshold = shared_state.db.get_confirm_threshold(&discord_id).unwrap_or(0);
+    let confirm_text = if threshold > 0 {
+        format!("ยืนยันก่อนซื้อเมื่อราคาตั้งแต่ {} coin ขึ้นไป", threshold)
+    } else {
+        "ยืนยันก่อนซื้อทุกครั้ง".to_string()
+    };
+
+    let cart_text = if shared_state.db.get_cart_mode(&discord_id).unwrap_or(false) {
+        "โหมดตะกร้า: เปิด (/cart mode เพื่อเปลี่ยน)"
+    } else {
+        "โหมดตะกร้า: ปิด (/cart mode เพื่อเปลี่ยน)"
+    };
+
+    reply(ctx, command, format!("⚙️ การตั้งค่าของคุณ\n• {}\n• {}", confirm_text, cart_text)).await;
+}
+
+async fn handle_addcoin(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState) {
+    if !require_permission(ctx, command, shared_state, Permission::ManageCoins).await {
+        return;
+    }
+
+    let options = command.data.options();
+    let (user, amount) = match (user_option(&options, "user"), integer_option(&options, "amount")) {
+        (Some(user), Some(amount)) => (user, amount),
+        _ => return,
+    };
+
+    let amount = match i32::try_from(amount) {
+        Ok(amount) => amount,
+        Err(_) => {
+            reply(ctx, command, "❌ จำนวน coin ไม่ถูกต้อง").await;
+            return;
+        }
+    };
+
+    let user_id = user.id.to_string();
+    let admin_id = command.user.id.to_string();
+    match shared_state.db.adjust_coin(&user_id, amount, LedgerReason::Admin, None, Some(&admin_id)) {
+        Ok(Some(balance)) => {
+            reply(ctx, command, format!("✅ เพิ่ม {} coins ให้ <@{}> สำเร็จ! (รวม: {} coins)", amount, user_id, balance)).await;
+        },
+        Ok(None) => reply(ctx, command, "❌ ผู้ใช้ยังไม่ได้ลงทะเบียน").await,
+        Err(e) => reply(ctx, command, format!("❌ ไม่สามารถอัปเดต coin: {:?}", e)).await,
+    }
+}
+
+async fn handle_updateshop(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState) {
+    if !require_permission(ctx, command, shared_state, Permission::ManageShop).await {
+        return;
+    }
+
+    let options = command.data.options();
+    let only_channel = string_option(&options, "shop");
+    let force = options.iter().any(|option| {
+        option.name == "full" && matches!(option.value, ResolvedValue::Boolean(true))
+    });
+
+    let _ = command.defer_ephemeral(&ctx.http).await;
+
+    let bot_id = ctx.cache.current_user().id;
+    let mut summary = SyncSummary::default();
+
+    for shop in &shared_state.shop_file().shops {
+        if only_channel.is_some_and(|channel| channel != shop.channel) {
+            continue;
+        }
+
+        let result = sync_shop(&ctx.http, shared_state, shop, bot_id, force).await;
+        summary.added.extend(result.added);
+        summary.edited.extend(result.edited);
+        summary.removed.extend(result.removed);
+        summary.unchanged += result.unchanged;
+        summary.failed.extend(result.failed);
+    }
+
+    info!(
+        "Shop sync by {}: {} added, {} edited, {} removed, {} unchanged, {} failed",
+        command.user.id, summary.added.len(), summary.edited.len(), summary.removed.len(), summary.unchanged, summary.failed.len()
+    );
+
+    let mut lines = vec![format!(
+        "✅ อัปเดตร้านค้าเรียบร้อย! เพิ่ม {} • แก้ไข {} • ลบ {} • ไม่เปลี่ยน {}",
+        summary.added.len(), summary.edited.len(), summary.removed.len(), summary.unchanged
+    )];
+    for (label, names) in [("➕ เพิ่ม", &summary.added), ("✏️ แก้ไข", &summary.edited), ("🗑️ ลบ", &summary.removed), ("❌ ล้มเหลว", &summary.failed)] {
+        if !names.is_empty() {
+            lines.push(format!("{}: {}", label, names.join(", ")));
+        }
+    }
+
+    let _ = command.edit_response(&ctx.http, EditInteractionResponse::new()
+        .content(truncate(&lines.join("\n"), 2000))
+    ).await;
+}
+
+pub async fn handle_destroy_command(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState) {
+    if !require_permission(ctx, command, shared_state, Permission::TriggerCleanup).await {
+        return;
+    }
+    
+    let profiles = &shared_state.config.cleanup_profiles;
+    let mut info_lines: Vec<String> = profiles.iter()
+        .map(|profile| format!("• {}: {}", profile.name, describe_schedule(profile)))
+        .collect();
+    info_lines.push("• คุณสามารถกดปุ่มเพื่อลบด้วยตนเองได้".to_string());
 
-pub async fn handle_destroy_command(ctx: &Context, msg: &Message) {
     let embed = CreateEmbed::new()
         .title("🧹 ระบบลบชุด")
         .description("กดปุ่มด้านล่างเพื่อลบชุดต่างๆ ในเกม")
         .color(0x00ff00)
-        .field(
-            "ℹ️ ข้อมูล",
-            "• ลบชุด/เสื้อผ้า: อัตโนมัติทุก 30 นาที\n• ลบวัสดุก่อสร้าง: อัตโนมัติทุก 120 นาที\n• คุณสามารถกดปุ่มเพื่อลบด้วยตนเองได้",
-            false
-        )
+        .field("ℹ️ ข้อมูล", info_lines.join("\n"), false)
         .footer(CreateEmbedFooter::new("© powered by TimeSkip"));
-    
-    let components = vec![
-        CreateActionRow::Buttons(vec![
-            CreateButton::new("destroy_type1")
-                .label("ลบชุด")
-                .style(ButtonStyle::Success),
-            CreateButton::new("destroy_type2")
-                .label("ลบวัสดุ")
-                .style(ButtonStyle::Danger),
-        ])
-    ];
-    
-    let _ = msg.channel_id.send_message(&ctx.http, CreateMessage::new()
-        .embed(embed)
-        .components(components)
-    ).await;
-}
\ No newline at end of file
+
+    let components: Vec<CreateActionRow> = profiles
+        .chunks(5)
+        .map(|row| {
+            CreateActionRow::Buttons(row.iter()
+                .map(|profile| CreateButton::new(profile.custom_id())
+                    .label(&profile.button_label)
+                    .style(button_style(&profile.button_style)))
+                .collect())
+        })
+        .collect();
+
+    let response = CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .embed(embed)
+            .components(components)
+    );
+    let _ = command.create_response(&ctx.http, response).await;
+}
+
+async fn handle_sendcommand(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState) {
+    if !require_permission(ctx, command, shared_state, Permission::SendRawCommands).await {
+        return;
+    }
+
+    let options = command.data.options();
+    let game_command = match string_option(&options, "command") {
+        Some(game_command) => game_command.trim().to_string(),
+        None => return,
+    };
+
+    info!("{} queued raw game command: {}", command.user.id, game_command);
+    shared_state.command_queue.lock().await.push(vec![game_command.clone()]);
+
+    reply(ctx, command, format!("✅ ส่งคำสั่งเข้าคิวแล้ว: `{}`", game_command)).await;
+}
+
+async fn handle_purchases(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState) {
+    if !require_permission(ctx, command, shared_state, Permission::ViewReports).await {
+        return;
+    }
+
+    let options = command.data.options();
+    let user_id = user_option(&options, "user").map(|user| user.id.to_string());
+    let limit = integer_option(&options, "limit").unwrap_or(10).clamp(1, 25) as u32;
+
+    match shared_state.db.get_recent_purchases(user_id.as_deref(), limit) {
+        Ok(logs) if logs.is_empty() => reply(ctx, command, "ไม่มีประวัติการซื้อ").await,
+        Ok(logs) => {
+            let lines: Vec<String> = logs.iter()
+                .map(|log| match &log.recipient_id {
+                    Some(recipient_id) => format!(
+                        "`{}` <@{}> 🎁→ <@{}> (`{}`) **{}** {} coins",
+                        log.timestamp, log.discord_id, recipient_id, log.steam_id, log.item_name, log.price
+                    ),
+                    None => format!(
+                        "`{}` <@{}> (`{}`) **{}** {} coins",
+                        log.timestamp, log.discord_id, log.steam_id, log.item_name, log.price
+                    ),
+                })
+                .collect();
+            let embed = CreateEmbed::new()
+                .title("🧾 ประวัติการซื้อล่าสุด")
+                .color(0x9900cc)
+                .description(truncate(&lines.join("\n"), 4000))
+                .footer(CreateEmbedFooter::new("© powered by TimeSkip"));
+            let response = CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .embed(embed)
+                    .ephemeral(true)
+            );
+            let _ = command.create_response(&ctx.http, response).await;
+        },
+        Err(e) => reply(ctx, command, format!("❌ เกิดข้อผิดพลาด: {:?}", e)).await,
+    }
+}
+
+/// Viewing needs `ViewReports`; clearing changes what a player may buy, so it needs `ManageShop`.
+async fn handle_cooldowns(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState) {
+    let options = command.data.options();
+    let (subcommand, sub_options) = match options.first() {
+        Some(ResolvedOption { name, value: ResolvedValue::SubCommand(sub_options), .. }) => (*name, sub_options),
+        _ => return,
+    };
+
+    let user = match user_option(sub_options, "user") {
+        Some(user) => user,
+        None => return,
+    };
+    let user_id = user.id.to_string();
+
+    match subcommand {
+        "view" => {
+            if !require_permission(ctx, command, shared_state, Permission::ViewReports).await {
+                return;
+            }
+
+            let cooldowns = shared_state.user_cooldowns(&user_id);
+            if cooldowns.is_empty() {
+                reply(ctx, command, format!("<@{}> ไม่มี cooldown ค้างอยู่", user_id)).await;
+                return;
+            }
+
+            let lines: Vec<String> = cooldowns.iter()
+                .map(|(trigger, remaining)| {
+                    let name = match find_button(&shared_state.shop_file().shops, trigger) {
+                        Some((item, button)) => format!("{} ({})", item.name, button.text),
+                        None => trigger.clone(),
+                    };
+                    format!("• **{}** — เหลือ {}", name, format_wait_time(remaining.as_secs() as i64))
+                })
+                .collect();
+            let embed = CreateEmbed::new()
+                .title(format!("⏳ Cooldown ของ {}", user.name))
+                .color(0x9900cc)
+                .description(truncate(&lines.join("\n"), 4000))
+                .footer(CreateEmbedFooter::new("© powered by TimeSkip"));
+            let response = CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .embed(embed)
+                    .ephemeral(true)
+            );
+            let _ = command.create_response(&ctx.http, response).await;
+        },
+        "clear" => {
+            if !require_permission(ctx, command, shared_state, Permission::ManageShop).await {
+                return;
+            }
+
+            let item = string_option(sub_options, "item");
+            let removed = shared_state.clear_cooldowns(&user_id, item);
+            info!("{} cleared {} cooldowns of {} (item: {:?})", command.user.id, removed, user_id, item);
+            reply(ctx, command, format!("✅ ล้าง cooldown ของ <@{}> แล้ว ({} รายการ)", user_id, removed)).await;
+        },
+        _ => {}
+    }
+}
+
+/// Managing grants is reserved for guild administrators so permissions can't escalate.
+async fn handle_permission(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState) {
+    if !is_guild_admin(command.member.as_deref()) {
+        reply(ctx, command, "❌ เฉพาะผู้ดูแลเซิร์ฟเวอร์เท่านั้นที่จัดการสิทธิ์ได้").await;
+        return;
+    }
+
+    let options = command.data.options();
+    let (subcommand, sub_options) = match options.first() {
+        Some(ResolvedOption { name, value: ResolvedValue::SubCommand(sub_options), .. }) => (*name, sub_options),
+        _ => return,
+    };
+
+    if subcommand == "list" {
+        let grants = match shared_state.db.list_permission_grants() {
+            Ok(grants) => grants,
+            Err(e) => {
+                reply(ctx, command, format!("❌ เกิดข้อผิดพลาด: {:?}", e)).await;
+                return;
+            }
+        };
+
+        let mut embed = CreateEmbed::new()
+            .title("🔐 สิทธิ์แอดมิน")
+            .description("ผู้ที่มีสิทธิ์ Administrator ในเซิร์ฟเวอร์ได้ทุกสิทธิ์อัตโนมัติ")
+            .color(0x00ff00)
+            .footer(CreateEmbedFooter::new("© powered by TimeSkip"));
+
+        for permission in Permission::ALL {
+            let subjects: Vec<String> = grants.iter()
+                .filter(|grant| grant.permission == permission.key())
+                .map(describe_grant)
+                .collect();
+            let value = if subjects.is_empty() { "-".to_string() } else { truncate(&subjects.join("\n"), 1024) };
+            embed = embed.field(format!("{} (`{}`)", permission.label(), permission.key()), value, false);
+        }
+
+        let response = CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .embed(embed)
+                .ephemeral(true)
+        );
+        let _ = command.create_response(&ctx.http, response).await;
+        return;
+    }
+
+    let permission = match string_option(sub_options, "permission").and_then(Permission::from_key) {
+        Some(permission) => permission,
+        None => return,
+    };
+
+    let (subject_type, subject_id, mention) = if let Some(user) = user_option(sub_options, "user") {
+        ("user", user.id.to_string(), format!("<@{}>", user.id))
+    } else if let Some(role) = role_option(sub_options, "role") {
+        ("role", role.id.to_string(), format!("<@&{}>", role.id))
+    } else {
+        reply(ctx, command, "❌ กรุณาเลือก user หรือ role").await;
+        return;
+    };
+
+    let result = if subcommand == "grant" {
+        shared_state.db.grant_permission(permission.key(), subject_type, &subject_id, &command.user.id.to_string())
+    } else {
+        shared_state.db.revoke_permission(permission.key(), subject_type, &subject_id)
+    };
+
+    match (subcommand, result) {
+        ("grant", Ok(true)) => reply(ctx, command, format!("✅ ให้สิทธิ์ {} กับ {} แล้ว", permission.label(), mention)).await,
+        ("grant", Ok(false)) => reply(ctx, command, format!("ℹ️ {} มีสิทธิ์ {} อยู่แล้ว", mention, permission.label())).await,
+        (_, Ok(true)) => reply(ctx, command, format!("✅ ถอนสิทธิ์ {} จาก {} แล้ว", permission.label(), mention)).await,
+        (_, Ok(false)) => reply(ctx, command, format!("ℹ️ {} ไม่มีสิทธิ์ {}", mention, permission.label())).await,
+        (_, Err(e)) => reply(ctx, command, format!("❌ เกิดข้อผิดพลาด: {:?}", e)).await,
+    }
+}
+
+/// `<@&role> • โดย <@admin> <t:...:R>`; grants moved over from the old `authorized_users`
+/// table name it instead of an admin.
+fn describe_grant(grant: &PermissionGrant) -> String {
+    let subject = match grant.subject_type.as_str() {
+        "role" => format!("<@&{}>", grant.subject_id),
+        _ => format!("<@{}>", grant.subject_id),
+    };
+    let granted_by = match grant.granted_by.parse::<u64>() {
+        Ok(_) => format!("<@{}>", grant.granted_by),
+        Err(_) => format!("`{}`", grant.granted_by),
+    };
+    format!("{} • โดย {} {}", subject, granted_by, discord_time(&grant.granted_at))
+}
+
+async fn handle_help(ctx: &Context, command: &CommandInteraction) {
+    let help_message = "
+**📋 คำสั่งที่ใช้ได้:**
+
+**สำหรับผู้เล่น:**
+`/register <steam_id>` - ลงทะเบียนเพื่อใช้งาน bot
+`/coin` - เช็คจำนวน coin ที่มี
+`/settings [confirm_below]` - ตั้งค่าการยืนยันก่อนซื้อ
+`/cart view|checkout|remove|clear|mode` - ตะกร้าสินค้า ซื้อหลายอย่างในครั้งเดียว
+`/shop search <text>` - ค้นหาสินค้าว่าซื้อได้ที่ช่องไหน
+`/gift <user> <item> [message]` - ซื้อสินค้าเป็นของขวัญให้ผู้เล่นคนอื่น
+`/stash view|mode` - คลังเก็บของ ซื้อไว้ก่อนแล้วค่อยรับเข้าเกม
+`/claim <id>` - รับของจากคลังเข้าเกม
+`/redeem <code>` - ใช้โค้ดรับ coin หรือส่วนลดการซื้อครั้งถัดไป
+
+**สำหรับ Admin:**
+`/updateshop [shop] [full]` - อัปเดตร้านค้าเฉพาะสินค้าที่เปลี่ยน (ทั้งหมด หรือเฉพาะร้านที่เลือก)
+`/shopedit item|button add|edit|disable|delete` - แก้ไขสินค้าและปุ่มซื้อ บันทึกลงไฟล์ร้านค้าทันที
+`/shopversions list|diff|rollback` - ดูประวัติการแก้ไขร้านค้าและย้อนกลับเวอร์ชัน
+`/addcoin <user> <amount>` - เพิ่ม coin ให้ผู้เล่น
+`/promocode create|list|info|disable` - สร้างและจัดการโค้ดโปรโมชั่น
+`/ledger [user] [limit]` - ดูประวัติการเปลี่ยนแปลง coin
+`/lootlog [user] [rare] [limit]` - ดูประวัติการเปิดกล่องสุ่ม
+`/destroy` - แสดงปุ่มลบไอเทม
+`/sendcommand <command>` - ส่งคำสั่งเข้าเกม
+`/purchases [user] [limit]` - ดูประวัติการซื้อ
+`/cooldowns view|clear <user> [item]` - ดูหรือล้าง cooldown การซื้อของผู้เล่น
+`/permission grant|revoke|list` - จัดการสิทธิ์ (เฉพาะ Administrator)
+
+**วิธีซื้อของ:**
+1. ลงทะเบียนด้วย `/register <steam_id>`
+2. ตรวจสอบ coin ด้วย `/coin`
+3. ไปที่ช่องร้านค้าและกดปุ่มซื้อ
+4. ตรวจสอบราคาแล้วกด \"ยืนยัน\"
+    ";
+    reply(ctx, command, help_message).await;
+}
+
+fn describe_schedule(profile: &CleanupProfile) -> String {
+    let mut description = if let Some(minutes) = profile.interval_minutes {
+        format!("อัตโนมัติทุก {} นาที", minutes)
+    } else if !profile.schedule.is_empty() {
+        format!("อัตโนมัติเวลา {}", profile.schedule.join(", "))
+    } else {
+        "กดปุ่มเท่านั้น".to_string()
+    };
+
+    if profile.cooldown_minutes > 0 {
+        description.push_str(&format!(" (คูลดาวน์ {} นาที)", profile.cooldown_minutes));
+    }
+    if profile.votes_required > 1 {
+        description.push_str(&format!(" ต้องกดโหวต {} คน", profile.votes_required));
+    }
+
+    description
+}
+
+fn button_style(style: &str) -> ButtonStyle {
+    match style {
+        "primary" => ButtonStyle::Primary,
+        "secondary" => ButtonStyle::Secondary,
+        "success" => ButtonStyle::Success,
+        _ => ButtonStyle::Danger,
+    }
+}
+
+fn permission_subcommand(name: &str, description: &str) -> CreateCommandOption {
+    let mut permission_option = CreateCommandOption::new(CommandOptionType::String, "permission", "สิทธิ์")
+        .required(true);
+    for permission in Permission::ALL {
+        permission_option = permission_option.add_string_choice(permission.label(), permission.key());
+    }
+
+    CreateCommandOption::new(CommandOptionType::SubCommand, name, description)
+        .add_sub_option(permission_option)
+        .add_sub_option(CreateCommandOption::new(CommandOptionType::User, "user", "ผู้ใช้"))
+        .add_sub_option(CreateCommandOption::new(CommandOptionType::Role, "role", "Role"))
+}
+
+/// Replies with a refusal and returns `false` when the caller lacks `permission`.
+pub async fn require_permission(
+    ctx: &Context,
+    command: &CommandInteraction,
+    shared_state: &SharedState,
+    permission: Permission,
+) -> bool {
+    if has_permission(shared_state, command.user.id, command.member.as_deref(), permission) {
+        return true;
+    }
+    reply(ctx, command, format!("❌ คุณไม่มีสิทธิ์ใช้คำสั่งนี้ (ต้องการสิทธิ์: {})", permission.label())).await;
+    false
+}
+
+pub async fn reply(ctx: &Context, command: &CommandInteraction, content: impl Into<String>) {
+    let response = CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .content(content)
+            .ephemeral(true)
+    );
+    let _ = command.create_response(&ctx.http, response).await;
+}
+
+pub fn string_option<'a>(options: &[ResolvedOption<'a>], name: &str) -> Option<&'a str> {
+    options.iter().find_map(|option| match option.value {
+        ResolvedValue::String(value) if option.name == name => Some(value),
+        _ => None,
+    })
+}
+
+pub fn integer_option(options: &[ResolvedOption<'_>], name: &str) -> Option<i64> {
+    options.iter().find_map(|option| match option.value {
+        ResolvedValue::Integer(value) if option.name == name => Some(value),
+        _ => None,
+    })
+}
+
+pub fn user_option<'a>(options: &[ResolvedOption<'a>], name: &str) -> Option<&'a User> {
+    options.iter().find_map(|option| match option.value {
+        ResolvedValue::User(user, _) if option.name == name => Some(user),
+        _ => None,
+    })
+}
+
+pub fn role_option<'a>(options: &[ResolvedOption<'a>], name: &str) -> Option<&'a Role> {
+    options.iter().find_map(|option| match option.value {
+        ResolvedValue::Role(role) if option.name == name => Some(role),
+        _ => None,
+    })
+}
+
+pub fn member_roles(command: &CommandInteraction) -> &[RoleId] {
+    command.member.as_ref().map(|member| member.roles.as_slice()).unwrap_or(&[])
+}
+
+pub fn truncate(text: &str, max_chars: usize) -> String {
+    text.chars().take(max_chars).collect()
+}
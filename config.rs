@@ -1,5 +1,13 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+
+use crate::kits::{resolve_commands, Kits};
+use crate::promotions::{Promotions, PROMOTION_TIME_FORMAT};
+use crate::scum_items::{describe, find_unknown_items, ItemCatalog};
+use crate::shopfile::{find_shop_file, read_shop_file};
+use chrono::{DateTime, NaiveDateTime, NaiveTime, TimeZone, Weekday};
+use chrono_tz::Asia::Bangkok;
+use chrono_tz::Tz;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ShopItem {
@@ -7,26 +15,197 @@ pub struct ShopItem {
     pub price: u32,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub image: Option<String>,
+    /// Groups items in the catalog layout's select menu.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// Commands for every button that doesn't list its own; use `{quantity}` to scale them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub commands: Vec<ButtonCommand>,
     pub buttons: Vec<ShopButton>,
+    /// Hidden from the shop but kept in the file, set with `/shopedit item disable`.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub disabled: bool,
+    /// Makes the item a loot box: every unit bought rolls one of these outcomes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loot: Option<LootTable>,
+}
+
+impl ShopItem {
+    /// Buttons that are shown and can be bought.
+    pub fn active_buttons(&self) -> impl Iterator<Item = &ShopButton> {
+        self.buttons.iter().filter(|button| !button.disabled)
+    }
+
+    /// Shown in the shop: enabled and with at least one button to buy it with.
+    pub fn is_listed(&self) -> bool {
+        !self.disabled && self.active_buttons().next().is_some()
+    }
+
+    /// Every command buying this item may run: its buttons' and its loot outcomes'.
+    pub fn all_commands(&self) -> impl Iterator<Item = &ButtonCommand> {
+        let loot = self.loot.iter().flat_map(|loot| &loot.outcomes).flat_map(|outcome| &outcome.resolved_commands);
+        self.buttons.iter().flat_map(|button| &button.resolved_commands).chain(loot)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ShopButton {
     pub text: String,
     pub trigger: String,
-    pub commands: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub commands: Vec<ButtonCommand>,
+    /// What a purchase runs: `commands` (or the item's) with kits expanded. Filled at load.
+    #[serde(skip)]
+    pub resolved_commands: Vec<ButtonCommand>,
     #[serde(default = "default_quantity")]
     pub quantity: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cooldown_seconds: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daily_limit: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weekly_limit: Option<u32>,
+    /// Global stock shared by all players; `None` means unlimited.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stock: Option<u32>,
+    /// Refill `stock` back to its maximum every N hours.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub restock_hours: Option<u64>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub disabled: bool,
 }
 
 fn default_quantity() -> u32 { 1 }
 
+/// Outcomes of a loot box, picked at random by weight:
+/// `{"pity": 20, "outcomes": [{"name": "AK47", "rarity": "Legendary", "weight": 1, "rare": true, "commands": [...]}, ...]}`.
+/// Outcome commands run once per unit bought, with `{quantity}` as 1.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LootTable {
+    pub outcomes: Vec<LootOutcome>,
+    /// A player's N-th roll in a row without a rare outcome is always rare.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pity: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LootOutcome {
+    pub name: String,
+    /// Shown to players, e.g. `Common` or `Legendary`.
+    pub rarity: String,
+    pub weight: u32,
+    /// Rare drops reset the pity counter and are announced in game.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub rare: bool,
+    pub commands: Vec<ButtonCommand>,
+    /// `commands` with kits expanded. Filled at load.
+    #[serde(skip)]
+    pub resolved_commands: Vec<ButtonCommand>,
+}
+
+fn is_false(value: &bool) -> bool { !*value }
+
+/// A game command run for a purchase. A plain string is delivered at the buyer; the object
+/// form picks another target, e.g. `{"command": "#ChangeFamePoints 50 {steam_id}", "delivery": "none"}`.
+/// `{"kit": "scar_set", "count": 5}` pulls in a kit from `botshop.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ButtonCommand {
+    Plain(String),
+    Targeted {
+        command: String,
+        #[serde(default)]
+        delivery: Delivery,
+    },
+    Kit {
+        kit: String,
+        #[serde(default = "default_quantity")]
+        count: u32,
+    },
+}
+
+impl ButtonCommand {
+    /// Empty for a kit reference; those are expanded away at load.
+    pub fn command(&self) -> &str {
+        match self {
+            ButtonCommand::Plain(command) => command,
+            ButtonCommand::Targeted { command, .. } => command,
+            ButtonCommand::Kit { .. } => "",
+        }
+    }
+
+    pub fn delivery(&self) -> Delivery {
+        match self {
+            ButtonCommand::Plain(_) => Delivery::Player,
+            ButtonCommand::Targeted { delivery, .. } => delivery.clone(),
+            ButtonCommand::Kit { .. } => Delivery::None,
+        }
+    }
+}
+
+/// Where a command's result appears in the world.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Delivery {
+    /// Next to the buyer (`Location <steam_id>`).
+    #[default]
+    Player,
+    /// Fixed world coordinates, `{"coords": [x, y, z]}`.
+    Coords([f64; 3]),
+    /// A named location from `locations.json`, `{"preset": "trader_a"}`.
+    Preset(String),
+    /// Run the command as is, for commands that don't take a location.
+    None,
+}
+
+/// Cooldown applied to buttons that don't set `cooldown_seconds`.
+const DEFAULT_BUTTON_COOLDOWN_SECS: u64 = 20;
+
+impl ShopButton {
+    pub fn cooldown(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.cooldown_seconds.unwrap_or(DEFAULT_BUTTON_COOLDOWN_SECS))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ShopConfig {
     pub channel: String,
+    #[serde(default, skip_serializing_if = "ShopLayout::is_default")]
+    pub layout: ShopLayout,
+    /// Title of the catalog message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Used for items without an image, or whose image file is missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback_image: Option<String>,
     pub items: Vec<ShopItem>,
 }
 
+/// How a shop channel is presented.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShopLayout {
+    /// One message per item with its buy buttons.
+    #[default]
+    Items,
+    /// One message with a category menu; items are browsed page by page in an ephemeral view.
+    Catalog,
+}
+
+impl ShopLayout {
+    fn is_default(&self) -> bool {
+        *self == ShopLayout::default()
+    }
+}
+
+/// The shop file: either a plain list of shops or this object, which adds reusable kits.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ShopFile {
+    #[serde(default, skip_serializing_if = "Kits::is_empty")]
+    pub kits: Kits,
+    pub shops: Vec<ShopConfig>,
+}
+
 #[derive(Debug, Clone)]
 pub struct VipRole {
     pub tier: u8,
@@ -34,46 +213,151 @@ pub struct VipRole {
     pub name: String,
 }
 
+/// A named cleanup run: a list of item IDs destroyed with `#DestroyAllItemsWithinRadius`,
+/// optionally repeated on an interval or at fixed wall-clock times (server timezone).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CleanupProfile {
+    pub id: String,
+    pub name: String,
+    pub button_label: String,
+    #[serde(default = "default_button_style")]
+    pub button_style: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval_minutes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schedule: Vec<String>,
+    #[serde(default = "default_cleanup_radius")]
+    pub radius: String,
+    pub announce_start: String,
+    pub announce_end: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_announce_start: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_announce_end: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required_roles: Vec<u64>,
+    #[serde(default)]
+    pub cooldown_minutes: u64,
+    #[serde(default = "default_votes_required")]
+    pub votes_required: u32,
+    #[serde(default = "default_vote_window_seconds")]
+    pub vote_window_seconds: u64,
+    #[serde(default)]
+    pub maintenance_policy: MaintenancePolicy,
+    #[serde(default = "default_maintenance_margin_minutes")]
+    pub maintenance_margin_minutes: u64,
+    pub items: Vec<String>,
+    #[serde(skip)]
+    pub schedule_times: Vec<NaiveTime>,
+}
+
+fn default_button_style() -> String { "danger".to_string() }
+
+fn default_votes_required() -> u32 { 1 }
+
+fn default_vote_window_seconds() -> u64 { 120 }
+
+fn default_maintenance_margin_minutes() -> u64 { 10 }
+
+/// What the scheduler does with a cleanup that falls inside (or just before) a restart window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MaintenancePolicy {
+    /// Run right after the server comes back.
+    #[default]
+    Shift,
+    /// Drop this occurrence and wait for the next one.
+    Skip,
+}
+
+fn default_cleanup_radius() -> String { "9999999999999999999999".to_string() }
+
+impl CleanupProfile {
+    pub fn custom_id(&self) -> String {
+        format!("destroy:{}", self.id)
+    }
+
+    pub fn commands(&self) -> Vec<String> {
+        self.items.iter()
+            .map(|item| format!("#DestroyAllItemsWithinRadius {} {}", item, self.radius))
+            .collect()
+    }
+
+    pub fn can_trigger(&self, roles: &[u64]) -> bool {
+        self.required_roles.is_empty() || roles.iter().any(|role| self.required_roles.contains(role))
+    }
+
+    pub fn start_announcement(&self, automatic: bool) -> String {
+        let text = match (&self.auto_announce_start, automatic) {
+            (Some(text), true) => text,
+            _ => &self.announce_start,
+        };
+        format!("#Announce {}", text)
+    }
+
+    pub fn end_announcement(&self, automatic: bool) -> String {
+        let text = match (&self.auto_announce_end, automatic) {
+            (Some(text), true) => text,
+            _ => &self.announce_end,
+        };
+        format!("#Announce {}", text)
+    }
+}
+
 pub struct Config {
-    pub shop_data: Vec<ShopConfig>,
+    /// The shop file as loaded at startup. `SharedState` takes it over, since `/shopedit`
+    /// can change it while the bot runs; read the shops from `SharedState::shop_file`.
+    pub shop_file: ShopFile,
+    /// Where `/shopedit` writes changes back to.
+    pub shop_path: &'static str,
     pub vip_roles: HashMap<u64, VipRole>,
     pub exempt_channel_id: u64,
-    pub destroy_commands_type1: Vec<String>,
-    pub destroy_commands_type2: Vec<String>,
-    pub special_commands: Vec<String>,
+    pub admin_channel_id: u64,
+    pub stash_expiry_days: u32,
+    pub stash_expiry_refund_percent: u32,
+    pub cleanup_profiles: Vec<CleanupProfile>,
+    /// Named delivery points from `locations.json`, `{"trader_a": [x, y, z]}`.
+    pub locations: HashMap<String, [f64; 3]>,
+    /// Sales and happy hours from `promotions.json`.
+    pub promotions: Promotions,
+    pub restart_times: Vec<NaiveTime>,
+    pub resume_times: Vec<NaiveTime>,
 }
 
 impl Config {
     pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
-        println!("Loading shop data from botshop.json...");
+        let shop_path = find_shop_file()?;
+        println!("Loading shop data from {}...", shop_path);
         
-        if !std::path::Path::new("botshop.json").exists() {
-            return Err("botshop.json file not found!".into());
-        }
-
-        let shop_data_str = match std::fs::read_to_string("botshop.json") {
-            Ok(data) => {
-                println!("Shop data file read successfully");
-                data
-            },
-            Err(e) => {
-                return Err(format!("Failed to read botshop.json: {}", e).into());
-            }
-        };
+        let mut shop_file = read_shop_file(shop_path)?;
+        
+        println!("Loaded {} shops and {} kits", shop_file.shops.len(), shop_file.kits.len());
+        
+        let locations = load_locations()?;
+        println!("Loaded {} delivery locations", locations.len());
         
-        println!("Parsing JSON data...");
+        validate_shop_file(&mut shop_file, &locations)?;
         
-        let shop_data: Vec<ShopConfig> = match serde_json::from_str(&shop_data_str) {
-            Ok(data) => {
-                println!("JSON parsed successfully");
-                data
-            },
-            Err(e) => {
-                return Err(format!("Failed to parse botshop.json: {}", e).into());
+        let cleanup_profiles = load_cleanup_profiles()?;
+        println!("Loaded {} cleanup profiles", cleanup_profiles.len());
+        
+        let promotions = load_promotions()?;
+        println!("Loaded {} promotions", promotions.promotions.len());
+        for promotion in &promotions.promotions {
+            for channel in promotion.shops.iter().filter(|channel| !shop_file.shops.iter().any(|shop| shop.channel == **channel)) {
+                println!("⚠️ Promotion '{}' lists unknown shop channel '{}'", promotion.id, channel);
             }
-        };
+            for name in promotion.items.iter().filter(|name| !shop_file.shops.iter().flat_map(|shop| &shop.items).any(|item| item.name == **name)) {
+                println!("⚠️ Promotion '{}' lists unknown item '{}'", promotion.id, name);
+            }
+        }
         
-        println!("Loaded {} shops", shop_data.len());
+        // Unknown names only warn: the bundled catalog can lag behind game updates.
+        let item_catalog = ItemCatalog::load()?;
+        println!("Loaded {} SCUM items", item_catalog.item_count());
+        for unknown in find_unknown_items(&item_catalog, &shop_file.shops, &cleanup_profiles) {
+            println!("⚠️ {}", describe(&unknown));
+        }
         
         let mut vip_roles = HashMap::new();
         vip_roles.insert(1375091477448888412, VipRole { tier: 1, discount: 0.0, name: "Silver".to_string() });
@@ -82,99 +366,289 @@ impl Config {
         vip_roles.insert(1381346983649874030, VipRole { tier: 4, discount: 0.60, name: "Diamond".to_string() });
         
         Ok(Config {
-            shop_data,
+            shop_file,
+            shop_path,
             vip_roles,
             exempt_channel_id: 1381383699320537209,
-            destroy_commands_type1: vec![
-                "#DestroyAllItemsWithinRadius Rag_Stripes 9999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Rags 99999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Peniswarmer_01 99999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Wool_Gloves_01_01 99999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Inmate_Hoodie_01 99999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Inmate_shirt_01 99999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Scum_Shirt_Event_Black 99999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Scum_Shirt_Event_White 999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Scum_Shirt_Event_Orange 999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Inmate_pants 99999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Underpants_01 99999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Scum_Shirt_Supporter_Pack_Black_01 99999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius F_Undershirt_Bra_01 999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Scum_Shirt_Event_Black 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Boxer_Briefs_01 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius HighTop_Shoes 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Sock_01 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Danny_Trejo_Vest 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Danny_Trejo_Pants 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius 1H_DannyMachete 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Danny_Trejo_Glove_01 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Danny_Trejo_Boots_01 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Military_Beanie_01 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Military_Beanie_02 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Parachute 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Military_Mask_02 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius F_Bra_Supporter_01 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Undershirt_01 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius LuisMoncada_Jacket 999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius LuisMoncada_Pants 999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius LuisMoncada_Boots 999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius 2H_La_Hacha_Axe 999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Raymond_Cruz_Boots 999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Raymond_Cruz_Hat 999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Raymond_Cruz_Pants 999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Raymond_Cruz_Shirt 999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius 1H_RaymondCruz_Knife 999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Undershirt_01 999999999999999999999".to_string(),
+            admin_channel_id: 0, // ใส่ channel id สำหรับส่งรายงานให้แอดมิน (0 = ปิด)
+            stash_expiry_days: 14, // ของในคลังที่ไม่ได้กดรับภายในกี่วันจะหมดอายุ
+            stash_expiry_refund_percent: 50, // คืน coin กี่ % เมื่อของในคลังหมดอายุ
+            cleanup_profiles,
+            locations,
+            promotions,
+            restart_times: vec![
+                NaiveTime::from_hms_opt(23, 58, 0).unwrap(),
+                NaiveTime::from_hms_opt(3, 58, 0).unwrap(),
+                NaiveTime::from_hms_opt(7, 58, 0).unwrap(),
+                NaiveTime::from_hms_opt(11, 58, 0).unwrap(),
+                NaiveTime::from_hms_opt(15, 58, 0).unwrap(),
+                NaiveTime::from_hms_opt(19, 58, 0).unwrap(),
+                NaiveTime::from_hms_opt(21, 58, 0).unwrap(),
             ],
-            destroy_commands_type2: vec![
-                "#DestroyAllItemsWithinRadius Rope1 99999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Paper 999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius PETBottle04 999999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Sock_01 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Wooden_Stick 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Bundle_Wooden_Plank 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Wooden_Log_02 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Wooden_Log_01 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Long_wooden_stick 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Paper 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Wooden_Plank 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Military_Beanie_01 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Military_Beanie_02 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Parachute 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Military_Mask_02 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius F_Bra_Supporter_01 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Undershirt_01 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Wooden_Log_Small_01 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Wooden_Log_Small_02 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Wooden_Log_Small_03 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Metal_Scrap_02 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Metal_Scrap_03 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Metal_Scrap_01 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Metal_Scrap_04 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Metal_Scrap_05 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius PETBottle01 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Brick 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius 2H_Axe 9999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius 2H_La_Hacha_Axe 999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius 1H_RaymondCruz_Knife 999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Inmate_Hoodie_01 999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Wool_Gloves_01_01 999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Undershirt_01 999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Bolts_Package_Box 999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Rope 999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Nails_Package_Box 999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius CementBag 999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius GravelBag 999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius SandBag 999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Barbed_Wire 999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Sledgehammer 999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Nails 999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius Bolts 999999999999999999999".to_string(),
-                "#DestroyAllItemsWithinRadius EmptyBag 999999999999999999999".to_string(),
-            ],
-            special_commands: vec![
-                "ChangeCurrencyBalance".to_string(),
-                "ChangeFamePoints".to_string(),
+            resume_times: vec![
+                NaiveTime::from_hms_opt(0, 6, 0).unwrap(),
+                NaiveTime::from_hms_opt(4, 6, 0).unwrap(),
+                NaiveTime::from_hms_opt(8, 6, 0).unwrap(),
+                NaiveTime::from_hms_opt(12, 6, 0).unwrap(),
+                NaiveTime::from_hms_opt(16, 6, 0).unwrap(),
+                NaiveTime::from_hms_opt(20, 6, 0).unwrap(),
+                NaiveTime::from_hms_opt(22, 6, 0).unwrap(),
             ],
         })
     }
-}
\ No newline at end of file
+}
+
+/// Expands kits into every button's `resolved_commands` and checks what a purchase would run.
+/// Used at startup and before `/shopedit` writes a change.
+/// Custom ID prefixes of the bot's own buttons and menus, which are routed before shop buttons.
+pub const RESERVED_TRIGGER_PREFIXES: [&str; 5] = ["destroy:", "shop_category:", "shop_page:", "buy_confirm:", "buy_cancel:"];
+
+pub fn validate_shop_file(file: &mut ShopFile, locations: &HashMap<String, [f64; 3]>) -> Result<(), String> {
+    resolve_commands(&mut file.shops, &file.kits)?;
+    
+    let mut triggers = HashSet::new();
+    for shop in &file.shops {
+        for item in &shop.items {
+            for button in &item.buttons {
+                if !triggers.insert(button.trigger.as_str()) {
+                    return Err(format!("Duplicate button trigger '{}'", button.trigger));
+                }
+                if let Some(prefix) = RESERVED_TRIGGER_PREFIXES.iter().find(|prefix| button.trigger.starts_with(*prefix)) {
+                    return Err(format!("Button '{}' of '{}' starts with '{}', which the bot uses itself", button.trigger, item.name, prefix));
+                }
+                if button.restock_hours.is_some() && button.stock.is_none() {
+                    return Err(format!("Button '{}' of '{}' has restock_hours but no stock", button.trigger, item.name));
+                }
+                if button.restock_hours == Some(0) {
+                    return Err(format!("Button '{}' of '{}' has restock_hours = 0", button.trigger, item.name));
+                }
+                if button.resolved_commands.is_empty() && item.loot.is_none() {
+                    return Err(format!("Button '{}' of '{}' has no commands", button.trigger, item.name));
+                }
+                for command in &button.resolved_commands {
+                    if let Err(e) = validate_command(command, locations) {
+                        return Err(format!("Button '{}' of '{}': {}", button.trigger, item.name, e));
+                    }
+                }
+            }
+            if let Some(loot) = &item.loot {
+                validate_loot(loot, locations).map_err(|e| format!("Loot of '{}': {}", item.name, e))?;
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+fn validate_loot(loot: &LootTable, locations: &HashMap<String, [f64; 3]>) -> Result<(), String> {
+    if loot.outcomes.is_empty() {
+        return Err("no outcomes".to_string());
+    }
+    if loot.pity == Some(0) {
+        return Err("pity must be at least 1".to_string());
+    }
+    if loot.pity.is_some() && !loot.outcomes.iter().any(|outcome| outcome.rare) {
+        return Err("pity is set but no outcome is rare".to_string());
+    }
+    
+    let mut names = HashSet::new();
+    for outcome in &loot.outcomes {
+        if !names.insert(outcome.name.as_str()) {
+            return Err(format!("duplicate outcome '{}'", outcome.name));
+        }
+        if outcome.rarity.trim().is_empty() {
+            return Err(format!("outcome '{}' has no rarity", outcome.name));
+        }
+        if outcome.weight == 0 {
+            return Err(format!("outcome '{}' has weight 0", outcome.name));
+        }
+        if outcome.resolved_commands.is_empty() {
+            return Err(format!("outcome '{}' has no commands", outcome.name));
+        }
+        for command in &outcome.resolved_commands {
+            validate_command(command, locations).map_err(|e| format!("outcome '{}': {}", outcome.name, e))?;
+        }
+    }
+    
+    Ok(())
+}
+
+fn validate_command(command: &ButtonCommand, locations: &HashMap<String, [f64; 3]>) -> Result<(), String> {
+    let text = command.command().trim();
+    if text.is_empty() {
+        return Err("empty command".to_string());
+    }
+    if let Err(e) = crate::template::validate(text) {
+        return Err(format!("'{}': {}", text, e));
+    }
+    
+    match command.delivery() {
+        Delivery::None => {},
+        _ if text.contains("Location") => {
+            return Err(format!("'{}' already has a Location, set \"delivery\": \"none\"", text));
+        },
+        Delivery::Player => {},
+        Delivery::Coords(coords) => {
+            if coords.iter().any(|value| !value.is_finite()) {
+                return Err(format!("'{}' has invalid coords {:?}", text, coords));
+            }
+        },
+        Delivery::Preset(name) => {
+            if !locations.contains_key(&name) {
+                return Err(format!("'{}' uses unknown location preset '{}'", text, name));
+            }
+        },
+    }
+    
+    Ok(())
+}
+
+/// `locations.json` is optional; without it only `player`, `coords` and `none` deliveries work.
+fn load_locations() -> Result<HashMap<String, [f64; 3]>, Box<dyn std::error::Error>> {
+    if !std::path::Path::new("locations.json").exists() {
+        return Ok(HashMap::new());
+    }
+    
+    let data = match std::fs::read_to_string("locations.json") {
+        Ok(data) => data,
+        Err(e) => {
+            return Err(format!("Failed to read locations.json: {}", e).into());
+        }
+    };
+    
+    let locations: HashMap<String, [f64; 3]> = match serde_json::from_str(&data) {
+        Ok(locations) => locations,
+        Err(e) => {
+            return Err(format!("Failed to parse locations.json: {}", e).into());
+        }
+    };
+    
+    for (name, coords) in &locations {
+        if coords.iter().any(|value| !value.is_finite()) {
+            return Err(format!("Location '{}' has invalid coords {:?}", name, coords).into());
+        }
+    }
+    
+    Ok(locations)
+}
+
+/// `promotions.json` is optional; without it prices only get VIP discounts.
+fn load_promotions() -> Result<Promotions, Box<dyn std::error::Error>> {
+    if !std::path::Path::new("promotions.json").exists() {
+        return Ok(Promotions::default());
+    }
+    
+    let data = match std::fs::read_to_string("promotions.json") {
+        Ok(data) => data,
+        Err(e) => {
+            return Err(format!("Failed to read promotions.json: {}", e).into());
+        }
+    };
+    
+    let mut promotions: Promotions = match serde_json::from_str(&data) {
+        Ok(promotions) => promotions,
+        Err(e) => {
+            return Err(format!("Failed to parse promotions.json: {}", e).into());
+        }
+    };
+    
+    let mut seen_ids = HashSet::new();
+    for promotion in &mut promotions.promotions {
+        if !seen_ids.insert(promotion.id.clone()) {
+            return Err(format!("Duplicate promotion id '{}'", promotion.id).into());
+        }
+        if promotion.percent_off == 0 || promotion.percent_off > 100 {
+            return Err(format!("Promotion '{}' has percent_off = {} (expected 1-100)", promotion.id, promotion.percent_off).into());
+        }
+        
+        promotion.starts_at = parse_promotion_time(&promotion.id, promotion.start.as_deref())?;
+        promotion.ends_at = parse_promotion_time(&promotion.id, promotion.end.as_deref())?;
+        if let (Some(starts_at), Some(ends_at)) = (promotion.starts_at, promotion.ends_at) {
+            if starts_at >= ends_at {
+                return Err(format!("Promotion '{}' ends before it starts", promotion.id).into());
+            }
+        }
+        
+        for hours in &mut promotion.hours {
+            for (text, time) in [(&hours.start, &mut hours.start_time), (&hours.end, &mut hours.end_time)] {
+                *time = match NaiveTime::parse_from_str(text, "%H:%M") {
+                    Ok(t) => t,
+                    Err(_) => {
+                        return Err(format!("Promotion '{}' has invalid happy hour time '{}' (expected HH:MM)", promotion.id, text).into());
+                    }
+                };
+            }
+            
+            hours.weekdays.clear();
+            for day in &hours.days {
+                match day.parse::<Weekday>() {
+                    Ok(weekday) => hours.weekdays.push(weekday),
+                    Err(_) => {
+                        return Err(format!("Promotion '{}' has invalid day '{}' (expected mon-sun)", promotion.id, day).into());
+                    }
+                }
+            }
+        }
+    }
+    
+    Ok(promotions)
+}
+
+fn parse_promotion_time(id: &str, value: Option<&str>) -> Result<Option<DateTime<Tz>>, String> {
+    let value = match value {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    NaiveDateTime::parse_from_str(value, PROMOTION_TIME_FORMAT)
+        .ok()
+        .and_then(|at| Bangkok.from_local_datetime(&at).single())
+        .map(Some)
+        .ok_or_else(|| format!("Promotion '{}' has invalid time '{}' (expected YYYY-MM-DD HH:MM)", id, value))
+}
+
+pub fn load_cleanup_profiles() -> Result<Vec<CleanupProfile>, Box<dyn std::error::Error>> {
+    println!("Loading cleanup profiles from cleanup.json...");
+    
+    let data = match std::fs::read_to_string("cleanup.json") {
+        Ok(data) => data,
+        Err(e) => {
+            return Err(format!("Failed to read cleanup.json: {}", e).into());
+        }
+    };
+    
+    let mut profiles: Vec<CleanupProfile> = match serde_json::from_str(&data) {
+        Ok(profiles) => profiles,
+        Err(e) => {
+            return Err(format!("Failed to parse cleanup.json: {}", e).into());
+        }
+    };
+    
+    let mut seen_ids = HashSet::new();
+    for profile in &mut profiles {
+        if !seen_ids.insert(profile.id.clone()) {
+            return Err(format!("Duplicate cleanup profile id '{}'", profile.id).into());
+        }
+        if profile.interval_minutes.is_some() && !profile.schedule.is_empty() {
+            return Err(format!("Cleanup profile '{}' cannot have both interval_minutes and schedule", profile.id).into());
+        }
+        if profile.interval_minutes == Some(0) {
+            return Err(format!("Cleanup profile '{}' has interval_minutes = 0", profile.id).into());
+        }
+        if profile.votes_required == 0 {
+            return Err(format!("Cleanup profile '{}' has votes_required = 0", profile.id).into());
+        }
+        
+        profile.schedule_times.clear();
+        for time in &profile.schedule {
+            match NaiveTime::parse_from_str(time, "%H:%M") {
+                Ok(t) => profile.schedule_times.push(t),
+                Err(_) => {
+                    return Err(format!("Cleanup profile '{}' has invalid schedule time '{}' (expected HH:MM)", profile.id, time).into());
+                }
+            }
+        }
+        profile.schedule_times.sort();
+    }
+    
+    Ok(profiles)
+}
@@ -1,12 +1,278 @@
-use rusqlite::{Connection, Result, params, OptionalExtension}; 
+use rusqlite::{Connection, Result, Transaction, params, OptionalExtension}; 
+use std::collections::HashMap;
 use std::sync::Mutex;
 
+use crate::permissions::Permission;
+
+/// Format of every timestamp column, matching SQLite's `CURRENT_TIMESTAMP`.
+pub const DB_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
 pub struct Player {
     pub discord_id: String,
     pub steam_id: String,
     pub coin: i32,
 }
 
+pub struct PermissionGrant {
+    pub permission: String,
+    pub subject_type: String,
+    pub subject_id: String,
+    pub granted_by: String,
+    pub granted_at: String,
+}
+
+pub struct PurchaseLog {
+    pub timestamp: String,
+    pub discord_id: String,
+    pub steam_id: String,
+    pub item_name: String,
+    pub price: i32,
+    /// Set when `discord_id` paid for an item delivered to someone else.
+    pub recipient_id: Option<String>,
+}
+
+/// A paid item waiting in a player's stash for `/claim`.
+pub struct StashItem {
+    pub id: i64,
+    pub discord_id: String,
+    /// Who paid for it and gets refunds: `discord_id` itself, or the sender of a gift.
+    pub paid_by: String,
+    pub trigger: String,
+    pub item_name: String,
+    pub price: i32,
+    pub created_at: String,
+    pub expires_at: String,
+    pub purchase_id: Option<i64>,
+    /// The button's quantity and resolved commands (JSON) when it was bought, so a later shop
+    /// edit doesn't change what's delivered. `None` for entries stored before they were kept.
+    pub quantity: Option<u32>,
+    pub commands: Option<String>,
+    /// Bought from a loot box, which rolls on its item's table when claimed.
+    pub loot_box: bool,
+}
+
+/// A posted shop item message, keyed by channel and item name.
+pub struct ShopMessage {
+    pub item_key: String,
+    pub message_id: String,
+    pub content_hash: String,
+}
+
+/// A snapshot of the shop file, saved whenever it changes.
+pub struct ShopVersion {
+    pub id: i64,
+    pub created_at: String,
+    /// Discord user ID of the editor, or `file` when the change was found on disk at startup.
+    pub author: String,
+    pub summary: String,
+    /// The whole shop file as JSON.
+    pub content: String,
+    /// Unified diff against the previous version; empty for the first one.
+    pub diff: String,
+}
+
+pub struct PurchaseLine {
+    pub item_name: String,
+    pub trigger: String,
+    pub price: i32,
+    pub daily_limit: Option<u32>,
+    pub weekly_limit: Option<u32>,
+    pub limited_stock: bool,
+    pub recipient_id: Option<String>,
+    pub gift_message: Option<String>,
+    /// When set, the item goes to the stash of the recipient (or buyer) until this time
+    /// instead of being delivered.
+    pub stash_until: Option<String>,
+    /// Kept with a stash entry: the button's quantity and resolved commands as JSON, and
+    /// whether the item is a loot box.
+    pub quantity: u32,
+    pub commands: String,
+    pub loot_box: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum LimitPeriod {
+    Daily,
+    Weekly,
+}
+
+impl LimitPeriod {
+    pub fn label(&self) -> &'static str {
+        match self {
+            LimitPeriod::Daily => "ต่อวัน",
+            LimitPeriod::Weekly => "ต่อสัปดาห์",
+        }
+    }
+    
+    fn sqlite_window(&self) -> &'static str {
+        match self {
+            LimitPeriod::Daily => "-1 day",
+            LimitPeriod::Weekly => "-7 days",
+        }
+    }
+}
+
+/// Result of a purchase transaction. Anything other than `Charged` means nothing was written.
+pub enum PurchaseOutcome {
+    /// Coins left, and the `purchase_logs` id of each line in order.
+    Charged { remaining: i32, purchase_ids: Vec<i64> },
+    InsufficientCoins,
+    LimitReached { item_name: String, period: LimitPeriod, limit: u32 },
+    OutOfStock { item_name: String },
+    /// The discount voucher was used by another purchase in the meantime.
+    VoucherUsed,
+}
+
+pub struct CartItem {
+    pub id: i64,
+    pub trigger: String,
+}
+
+/// Why a player's coins changed, stored as `coin_ledger.reason`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LedgerReason {
+    Purchase,
+    Admin,
+    StashRefund,
+    StashExpired,
+    PromoCode,
+}
+
+impl LedgerReason {
+    pub const ALL: [LedgerReason; 5] = [
+        LedgerReason::Purchase,
+        LedgerReason::Admin,
+        LedgerReason::StashRefund,
+        LedgerReason::StashExpired,
+        LedgerReason::PromoCode,
+    ];
+    
+    pub fn key(&self) -> &'static str {
+        match self {
+            LedgerReason::Purchase => "purchase",
+            LedgerReason::Admin => "admin",
+            LedgerReason::StashRefund => "stash_refund",
+            LedgerReason::StashExpired => "stash_expired",
+            LedgerReason::PromoCode => "promo_code",
+        }
+    }
+    
+    pub fn label(&self) -> &'static str {
+        match self {
+            LedgerReason::Purchase => "ซื้อสินค้า",
+            LedgerReason::Admin => "แอดมินปรับ coin",
+            LedgerReason::StashRefund => "คืนเงินของในคลัง",
+            LedgerReason::StashExpired => "ของในคลังหมดอายุ",
+            LedgerReason::PromoCode => "โค้ดโปรโมชั่น",
+        }
+    }
+    
+    pub fn from_key(key: &str) -> Option<LedgerReason> {
+        LedgerReason::ALL.iter().copied().find(|reason| reason.key() == key)
+    }
+}
+
+/// One change to a player's coins.
+pub struct LedgerEntry {
+    pub id: i64,
+    pub created_at: String,
+    pub discord_id: String,
+    pub amount: i32,
+    /// The player's coins right after this change.
+    pub balance: i32,
+    pub reason: String,
+    /// What the change was for, e.g. `purchase:42` or `code:WELCOME`.
+    pub reference: Option<String>,
+    /// Who made the change when it wasn't the player, e.g. the admin of `/addcoin`.
+    pub actor: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromoKind {
+    /// Grants `amount` coins when redeemed.
+    Coins,
+    /// Takes `amount` percent off the player's next purchase.
+    Discount,
+}
+
+impl PromoKind {
+    pub fn key(&self) -> &'static str {
+        match self {
+            PromoKind::Coins => "coins",
+            PromoKind::Discount => "discount",
+        }
+    }
+    
+    pub fn from_key(key: &str) -> Option<PromoKind> {
+        [PromoKind::Coins, PromoKind::Discount].into_iter().find(|kind| kind.key() == key)
+    }
+}
+
+pub struct PromoCode {
+    pub code: String,
+    pub kind: PromoKind,
+    pub amount: i32,
+    /// Total redemptions allowed across all players; `None` is unlimited.
+    pub max_uses: Option<u32>,
+    pub uses: u32,
+    /// UTC, formatted with `DB_TIME_FORMAT`.
+    pub expires_at: Option<String>,
+    pub required_role: Option<String>,
+    pub created_by: String,
+    pub created_at: String,
+    pub disabled: bool,
+}
+
+pub struct PromoRedemption {
+    pub discord_id: String,
+    pub redeemed_at: String,
+    /// When a discount was spent; always `None` for coin codes.
+    pub used_at: Option<String>,
+    pub purchase_id: Option<i64>,
+}
+
+/// A redeemed discount code waiting for the player's next purchase.
+pub struct Voucher {
+    pub redemption_id: i64,
+    pub code: String,
+    pub percent: u32,
+}
+
+pub enum RedeemOutcome {
+    Coins { amount: i32, balance: i32 },
+    Discount { percent: u32 },
+    NotFound,
+    Disabled,
+    Expired,
+    UsedUp,
+    AlreadyRedeemed,
+    RoleRequired { role_id: String },
+    NotRegistered,
+    /// Only one unspent discount at a time, so it's clear which one the next purchase uses.
+    DiscountPending { code: String },
+}
+
+/// A loot box roll about to be saved.
+pub struct LootDraw {
+    /// Seeds the roll's RNG, so the outcome can be reproduced from the audit log.
+    pub seed: u64,
+    pub outcome: String,
+    pub rarity: String,
+    pub rare: bool,
+    /// The player's rolls in a row without a rare outcome before this one.
+    pub pity: u32,
+}
+
+/// A saved loot box roll.
+pub struct LootRoll {
+    pub id: i64,
+    pub created_at: String,
+    pub discord_id: String,
+    pub item_name: String,
+    pub purchase_id: Option<i64>,
+    pub draw: LootDraw,
+}
+
 pub struct Database {
     conn: Mutex<Connection>,
 }
@@ -48,10 +314,220 @@ impl Database {
             [],
         )?;
         
+        ensure_column(&conn, "purchase_logs", "trigger", "TEXT")?;
+        ensure_column(&conn, "purchase_logs", "recipient_id", "TEXT")?;
+        ensure_column(&conn, "purchase_logs", "gift_message", "TEXT")?;
+        
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS cooldowns (
+                discord_id TEXT,
+                trigger TEXT,
+                expires_at DATETIME,
+                PRIMARY KEY (discord_id, trigger)
+            )",
+            [],
+        )?;
+        
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS shop_messages (
+                channel_id TEXT,
+                item_key TEXT,
+                message_id TEXT,
+                content_hash TEXT,
+                PRIMARY KEY (channel_id, item_key)
+            )",
+            [],
+        )?;
+        
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS message_images (
+                message_id TEXT PRIMARY KEY,
+                path TEXT,
+                fingerprint TEXT,
+                url TEXT
+            )",
+            [],
+        )?;
+        // Replaced by message_images: its URLs were shared by messages that didn't host them.
+        conn.execute("DROP TABLE IF EXISTS image_cache", [])?;
+        
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS item_stock (
+                trigger TEXT PRIMARY KEY,
+                remaining INTEGER,
+                last_restock DATETIME
+            )",
+            [],
+        )?;
+        
         conn.execute(
-            "CREATE TABLE IF NOT EXISTS authorized_users (
+            "CREATE TABLE IF NOT EXISTS permission_grants (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
-                user_id TEXT UNIQUE
+                permission TEXT,
+                subject_type TEXT,
+                subject_id TEXT,
+                granted_by TEXT,
+                granted_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                UNIQUE(permission, subject_type, subject_id)
+            )",
+            [],
+        )?;
+        
+        // Users from the old all-or-nothing authorized_users table keep every permission.
+        let has_legacy_table: bool = conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'authorized_users'",
+            [],
+            |row| row.get::<_, i32>(0),
+        )? > 0;
+        
+        if has_legacy_table {
+            for permission in Permission::ALL {
+                conn.execute(
+                    "INSERT OR IGNORE INTO permission_grants (permission, subject_type, subject_id, granted_by)
+                     SELECT ?, 'user', user_id, 'authorized_users' FROM authorized_users WHERE user_id != ''",
+                    params![permission.key()],
+                )?;
+            }
+            conn.execute("DROP TABLE authorized_users", [])?;
+        }
+        
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS user_settings (
+                discord_id TEXT PRIMARY KEY,
+                confirm_threshold INTEGER DEFAULT 0
+            )",
+            [],
+        )?;
+        
+        ensure_column(&conn, "user_settings", "cart_mode", "INTEGER DEFAULT 0")?;
+        ensure_column(&conn, "user_settings", "stash_mode", "INTEGER DEFAULT 0")?;
+        
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS stash_items (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                discord_id TEXT,
+                trigger TEXT,
+                item_name TEXT,
+                price INTEGER,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                expires_at DATETIME,
+                status TEXT DEFAULT 'stored',
+                closed_at DATETIME
+            )",
+            [],
+        )?;
+        
+        ensure_column(&conn, "stash_items", "purchase_id", "INTEGER")?;
+        ensure_column(&conn, "stash_items", "quantity", "INTEGER")?;
+        ensure_column(&conn, "stash_items", "commands", "TEXT")?;
+        ensure_column(&conn, "stash_items", "loot_box", "INTEGER DEFAULT 0")?;
+        ensure_column(&conn, "stash_items", "paid_by", "TEXT")?;
+        conn.execute(
+            "UPDATE stash_items SET paid_by = (SELECT discord_id FROM purchase_logs WHERE id = stash_items.purchase_id)
+             WHERE paid_by IS NULL AND purchase_id IS NOT NULL",
+            [],
+        )?;
+        
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS cart_items (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                discord_id TEXT,
+                trigger TEXT,
+                added_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            )",
+            [],
+        )?;
+        
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS cleanup_runs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                profile_id TEXT,
+                trigger TEXT,
+                triggered_by TEXT,
+                command_count INTEGER,
+                started_at DATETIME,
+                finished_at DATETIME
+            )",
+            [],
+        )?;
+        
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS shop_versions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                author TEXT,
+                summary TEXT,
+                content TEXT,
+                diff TEXT
+            )",
+            [],
+        )?;
+        
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS coin_ledger (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                discord_id TEXT,
+                amount INTEGER,
+                balance INTEGER,
+                reason TEXT,
+                reference TEXT,
+                actor TEXT
+            )",
+            [],
+        )?;
+        
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS promo_codes (
+                code TEXT PRIMARY KEY,
+                kind TEXT,
+                amount INTEGER,
+                max_uses INTEGER,
+                uses INTEGER DEFAULT 0,
+                expires_at DATETIME,
+                required_role TEXT,
+                created_by TEXT,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                disabled INTEGER DEFAULT 0
+            )",
+            [],
+        )?;
+        
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS promo_redemptions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                code TEXT,
+                discord_id TEXT,
+                redeemed_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                used_at DATETIME,
+                purchase_id INTEGER,
+                UNIQUE(code, discord_id)
+            )",
+            [],
+        )?;
+        
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS loot_pity (
+                discord_id TEXT,
+                item_name TEXT,
+                count INTEGER,
+                PRIMARY KEY (discord_id, item_name)
+            )",
+            [],
+        )?;
+        
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS loot_rolls (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                discord_id TEXT,
+                item_name TEXT,
+                purchase_id INTEGER,
+                seed INTEGER,
+                outcome TEXT,
+                rarity TEXT,
+                rare INTEGER,
+                pity INTEGER
             )",
             [],
         )?;
@@ -76,12 +552,14 @@ impl Database {
         Ok(player)
     }
     
+    /// Registers a player with `coin`, or updates the Steam ID of one already registered.
+    /// An existing balance is kept, since balance changes go through the ledger.
     pub fn add_or_update_player(&self, discord_id: &str, steam_id: &str, coin: i32) -> Result<bool> {
         let conn = self.conn.lock().unwrap();
         
         match conn.execute(
             "INSERT INTO players (discord_id, steam_id, coin) VALUES (?1, ?2, ?3)
-             ON CONFLICT(discord_id) DO UPDATE SET steam_id = ?2, coin = ?3",
+             ON CONFLICT(discord_id) DO UPDATE SET steam_id = ?2",
             params![discord_id, steam_id, coin],
         ) {
             Ok(_) => Ok(true),
@@ -89,53 +567,955 @@ impl Database {
         }
     }
     
-    pub fn update_coin(&self, discord_id: &str, new_coin: i32) -> Result<()> {
+    /// Adds `amount` (negative to deduct) and records it in the ledger. Returns the new
+    /// balance, or `None` if the player isn't registered.
+    pub fn adjust_coin(&self, discord_id: &str, amount: i32, reason: LedgerReason, reference: Option<&str>, actor: Option<&str>) -> Result<Option<i32>> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let updated = tx.execute(
+            "UPDATE players SET coin = coin + ? WHERE discord_id = ?",
+            params![amount, discord_id],
+        )?;
+        if updated == 0 {
+            return Ok(None);
+        }
+        
+        let balance = record_change(&tx, discord_id, amount, reason, reference, actor)?;
+        tx.commit()?;
+        Ok(balance)
+    }
+    
+    /// Checks limits and stock, deducts the total of `lines` and logs each line in one
+    /// transaction. `voucher` is a pending discount redemption the prices already include;
+    /// it's marked spent in the same transaction.
+    pub fn charge_purchase(&self, discord_id: &str, steam_id: &str, lines: &[PurchaseLine], voucher: Option<i64>) -> Result<PurchaseOutcome> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        
+        let outcome = charge_lines(&tx, discord_id, steam_id, lines, voucher)?;
+        if let PurchaseOutcome::Charged { .. } = outcome {
+            tx.commit()?;
+        }
+        Ok(outcome)
+    }
+    
+    /// Same as `charge_purchase`, but also removes the checked-out cart lines in the same
+    /// transaction so a cart can't be paid twice.
+    pub fn checkout_cart(&self, discord_id: &str, steam_id: &str, lines: &[PurchaseLine], cart_item_ids: &[i64], voucher: Option<i64>) -> Result<PurchaseOutcome> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        
+        let outcome = charge_lines(&tx, discord_id, steam_id, lines, voucher)?;
+        if let PurchaseOutcome::Charged { .. } = outcome {
+            for id in cart_item_ids {
+                tx.execute(
+                    "DELETE FROM cart_items WHERE discord_id = ? AND id = ?",
+                    params![discord_id, id],
+                )?;
+            }
+            tx.commit()?;
+        }
+        Ok(outcome)
+    }
+    
+    /// Creates the stock row for a limited button, clamping it if the configured maximum shrank.
+    pub fn sync_stock(&self, trigger: &str, max_stock: u32) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR IGNORE INTO item_stock (trigger, remaining, last_restock) VALUES (?1, ?2, CURRENT_TIMESTAMP)",
+            params![trigger, max_stock],
+        )?;
+        conn.execute(
+            "UPDATE item_stock SET remaining = MIN(remaining, ?2) WHERE trigger = ?1",
+            params![trigger, max_stock],
+        )?;
+        Ok(())
+    }
+    
+    /// Refills a button's stock if `hours` have passed since the last restock.
+    pub fn restock_if_due(&self, trigger: &str, max_stock: u32, hours: u64) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE item_stock SET remaining = ?2, last_restock = CURRENT_TIMESTAMP
+             WHERE trigger = ?1 AND last_restock <= datetime('now', ?3)",
+            params![trigger, max_stock, format!("-{} hours", hours)],
+        )?;
+        Ok(updated > 0)
+    }
+    
+    pub fn get_stock_levels(&self) -> Result<HashMap<String, i64>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT trigger, remaining FROM item_stock")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect()
+    }
+    
+    /// Purchases priced below this skip the confirmation step (0 = always confirm).
+    pub fn get_confirm_threshold(&self, discord_id: &str) -> Result<i64> {
+        let conn = self.conn.lock().unwrap();
+        let threshold = conn.query_row(
+            "SELECT confirm_threshold FROM user_settings WHERE discord_id = ?",
+            params![discord_id],
+            |row| row.get(0),
+        ).optional()?;
+        Ok(threshold.unwrap_or(0))
+    }
+    
+    pub fn set_confirm_threshold(&self, discord_id: &str, threshold: i64) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO user_settings (discord_id, confirm_threshold) VALUES (?1, ?2)
+             ON CONFLICT(discord_id) DO UPDATE SET confirm_threshold = ?2",
+            params![discord_id, threshold],
+        )?;
+        Ok(())
+    }
+    
+    pub fn get_cart_mode(&self, discord_id: &str) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let cart_mode: Option<i32> = conn.query_row(
+            "SELECT cart_mode FROM user_settings WHERE discord_id = ?",
+            params![discord_id],
+            |row| row.get(0),
+        ).optional()?;
+        Ok(cart_mode.unwrap_or(0) != 0)
+    }
+    
+    pub fn set_cart_mode(&self, discord_id: &str, enabled: bool) -> Result<()> {
         let conn = self.conn.lock().unwrap();
         conn.execute(
-            "UPDATE players SET coin = ? WHERE discord_id = ?",
-            params![new_coin, discord_id],
+            "INSERT INTO user_settings (discord_id, cart_mode) VALUES (?1, ?2)
+             ON CONFLICT(discord_id) DO UPDATE SET cart_mode = ?2",
+            params![discord_id, enabled as i32],
         )?;
         Ok(())
     }
     
-    pub fn remove_coin(&self, discord_id: &str, amount: i32) -> Result<bool> {
-        if let Some(player) = self.get_player_by_discord_id(discord_id)? {
-            if player.coin < amount {
-                return Ok(false);
+    pub fn get_stash_mode(&self, discord_id: &str) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let stash_mode: Option<i32> = conn.query_row(
+            "SELECT stash_mode FROM user_settings WHERE discord_id = ?",
+            params![discord_id],
+            |row| row.get(0),
+        ).optional()?;
+        Ok(stash_mode.unwrap_or(0) != 0)
+    }
+    
+    pub fn set_stash_mode(&self, discord_id: &str, enabled: bool) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO user_settings (discord_id, stash_mode) VALUES (?1, ?2)
+             ON CONFLICT(discord_id) DO UPDATE SET stash_mode = ?2",
+            params![discord_id, enabled as i32],
+        )?;
+        Ok(())
+    }
+    
+    /// Unclaimed, unexpired stash items, oldest first.
+    pub fn get_stash_items(&self, discord_id: &str) -> Result<Vec<StashItem>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, discord_id, trigger, item_name, price, created_at, expires_at, purchase_id, quantity, commands, loot_box,
+             COALESCE(paid_by, discord_id) FROM stash_items
+             WHERE discord_id = ? AND status = 'stored' AND expires_at > datetime('now')
+             ORDER BY id"
+        )?;
+        
+        let items = stmt.query_map(params![discord_id], stash_item_from_row)?;
+        items.collect()
+    }
+    
+    /// Marks a stash item claimed and returns it; `None` if it isn't this player's, was
+    /// already claimed or has expired.
+    pub fn claim_stash_item(&self, discord_id: &str, id: i64) -> Result<Option<StashItem>> {
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE stash_items SET status = 'claimed', closed_at = CURRENT_TIMESTAMP
+             WHERE id = ? AND discord_id = ? AND status = 'stored' AND expires_at > datetime('now')",
+            params![id, discord_id],
+        )?;
+        if updated == 0 {
+            return Ok(None);
+        }
+        
+        conn.query_row(
+            "SELECT id, discord_id, trigger, item_name, price, created_at, expires_at, purchase_id, quantity, commands, loot_box,
+             COALESCE(paid_by, discord_id) FROM stash_items WHERE id = ?",
+            params![id],
+            stash_item_from_row,
+        ).optional()
+    }
+    
+    /// Gives back the full price of a claimed item that can no longer be delivered to whoever
+    /// paid for it.
+    pub fn refund_stash_item(&self, id: i64) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let (discord_id, price): (String, i32) = tx.query_row(
+            "SELECT COALESCE(paid_by, discord_id), price FROM stash_items WHERE id = ?",
+            params![id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        tx.execute(
+            "UPDATE players SET coin = coin + ? WHERE discord_id = ?",
+            params![price, discord_id],
+        )?;
+        tx.execute(
+            "UPDATE stash_items SET status = 'refunded', closed_at = CURRENT_TIMESTAMP WHERE id = ?",
+            params![id],
+        )?;
+        record_change(&tx, &discord_id, price, LedgerReason::StashRefund, Some(&format!("stash:{}", id)), None)?;
+        tx.commit()
+    }
+    
+    /// Closes every stash item past its expiry and refunds `refund_percent` of its price to
+    /// whoever paid for it. Returns the expired items with the refunded amount.
+    pub fn expire_stash_items(&self, refund_percent: u32) -> Result<Vec<(StashItem, i32)>> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        
+        let expired: Vec<StashItem> = {
+            let mut stmt = tx.prepare(
+                "SELECT id, discord_id, trigger, item_name, price, created_at, expires_at, purchase_id, quantity, commands, loot_box,
+                 COALESCE(paid_by, discord_id) FROM stash_items
+                 WHERE status = 'stored' AND expires_at <= datetime('now')"
+            )?;
+            let items = stmt.query_map([], stash_item_from_row)?;
+            items.collect::<Result<_>>()?
+        };
+        
+        let mut refunds = Vec::new();
+        for item in expired {
+            let refund = item.price * refund_percent as i32 / 100;
+            tx.execute(
+                "UPDATE stash_items SET status = 'expired', closed_at = CURRENT_TIMESTAMP WHERE id = ?",
+                params![item.id],
+            )?;
+            tx.execute(
+                "UPDATE players SET coin = coin + ? WHERE discord_id = ?",
+                params![refund, item.paid_by],
+            )?;
+            record_change(&tx, &item.paid_by, refund, LedgerReason::StashExpired, Some(&format!("stash:{}", item.id)), None)?;
+            refunds.push((item, refund));
+        }
+        
+        tx.commit()?;
+        Ok(refunds)
+    }
+    
+    /// Returns the number of lines in the cart after adding.
+    pub fn add_cart_item(&self, discord_id: &str, trigger: &str) -> Result<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO cart_items (discord_id, trigger) VALUES (?, ?)",
+            params![discord_id, trigger],
+        )?;
+        conn.query_row(
+            "SELECT COUNT(*) FROM cart_items WHERE discord_id = ?",
+            params![discord_id],
+            |row| row.get(0),
+        )
+    }
+    
+    pub fn get_cart_items(&self, discord_id: &str) -> Result<Vec<CartItem>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, trigger FROM cart_items WHERE discord_id = ? ORDER BY id"
+        )?;
+        let items = stmt.query_map(params![discord_id], |row| {
+            Ok(CartItem {
+                id: row.get(0)?,
+                trigger: row.get(1)?,
+            })
+        })?;
+        items.collect()
+    }
+    
+    pub fn remove_cart_item(&self, discord_id: &str, id: i64) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let deleted = conn.execute(
+            "DELETE FROM cart_items WHERE discord_id = ? AND id = ?",
+            params![discord_id, id],
+        )?;
+        Ok(deleted > 0)
+    }
+    
+    pub fn clear_cart(&self, discord_id: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM cart_items WHERE discord_id = ?", params![discord_id])?;
+        Ok(())
+    }
+    
+    pub fn has_permission_grant(&self, permission: &str, user_id: &str, role_ids: &[String]) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT subject_type, subject_id FROM permission_grants WHERE permission = ?"
+        )?;
+        
+        let grants = stmt.query_map(params![permission], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+        
+        for grant in grants {
+            let (subject_type, subject_id) = grant?;
+            let matches = match subject_type.as_str() {
+                "user" => subject_id == user_id,
+                "role" => role_ids.contains(&subject_id),
+                _ => false,
+            };
+            if matches {
+                return Ok(true);
             }
-            let new_coin = player.coin - amount;
-            self.update_coin(discord_id, new_coin)?;
-            Ok(true)
-        } else {
-            Ok(false)
         }
+        
+        Ok(false)
+    }
+    
+    /// Returns `false` if the grant already existed.
+    pub fn grant_permission(&self, permission: &str, subject_type: &str, subject_id: &str, granted_by: &str) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let inserted = conn.execute(
+            "INSERT OR IGNORE INTO permission_grants (permission, subject_type, subject_id, granted_by) VALUES (?, ?, ?, ?)",
+            params![permission, subject_type, subject_id, granted_by],
+        )?;
+        Ok(inserted > 0)
     }
     
-    pub fn log_purchase(&self, discord_id: &str, steam_id: &str, item_name: &str, price: i32) -> Result<()> {
+    /// Returns `false` if there was nothing to revoke.
+    pub fn revoke_permission(&self, permission: &str, subject_type: &str, subject_id: &str) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let deleted = conn.execute(
+            "DELETE FROM permission_grants WHERE permission = ? AND subject_type = ? AND subject_id = ?",
+            params![permission, subject_type, subject_id],
+        )?;
+        Ok(deleted > 0)
+    }
+    
+    pub fn list_permission_grants(&self) -> Result<Vec<PermissionGrant>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT permission, subject_type, subject_id, granted_by, granted_at
+             FROM permission_grants ORDER BY permission, subject_type, subject_id"
+        )?;
+        
+        let grants = stmt.query_map([], |row| {
+            Ok(PermissionGrant {
+                permission: row.get(0)?,
+                subject_type: row.get(1)?,
+                subject_id: row.get(2)?,
+                granted_by: row.get(3)?,
+                granted_at: row.get(4)?,
+            })
+        })?;
+        grants.collect()
+    }
+    
+    pub fn get_recent_purchases(&self, discord_id: Option<&str>, limit: u32) -> Result<Vec<PurchaseLog>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT timestamp, discord_id, steam_id, item_name, price, recipient_id FROM purchase_logs
+             WHERE ?1 IS NULL OR discord_id = ?1 OR recipient_id = ?1
+             ORDER BY id DESC LIMIT ?2"
+        )?;
+        
+        let logs = stmt.query_map(params![discord_id, limit], |row| {
+            Ok(PurchaseLog {
+                timestamp: row.get(0)?,
+                discord_id: row.get(1)?,
+                steam_id: row.get(2)?,
+                item_name: row.get(3)?,
+                price: row.get(4)?,
+                recipient_id: row.get(5)?,
+            })
+        })?;
+        logs.collect()
+    }
+    
+    pub fn get_shop_messages(&self, channel_id: &str) -> Result<Vec<ShopMessage>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT item_key, message_id, content_hash FROM shop_messages WHERE channel_id = ?"
+        )?;
+        
+        let messages = stmt.query_map(params![channel_id], |row| {
+            Ok(ShopMessage {
+                item_key: row.get(0)?,
+                message_id: row.get(1)?,
+                content_hash: row.get(2)?,
+            })
+        })?;
+        messages.collect()
+    }
+    
+    pub fn upsert_shop_message(&self, channel_id: &str, item_key: &str, message_id: &str, content_hash: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO shop_messages (channel_id, item_key, message_id, content_hash) VALUES (?, ?, ?, ?)",
+            params![channel_id, item_key, message_id, content_hash],
+        )?;
+        Ok(())
+    }
+    
+    pub fn delete_shop_message(&self, channel_id: &str, item_key: &str) -> Result<()> {
         let conn = self.conn.lock().unwrap();
         conn.execute(
-            "INSERT INTO purchase_logs (discord_id, steam_id, item_name, price) VALUES (?, ?, ?, ?)",
-            params![discord_id, steam_id, item_name, price],
+            "DELETE FROM shop_messages WHERE channel_id = ? AND item_key = ?",
+            params![channel_id, item_key],
         )?;
         Ok(())
     }
     
-    pub fn is_authorized(&self, user_id: &str) -> Result<bool> {
+    /// CDN URL of the image attached to `message_id`, if it's still this version of `path`.
+    pub fn get_cached_image(&self, message_id: &str, path: &str, fingerprint: &str) -> Result<Option<String>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT url FROM message_images WHERE message_id = ? AND path = ? AND fingerprint = ?",
+            params![message_id, path, fingerprint],
+            |row| row.get(0),
+        ).optional()
+    }
+    
+    /// `url` points at the attachment of `message_id` and dies with it, so it's only reused
+    /// for that message.
+    pub fn cache_image(&self, message_id: &str, path: &str, fingerprint: &str, url: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO message_images (message_id, path, fingerprint, url) VALUES (?, ?, ?, ?)",
+            params![message_id, path, fingerprint, url],
+        )?;
+        Ok(())
+    }
+    
+    pub fn forget_images_of_message(&self, message_id: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM message_images WHERE message_id = ?", params![message_id])?;
+        Ok(())
+    }
+    
+    pub fn log_cleanup_run(
+        &self,
+        profile_id: &str,
+        trigger: &str,
+        triggered_by: &str,
+        command_count: usize,
+        started_at: &str,
+        finished_at: &str,
+    ) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO cleanup_runs (profile_id, trigger, triggered_by, command_count, started_at, finished_at)
+             VALUES (?, ?, ?, ?, ?, ?)",
+            params![profile_id, trigger, triggered_by, command_count as i64, started_at, finished_at],
+        )?;
+        Ok(())
+    }
+    
+    pub fn add_shop_version(&self, author: &str, summary: &str, content: &str, diff: &str) -> Result<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO shop_versions (author, summary, content, diff) VALUES (?, ?, ?, ?)",
+            params![author, summary, content, diff],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+    
+    pub fn get_shop_version(&self, id: i64) -> Result<Option<ShopVersion>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT id, created_at, author, summary, content, diff FROM shop_versions WHERE id = ?",
+            params![id],
+            shop_version_from_row,
+        ).optional()
+    }
+    
+    pub fn get_latest_shop_version(&self) -> Result<Option<ShopVersion>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT id, created_at, author, summary, content, diff FROM shop_versions ORDER BY id DESC LIMIT 1",
+            [],
+            shop_version_from_row,
+        ).optional()
+    }
+    
+    /// Newest first.
+    pub fn list_shop_versions(&self, limit: u32) -> Result<Vec<ShopVersion>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, created_at, author, summary, content, diff FROM shop_versions ORDER BY id DESC LIMIT ?"
+        )?;
+        let versions = stmt.query_map(params![limit], shop_version_from_row)?;
+        versions.collect()
+    }
+    
+    pub fn get_ledger(&self, discord_id: Option<&str>, limit: u32) -> Result<Vec<LedgerEntry>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, created_at, discord_id, amount, balance, reason, reference, actor FROM coin_ledger
+             WHERE ?1 IS NULL OR discord_id = ?1
+             ORDER BY id DESC LIMIT ?2"
+        )?;
+        
+        let entries = stmt.query_map(params![discord_id, limit], |row| {
+            Ok(LedgerEntry {
+                id: row.get(0)?,
+                created_at: row.get(1)?,
+                discord_id: row.get(2)?,
+                amount: row.get(3)?,
+                balance: row.get(4)?,
+                reason: row.get(5)?,
+                reference: row.get(6)?,
+                actor: row.get(7)?,
+            })
+        })?;
+        entries.collect()
+    }
+    
+    /// Codes are stored as given; callers upper-case them. `uses`, `created_at` and `disabled`
+    /// of `promo` are ignored. Returns `false` if the code exists.
+    pub fn create_promo_code(&self, promo: &PromoCode) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let inserted = conn.execute(
+            "INSERT OR IGNORE INTO promo_codes (code, kind, amount, max_uses, expires_at, required_role, created_by)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+            params![promo.code, promo.kind.key(), promo.amount, promo.max_uses, promo.expires_at, promo.required_role, promo.created_by],
+        )?;
+        Ok(inserted > 0)
+    }
+    
+    pub fn get_promo_code(&self, code: &str) -> Result<Option<PromoCode>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            &format!("SELECT {} FROM promo_codes WHERE code = ?", PROMO_CODE_COLUMNS),
+            params![code],
+            promo_code_from_row,
+        ).optional()
+    }
+    
+    /// Newest first.
+    pub fn list_promo_codes(&self, limit: u32) -> Result<Vec<PromoCode>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            &format!("SELECT {} FROM promo_codes ORDER BY created_at DESC, code LIMIT ?", PROMO_CODE_COLUMNS)
+        )?;
+        let codes = stmt.query_map(params![limit], promo_code_from_row)?;
+        codes.collect()
+    }
+    
+    pub fn set_promo_code_disabled(&self, code: &str, disabled: bool) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE promo_codes SET disabled = ? WHERE code = ?",
+            params![disabled as i32, code],
+        )?;
+        Ok(updated > 0)
+    }
+    
+    pub fn get_promo_redemptions(&self, code: &str) -> Result<Vec<PromoRedemption>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT discord_id, redeemed_at, used_at, purchase_id FROM promo_redemptions WHERE code = ? ORDER BY id"
+        )?;
+        let redemptions = stmt.query_map(params![code], |row| {
+            Ok(PromoRedemption {
+                discord_id: row.get(0)?,
+                redeemed_at: row.get(1)?,
+                used_at: row.get(2)?,
+                purchase_id: row.get(3)?,
+            })
+        })?;
+        redemptions.collect()
+    }
+    
+    pub fn get_pending_voucher(&self, discord_id: &str) -> Result<Option<Voucher>> {
         let conn = self.conn.lock().unwrap();
-        let count: i32 = conn.query_row(
-            "SELECT COUNT(*) FROM authorized_users WHERE user_id = ?",
-            params![user_id],
+        pending_voucher(&conn, discord_id)
+    }
+    
+    /// Checks every restriction and redeems the code in one transaction: coin codes are paid
+    /// out and logged in the ledger, discount codes wait for the next purchase.
+    pub fn redeem_promo_code(&self, code: &str, discord_id: &str, role_ids: &[String]) -> Result<RedeemOutcome> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        
+        let promo = match tx.query_row(
+            &format!("SELECT {} FROM promo_codes WHERE code = ?", PROMO_CODE_COLUMNS),
+            params![code],
+            promo_code_from_row,
+        ).optional()? {
+            Some(promo) => promo,
+            None => return Ok(RedeemOutcome::NotFound),
+        };
+        
+        if promo.disabled {
+            return Ok(RedeemOutcome::Disabled);
+        }
+        let expired: bool = tx.query_row(
+            "SELECT COALESCE(expires_at <= datetime('now'), 0) FROM promo_codes WHERE code = ?",
+            params![code],
             |row| row.get(0),
         )?;
-        Ok(count > 0)
+        if expired {
+            return Ok(RedeemOutcome::Expired);
+        }
+        if promo.max_uses.is_some_and(|max_uses| promo.uses >= max_uses) {
+            return Ok(RedeemOutcome::UsedUp);
+        }
+        if let Some(role_id) = &promo.required_role {
+            if !role_ids.contains(role_id) {
+                return Ok(RedeemOutcome::RoleRequired { role_id: role_id.clone() });
+            }
+        }
+        
+        let redeemed: i64 = tx.query_row(
+            "SELECT COUNT(*) FROM promo_redemptions WHERE code = ? AND discord_id = ?",
+            params![code, discord_id],
+            |row| row.get(0),
+        )?;
+        if redeemed > 0 {
+            return Ok(RedeemOutcome::AlreadyRedeemed);
+        }
+        let registered: i64 = tx.query_row(
+            "SELECT COUNT(*) FROM players WHERE discord_id = ?",
+            params![discord_id],
+            |row| row.get(0),
+        )?;
+        if registered == 0 {
+            return Ok(RedeemOutcome::NotRegistered);
+        }
+        if promo.kind == PromoKind::Discount {
+            if let Some(voucher) = pending_voucher(&tx, discord_id)? {
+                return Ok(RedeemOutcome::DiscountPending { code: voucher.code });
+            }
+        }
+        
+        tx.execute(
+            "INSERT INTO promo_redemptions (code, discord_id) VALUES (?, ?)",
+            params![code, discord_id],
+        )?;
+        tx.execute(
+            "UPDATE promo_codes SET uses = uses + 1 WHERE code = ?",
+            params![code],
+        )?;
+        
+        let outcome = match promo.kind {
+            PromoKind::Coins => {
+                tx.execute(
+                    "UPDATE players SET coin = coin + ? WHERE discord_id = ?",
+                    params![promo.amount, discord_id],
+                )?;
+                let balance = record_change(&tx, discord_id, promo.amount, LedgerReason::PromoCode, Some(&format!("code:{}", code)), None)?;
+                RedeemOutcome::Coins { amount: promo.amount, balance: balance.unwrap_or(0) }
+            },
+            PromoKind::Discount => RedeemOutcome::Discount { percent: promo.amount.max(0) as u32 },
+        };
+        
+        tx.commit()?;
+        Ok(outcome)
     }
     
-    pub fn add_authorized_user(&self, user_id: &str) -> Result<()> {
+    /// Hands the player's pity counter for `item_name` to `roll` and saves what it rolled with
+    /// the new counter, in one transaction so two openings can't share a counter.
+    pub fn open_loot(
+        &self,
+        discord_id: &str,
+        item_name: &str,
+        purchase_id: Option<i64>,
+        roll: impl FnOnce(u32) -> Vec<LootDraw>,
+    ) -> Result<Vec<LootDraw>> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        
+        let pity: u32 = tx.query_row(
+            "SELECT count FROM loot_pity WHERE discord_id = ? AND item_name = ?",
+            params![discord_id, item_name],
+            |row| row.get(0),
+        ).optional()?.unwrap_or(0);
+        
+        let draws = roll(pity);
+        for draw in &draws {
+            tx.execute(
+                "INSERT INTO loot_rolls (discord_id, item_name, purchase_id, seed, outcome, rarity, rare, pity)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+                params![discord_id, item_name, purchase_id, draw.seed as i64, draw.outcome, draw.rarity, draw.rare, draw.pity],
+            )?;
+        }
+        
+        if let Some(last) = draws.last() {
+            let count = if last.rare { 0 } else { last.pity + 1 };
+            tx.execute(
+                "INSERT INTO loot_pity (discord_id, item_name, count) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(discord_id, item_name) DO UPDATE SET count = ?3",
+                params![discord_id, item_name, count],
+            )?;
+        }
+        
+        tx.commit()?;
+        Ok(draws)
+    }
+    
+    pub fn get_loot_rolls(&self, discord_id: Option<&str>, rare_only: bool, limit: u32) -> Result<Vec<LootRoll>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, created_at, discord_id, item_name, purchase_id, seed, outcome, rarity, rare, pity FROM loot_rolls
+             WHERE (?1 IS NULL OR discord_id = ?1) AND (?2 = 0 OR rare = 1)
+             ORDER BY id DESC LIMIT ?3"
+        )?;
+        
+        let rolls = stmt.query_map(params![discord_id, rare_only, limit], |row| {
+            Ok(LootRoll {
+                id: row.get(0)?,
+                created_at: row.get(1)?,
+                discord_id: row.get(2)?,
+                item_name: row.get(3)?,
+                purchase_id: row.get(4)?,
+                draw: LootDraw {
+                    seed: row.get::<_, i64>(5)? as u64,
+                    outcome: row.get(6)?,
+                    rarity: row.get(7)?,
+                    rare: row.get(8)?,
+                    pity: row.get(9)?,
+                },
+            })
+        })?;
+        rolls.collect()
+    }
+    
+    /// `expires_at` is UTC, formatted with `DB_TIME_FORMAT`.
+    pub fn set_cooldown(&self, discord_id: &str, trigger: &str, expires_at: &str) -> Result<()> {
         let conn = self.conn.lock().unwrap();
         conn.execute(
-            "INSERT OR IGNORE INTO authorized_users (user_id) VALUES (?)",
-            params![user_id],
+            "INSERT OR REPLACE INTO cooldowns (discord_id, trigger, expires_at) VALUES (?, ?, ?)",
+            params![discord_id, trigger, expires_at],
         )?;
         Ok(())
     }
-}
\ No newline at end of file
+    
+    pub fn get_active_cooldowns(&self) -> Result<Vec<(String, String, String)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT discord_id, trigger, expires_at FROM cooldowns WHERE expires_at > datetime('now')"
+        )?;
+        
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+        rows.collect()
+    }
+    
+    /// Removes a user's cooldowns, or only the one for `trigger` when given.
+    pub fn clear_cooldowns(&self, discord_id: &str, trigger: Option<&str>) -> Result<usize> {
+        let conn = self.conn.lock().unwrap();
+        match trigger {
+            Some(trigger) => conn.execute(
+                "DELETE FROM cooldowns WHERE discord_id = ? AND trigger = ?",
+                params![discord_id, trigger],
+            ),
+            None => conn.execute(
+                "DELETE FROM cooldowns WHERE discord_id = ?",
+                params![discord_id],
+            ),
+        }
+    }
+    
+    pub fn delete_expired_cooldowns(&self) -> Result<usize> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM cooldowns WHERE expires_at <= datetime('now')", [])
+    }
+    
+    /// Latest `started_at` per cleanup profile, so schedules survive restarts.
+    pub fn get_last_cleanup_runs(&self) -> Result<Vec<(String, String)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT profile_id, MAX(started_at) FROM cleanup_runs GROUP BY profile_id"
+        )?;
+        
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect()
+    }
+}
+
+fn charge_lines(tx: &Transaction, discord_id: &str, steam_id: &str, lines: &[PurchaseLine], voucher: Option<i64>) -> Result<PurchaseOutcome> {
+    for (index, line) in lines.iter().enumerate() {
+        let earlier_in_batch = lines[..index].iter().filter(|l| l.trigger == line.trigger).count() as i64;
+        
+        for (limit, period) in [(line.daily_limit, LimitPeriod::Daily), (line.weekly_limit, LimitPeriod::Weekly)] {
+            if let Some(limit) = limit {
+                let bought: i64 = tx.query_row(
+                    "SELECT COUNT(*) FROM purchase_logs
+                     WHERE discord_id = ? AND trigger = ? AND timestamp >= datetime('now', ?)",
+                    params![discord_id, line.trigger, period.sqlite_window()],
+                    |row| row.get(0),
+                )?;
+                if bought + earlier_in_batch >= limit as i64 {
+                    return Ok(PurchaseOutcome::LimitReached { item_name: line.item_name.clone(), period, limit });
+                }
+            }
+        }
+        
+        if line.limited_stock {
+            let updated = tx.execute(
+                "UPDATE item_stock SET remaining = remaining - 1 WHERE trigger = ? AND remaining > 0",
+                params![line.trigger],
+            )?;
+            if updated == 0 {
+                return Ok(PurchaseOutcome::OutOfStock { item_name: line.item_name.clone() });
+            }
+        }
+    }
+    
+    let total: i32 = lines.iter().map(|line| line.price).sum();
+    let updated = tx.execute(
+        "UPDATE players SET coin = coin - ?1 WHERE discord_id = ?2 AND coin >= ?1",
+        params![total, discord_id],
+    )?;
+    if updated == 0 {
+        return Ok(PurchaseOutcome::InsufficientCoins);
+    }
+    
+    let remaining: i32 = tx.query_row(
+        "SELECT coin FROM players WHERE discord_id = ?",
+        params![discord_id],
+        |row| row.get(0),
+    )?;
+    
+    let mut balance = remaining + total;
+    let mut purchase_ids = Vec::with_capacity(lines.len());
+    for line in lines {
+        tx.execute(
+            "INSERT INTO purchase_logs (discord_id, steam_id, item_name, price, trigger, recipient_id, gift_message)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+            params![discord_id, steam_id, line.item_name, line.price, line.trigger, line.recipient_id, line.gift_message],
+        )?;
+        let purchase_id = tx.last_insert_rowid();
+        purchase_ids.push(purchase_id);
+        
+        balance -= line.price;
+        insert_ledger(tx, discord_id, -line.price, balance, LedgerReason::Purchase, Some(&format!("purchase:{}", purchase_id)), None)?;
+        
+        if let Some(stash_until) = &line.stash_until {
+            let owner = line.recipient_id.as_deref().unwrap_or(discord_id);
+            tx.execute(
+                "INSERT INTO stash_items (discord_id, paid_by, trigger, item_name, price, expires_at, purchase_id, quantity, commands, loot_box)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                params![owner, discord_id, line.trigger, line.item_name, line.price, stash_until, purchase_id, line.quantity, line.commands, line.loot_box],
+            )?;
+        }
+    }
+    
+    if let Some(redemption_id) = voucher {
+        let updated = tx.execute(
+            "UPDATE promo_redemptions SET used_at = CURRENT_TIMESTAMP, purchase_id = ?
+             WHERE id = ? AND discord_id = ? AND used_at IS NULL",
+            params![purchase_ids.first(), redemption_id, discord_id],
+        )?;
+        if updated == 0 {
+            return Ok(PurchaseOutcome::VoucherUsed);
+        }
+    }
+    
+    Ok(PurchaseOutcome::Charged { remaining, purchase_ids })
+}
+
+fn insert_ledger(
+    conn: &Connection,
+    discord_id: &str,
+    amount: i32,
+    balance: i32,
+    reason: LedgerReason,
+    reference: Option<&str>,
+    actor: Option<&str>,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO coin_ledger (discord_id, amount, balance, reason, reference, actor) VALUES (?, ?, ?, ?, ?, ?)",
+        params![discord_id, amount, balance, reason.key(), reference, actor],
+    )?;
+    Ok(())
+}
+
+/// Logs a change already applied to `players.coin` and returns the balance after it;
+/// `None` (and nothing logged) if the player doesn't exist.
+fn record_change(
+    conn: &Connection,
+    discord_id: &str,
+    amount: i32,
+    reason: LedgerReason,
+    reference: Option<&str>,
+    actor: Option<&str>,
+) -> Result<Option<i32>> {
+    let balance: Option<i32> = conn.query_row(
+        "SELECT coin FROM players WHERE discord_id = ?",
+        params![discord_id],
+        |row| row.get(0),
+    ).optional()?;
+    if let Some(balance) = balance {
+        insert_ledger(conn, discord_id, amount, balance, reason, reference, actor)?;
+    }
+    Ok(balance)
+}
+
+fn pending_voucher(conn: &Connection, discord_id: &str) -> Result<Option<Voucher>> {
+    conn.query_row(
+        "SELECT r.id, r.code, c.amount FROM promo_redemptions r JOIN promo_codes c ON c.code = r.code
+         WHERE r.discord_id = ? AND c.kind = 'discount' AND r.used_at IS NULL
+         ORDER BY r.id LIMIT 1",
+        params![discord_id],
+        |row| Ok(Voucher { redemption_id: row.get(0)?, code: row.get(1)?, percent: row.get::<_, i32>(2)?.max(0) as u32 }),
+    ).optional()
+}
+
+const PROMO_CODE_COLUMNS: &str = "code, kind, amount, max_uses, uses, expires_at, required_role, created_by, created_at, disabled";
+
+fn promo_code_from_row(row: &rusqlite::Row) -> Result<PromoCode> {
+    let kind: String = row.get(1)?;
+    Ok(PromoCode {
+        code: row.get(0)?,
+        kind: PromoKind::from_key(&kind).unwrap_or(PromoKind::Coins),
+        amount: row.get(2)?,
+        max_uses: row.get(3)?,
+        uses: row.get(4)?,
+        expires_at: row.get(5)?,
+        required_role: row.get(6)?,
+        created_by: row.get(7)?,
+        created_at: row.get(8)?,
+        disabled: row.get::<_, i32>(9)? != 0,
+    })
+}
+
+fn shop_version_from_row(row: &rusqlite::Row) -> Result<ShopVersion> {
+    Ok(ShopVersion {
+        id: row.get(0)?,
+        created_at: row.get(1)?,
+        author: row.get(2)?,
+        summary: row.get(3)?,
+        content: row.get(4)?,
+        diff: row.get(5)?,
+    })
+}
+
+fn stash_item_from_row(row: &rusqlite::Row) -> Result<StashItem> {
+    Ok(StashItem {
+        id: row.get(0)?,
+        discord_id: row.get(1)?,
+        trigger: row.get(2)?,
+        item_name: row.get(3)?,
+        price: row.get(4)?,
+        created_at: row.get(5)?,
+        expires_at: row.get(6)?,
+        purchase_id: row.get(7)?,
+        quantity: row.get(8)?,
+        commands: row.get(9)?,
+        loot_box: row.get::<_, Option<bool>>(10)?.unwrap_or(false),
+        paid_by: row.get(11)?,
+    })
+}
+
+/// Adds a column to an existing table if it isn't there yet (SQLite has no
+/// `ADD COLUMN IF NOT EXISTS`).
+fn ensure_column(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let columns = stmt.query_map([], |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<_>>>()?;
+    
+    if !columns.iter().any(|c| c == column) {
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])?;
+    }
+    Ok(())
+}
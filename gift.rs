@@ -0,0 +1,193 @@
+use serenity::builder::*;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+
+use crate::commands::{member_roles, reply, string_option, truncate, user_option};
+use crate::database::PurchaseOutcome;
+use crate::promo_codes::with_voucher;
+use crate::promotions::quote;
+use crate::loot::{append_draws, delivery_commands, describe_draws};
+use crate::purchase::{find_shop_button, get_vip_tier, purchase_line, rejection_message, send_receipt, template_names, ReceiptLine};
+use crate::search::search;
+use crate::shared_state::SharedState;
+use crate::shop::refresh_item_messages;
+use crate::stash::stash_until;
+use crate::template::TemplateContext;
+
+pub fn gift_command() -> CreateCommand {
+    CreateCommand::new("gift")
+        .description("ซื้อสินค้าเป็นของขวัญให้ผู้เล่นคนอื่น")
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::User, "user", "ผู้รับของขวัญ")
+                .required(true)
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::String, "item", "สินค้า")
+                .required(true)
+                .set_autocomplete(true)
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::String, "message", "ข้อความถึงผู้รับ")
+                .max_length(200)
+        )
+}
+
+/// Charges the sender and delivers to the recipient's Steam ID. Cooldowns, purchase limits
+/// and the VIP discount are the sender's, since the sender is the one buying.
+pub async fn handle_gift_command(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState) {
+    if !shared_state.is_bot_active().await {
+        reply(ctx, command, "❌ ระบบ BOTSHOP หยุดทำงานชั่วคราวเนื่องจาก SERVER กำลังจะ RESTART").await;
+        return;
+    }
+    if shared_state.destroy_lock.try_lock().is_err() {
+        reply(ctx, command, "❌ บอทกำลังลบขยะ กรุณาลองใหม่อีกครั้งภายหลัง").await;
+        return;
+    }
+
+    let options = command.data.options();
+    let (recipient, trigger) = match (user_option(&options, "user"), string_option(&options, "item")) {
+        (Some(recipient), Some(trigger)) => (recipient, trigger),
+        _ => return,
+    };
+    let message = string_option(&options, "message").map(str::trim).filter(|message| !message.is_empty());
+
+    if recipient.id == command.user.id {
+        reply(ctx, command, "❌ ไม่สามารถส่งของขวัญให้ตัวเองได้ กดปุ่มในร้านค้าเพื่อซื้อแทน").await;
+        return;
+    }
+    if recipient.bot {
+        reply(ctx, command, "❌ ไม่สามารถส่งของขวัญให้บอทได้").await;
+        return;
+    }
+
+    let (item, button) = match find_shop_button(&shared_state.shop_file().shops, trigger) {
+        Some(found) => found,
+        None => {
+            reply(ctx, command, "❌ ไม่พบสินค้านี้ กรุณาเลือกจากรายการ").await;
+            return;
+        }
+    };
+
+    let sender_id = command.user.id.to_string();
+    let recipient_id = recipient.id.to_string();
+
+    let sender = match shared_state.db.get_player_by_discord_id(&sender_id) {
+        Ok(Some(player)) => player,
+        _ => {
+            reply(ctx, command, "ไม่พบข้อมูลผู้เล่นของคุณในระบบ! กรุณาลงทะเบียนด้วย /register").await;
+            return;
+        }
+    };
+    let receiver = match shared_state.db.get_player_by_discord_id(&recipient_id) {
+        Ok(Some(player)) => player,
+        _ => {
+            reply(ctx, command, format!("❌ <@{}> ยังไม่ได้ลงทะเบียน ไม่สามารถรับของขวัญได้", recipient_id)).await;
+            return;
+        }
+    };
+
+    if let Some(remaining) = shared_state.check_cooldown(&sender_id, trigger) {
+        reply(ctx, command, format!("สินค้าดังกล่าวอยู่ในช่วง cooldown กรุณารออีก {} วินาที", remaining.as_secs())).await;
+        return;
+    }
+
+    let vip = get_vip_tier(&shared_state.config, member_roles(command));
+    let (price, voucher) = with_voucher(shared_state, &sender_id, quote(shared_state, &item, &button, vip).total);
+
+    let mut line = purchase_line(&item, &button, price);
+    line.recipient_id = Some(recipient_id.clone());
+    line.gift_message = message.map(str::to_string);
+    line.stash_until = stash_until(shared_state, &recipient_id);
+    let stashed = line.stash_until.is_some();
+
+    let (remaining_coin, purchase_ids) = match shared_state.db.charge_purchase(&sender_id, &receiver.steam_id, &[line], voucher.map(|voucher| voucher.redemption_id)) {
+        Ok(PurchaseOutcome::Charged { remaining, purchase_ids }) => (remaining, purchase_ids),
+        Ok(outcome) => {
+            reply(ctx, command, rejection_message(&outcome, price, sender.coin)).await;
+            return;
+        },
+        Err(_) => {
+            reply(ctx, command, "เกิดข้อผิดพลาดในการหัก coin!").await;
+            return;
+        }
+    };
+
+    let draws = if stashed {
+        Vec::new()
+    } else {
+        let (discord_name, player_name) = template_names(recipient, None);
+        let context = TemplateContext {
+            steam_id: &receiver.steam_id,
+            discord_name: &discord_name,
+            player_name: &player_name,
+            quantity: button.quantity,
+            purchase_id: purchase_ids.first().copied().unwrap_or(0),
+        };
+        let (commands, draws) = delivery_commands(shared_state, &item, &button, &context, &recipient_id);
+        shared_state.command_queue.lock().await.push(commands);
+        draws
+    };
+    shared_state.set_cooldown(&sender_id, trigger, button.cooldown());
+
+    reply(ctx, command, append_draws(format!(
+        "🎁 ส่ง {} ให้ <@{}> สำเร็จ! หัก {} coins (เหลือ {} coins)",
+        item.name, recipient_id, price, remaining_coin
+    ), &draws)).await;
+
+    let receipt = ReceiptLine {
+        item_name: format!("{} (ของขวัญให้ {})", item.name, recipient.name),
+        quantity: button.quantity,
+        price,
+    };
+    send_receipt(&ctx.http, &command.user, &[receipt], remaining_coin).await;
+
+    if let Ok(dm_channel) = recipient.create_dm_channel(&ctx.http).await {
+        let mut embed = CreateEmbed::new()
+            .title("🎁 คุณได้รับของขวัญ!")
+            .color(0x9900cc)
+            .field("👤 จาก", format!("<@{}>", sender_id), true)
+            .field("🛒 สินค้า", format!("**{}** x{}", item.name, button.quantity), true)
+            .footer(CreateEmbedFooter::new(if stashed {
+                "เก็บไว้ในคลังของคุณแล้ว ใช้ /claim เพื่อรับของ • © powered by TimeSkip"
+            } else {
+                "ไอเทมจะถูกส่งเข้าเกมในไม่ช้า • © powered by TimeSkip"
+            }));
+        if !draws.is_empty() {
+            embed = embed.field("🎲 ได้รับ", describe_draws(&draws), false);
+        }
+        if let Some(message) = message {
+            embed = embed.field("💌 ข้อความ", message, false);
+        }
+        let _ = dm_channel.send_message(&ctx.http, CreateMessage::new().embed(embed)).await;
+    }
+
+    if button.stock.is_some() {
+        refresh_item_messages(&ctx.http, shared_state, std::slice::from_ref(&button.trigger)).await;
+    }
+}
+
+/// Item choices are individual buttons, valued by trigger.
+pub async fn handle_gift_autocomplete(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState) {
+    let focused = match command.data.autocomplete() {
+        Some(focused) if focused.name == "item" => focused,
+        _ => return,
+    };
+
+    let mut response = CreateAutocompleteResponse::new();
+    let shop_file = shared_state.shop_file();
+    let items: Vec<_> = if focused.value.trim().is_empty() {
+        shop_file.shops.iter().flat_map(|shop| &shop.items).filter(|item| item.is_listed()).collect()
+    } else {
+        search(&shop_file.shops, focused.value, 25).into_iter().map(|hit| hit.item).collect()
+    };
+
+    let choices = items.iter()
+        .flat_map(|item| item.active_buttons().map(move |button| (item, button)))
+        .take(25);
+    for (item, button) in choices {
+        let label = format!("{} ({}) — {} coin", item.name, button.text, item.price * button.quantity);
+        response = response.add_string_choice(truncate(&label, 100), button.trigger.clone());
+    }
+
+    let _ = command.create_response(&ctx.http, CreateInteractionResponse::Autocomplete(response)).await;
+}
@@ -1,31 +1,54 @@
 use serenity::async_trait;
 use serenity::model::prelude::*;
 use serenity::prelude::*;
-use serenity::builder::{CreateInteractionResponse, CreateInteractionResponseMessage, CreateMessage, CreateEmbed, CreateEmbedFooter, CreateActionRow, CreateButton, GetMessages, CreateInteractionResponseFollowup};
+use serenity::builder::{CreateInteractionResponse, CreateInteractionResponseMessage, CreateEmbed, CreateEmbedFooter, CreateInteractionResponseFollowup, CreateActionRow, CreateButton, EditInteractionResponse};
 use std::sync::Arc;
 use log::info;
 use tokio::time::{sleep, Duration};
 
+use crate::config::ShopButton;
 use crate::shared_state::SharedState;
-use crate::utils::{send_commands_to_game, substitute_steam_id_in_commands, is_special_command, calculate_discounted_price};
-use crate::config::VipRole;
+use crate::stash::stash_until;
+use crate::template::TemplateContext;
+use crate::cleanup::{run_cleanup, CleanupTrigger};
+use crate::utils::{send_commands_to_game, format_wait_time};
+use crate::database::PurchaseOutcome;
+use crate::purchase::{find_shop_button, get_vip_tier, purchase_line, rejection_message, send_receipt, template_names, ReceiptLine};
+use crate::permissions::{has_permission, Permission};
+use crate::promotions::quote;
+use crate::promo_codes::with_voucher;
+use crate::loot::{append_draws, delivery_commands};
+
+/// How long the Confirm/Cancel buttons of a purchase stay valid.
+const PURCHASE_CONFIRM_TIMEOUT_SECS: u64 = 60;
+
+const COOLDOWN_EVICTION_INTERVAL_SECS: u64 = 600;
+
+/// Shop buttons need the server member for VIP roles; DMs and uncached members have none.
+const MEMBER_REQUIRED_MESSAGE: &str = "❌ ไม่พบข้อมูลสมาชิกของคุณในเซิร์ฟเวอร์ กรุณากดซื้อใหม่อีกครั้ง";
 
 pub struct Handler {
     shared_state: Arc<SharedState>,
+    guild_id: GuildId,
 }
 
 impl Handler {
-    pub fn new(shared_state: Arc<SharedState>) -> Self {
-        Handler { shared_state }
+    pub fn new(shared_state: Arc<SharedState>, guild_id: GuildId) -> Self {
+        Handler { shared_state, guild_id }
     }
 }
 
 #[async_trait]
 impl EventHandler for Handler {
-    async fn ready(&self, _ctx: Context, ready: Ready) {
+    async fn ready(&self, ctx: Context, ready: Ready) {
         println!("Bot {} is connected!", ready.user.name);
         info!("{} is connected!", ready.user.name);
         
+        match self.guild_id.set_commands(&ctx.http, crate::commands::guild_commands()).await {
+            Ok(commands) => println!("Registered {} slash commands", commands.len()),
+            Err(e) => eprintln!("Failed to register slash commands: {:?}", e),
+        }
+        
         let shared_state = self.shared_state.clone();
         tokio::spawn(async move {
             crate::maintenance::start_maintenance_schedule(shared_state).await;
@@ -37,185 +60,55 @@ impl EventHandler for Handler {
         });
         
         let shared_state = self.shared_state.clone();
+        let http = ctx.http.clone();
         tokio::spawn(async move {
-            auto_destroy_items_type1(shared_state).await;
+            crate::cleanup::start_cleanup_scheduler(http, shared_state).await;
         });
         
         let shared_state = self.shared_state.clone();
         tokio::spawn(async move {
-            auto_destroy_items_type2(shared_state).await;
+            evict_expired_cooldowns(shared_state).await;
+        });
+        
+        let shared_state = self.shared_state.clone();
+        let http = ctx.http.clone();
+        tokio::spawn(async move {
+            crate::stash::start_stash_expiry(http, shared_state).await;
+        });
+        
+        let shared_state = self.shared_state.clone();
+        let http = ctx.http.clone();
+        tokio::spawn(async move {
+            crate::shop::start_restock_task(http, shared_state).await;
+        });
+        
+        let shared_state = self.shared_state.clone();
+        let http = ctx.http.clone();
+        tokio::spawn(async move {
+            crate::promotions::start_promotion_scheduler(http, shared_state).await;
         });
         
         println!("All background tasks started successfully!");
     }
     
-    async fn message(&self, ctx: Context, msg: Message) {
-        if msg.author.bot {
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        if let Interaction::Command(command) = &interaction {
+            crate::commands::handle_command(&ctx, command, &self.shared_state).await;
             return;
         }
         
-        if msg.content == "!destroy" {
-            crate::commands::handle_destroy_command(&ctx, &msg).await;
-        }
-        
-        if msg.content.starts_with("!register ") {
-            let args: Vec<&str> = msg.content.split_whitespace().collect();
-            if args.len() >= 2 {
-                let steam_id = args[1];
-                let discord_id = msg.author.id.to_string();
-                
-                match self.shared_state.db.add_or_update_player(&discord_id, steam_id, 0) {
-                    Ok(_) => {
-                        let _ = msg.reply(&ctx.http, format!("✅ ลงทะเบียนสำเร็จ! Steam ID: {}", steam_id)).await;
-                    },
-                    Err(e) => {
-                        let _ = msg.reply(&ctx.http, format!("❌ ลงทะเบียนไม่สำเร็จ: {:?}", e)).await;
-                    }
-                }
-            } else {
-                let _ = msg.reply(&ctx.http, "❌ รูปแบบคำสั่ง: !register <steam_id>").await;
-            }
-        }
-        
-        if msg.content == "!coin" {
-            let discord_id = msg.author.id.to_string();
-            match self.shared_state.db.get_player_by_discord_id(&discord_id) {
-                Ok(Some(player)) => {
-                    let _ = msg.reply(&ctx.http, format!("💰 คุณมี {} coins", player.coin)).await;
-                },
-                Ok(none) => {
-                    let _ = msg.reply(&ctx.http, "❌ คุณยังไม่ได้ลงทะเบียน ใช้คำสั่ง !register <steam_id>").await;
-                },
-                Err(e) => {
-                    let _ = msg.reply(&ctx.http, format!("❌ เกิดข้อผิดพลาด: {:?}", e)).await;
-                }
-            }
-        }
-        
-        if msg.content == "!updateshop" {
-            if self.shared_state.db.is_authorized(&msg.author.id.to_string()).unwrap_or(false) {
-                let _ = msg.reply(&ctx.http, "🔄 กำลังอัปเดตร้านค้า...").await;
-                
-                for shop in &self.shared_state.config.shop_data {
-                    if let Ok(channel_id) = shop.channel.parse::<u64>() {
-                        let channel_id = ChannelId::new(channel_id);
-                        
-                        let messages = channel_id
-                            .messages(&ctx.http, GetMessages::new().limit(100))
-                            .await
-                            .unwrap_or_default();
-                        
-                        for message in messages {
-                            let _ = message.delete(&ctx.http).await;
-                            sleep(Duration::from_millis(100)).await;
-                        }
-                        
-                        for item in &shop.items {
-                            let embed = CreateEmbed::new()
-                                .title(&item.name)
-                                .color(0xFF00FF)
-                                .field("💰 ราคา", format!("{} COIN", item.price), true)
-                                .thumbnail("https://cdn.discordapp.com/attachments/1347264410087067709/1364553843316363304/raw.png")
-                                .footer(CreateEmbedFooter::new("© powered by TimeSkip"));
-                            
-                            let mut components = vec![];
-                            let mut buttons = vec![];
-                            
-                            for button in &item.buttons {
-                                buttons.push(
-                                    CreateButton::new(&button.trigger)
-                                        .label(&button.text)
-                                        .style(ButtonStyle::Danger)
-                                );
-                                
-                                if buttons.len() == 5 {
-                                    components.push(CreateActionRow::Buttons(buttons.clone()));
-                                    buttons.clear();
-                                }
-                            }
-                            
-                            if !buttons.is_empty() {
-                                components.push(CreateActionRow::Buttons(buttons));
-                            }
-                            
-                            let message = CreateMessage::new()
-                                .embed(embed)
-                                .components(components);
-                            
-                            let _ = channel_id.send_message(&ctx.http, message).await;
-                            
-                            sleep(Duration::from_millis(500)).await;
-                        }
-                    }
-                }
-                
-                let _ = msg.reply(&ctx.http, "✅ อัปเดตร้านค้าเรียบร้อย!").await;
-            } else {
-                let _ = msg.reply(&ctx.http, "❌ คุณไม่มีสิทธิ์ใช้คำสั่งนี้").await;
-            }
+        if let Interaction::Autocomplete(command) = &interaction {
+            crate::commands::handle_autocomplete(&ctx, command, &self.shared_state).await;
+            return;
         }
         
-        if msg.content.starts_with("!addcoin ") {
-            if self.shared_state.db.is_authorized(&msg.author.id.to_string()).unwrap_or(false) {
-                let args: Vec<&str> = msg.content.split_whitespace().collect();
-                if args.len() >= 3 {
-                    let user_mention = args[1];
-                    if let Some(user_id) = user_mention.strip_prefix("<@").and_then(|s| s.strip_suffix(">")) {
-                        let user_id = user_id.trim_start_matches('!');
-                        if let Ok(amount) = args[2].parse::<i32>() {
-                            match self.shared_state.db.get_player_by_discord_id(user_id) {
-                                Ok(Some(mut player)) => {
-                                    player.coin += amount;
-                                    if let Err(e) = self.shared_state.db.update_coin(user_id, player.coin) {
-                                        let _ = msg.reply(&ctx.http, format!("❌ ไม่สามารถอัปเดต coin: {:?}", e)).await;
-                                    } else {
-                                        let _ = msg.reply(&ctx.http, format!("✅ เพิ่ม {} coins ให้ <@{}> สำเร็จ! (รวม: {} coins)", amount, user_id, player.coin)).await;
-                                    }
-                                },
-                                Ok(none) => {
-                                    let _ = msg.reply(&ctx.http, "❌ ผู้ใช้ยังไม่ได้ลงทะเบียน").await;
-                                },
-                                Err(e) => {
-                                    let _ = msg.reply(&ctx.http, format!("❌ เกิดข้อผิดพลาด: {:?}", e)).await;
-                                }
-                            }
-                        } else {
-                            let _ = msg.reply(&ctx.http, "❌ จำนวน coin ไม่ถูกต้อง").await;
-                        }
-                    } else {
-                        let _ = msg.reply(&ctx.http, "❌ รูปแบบคำสั่ง: !addcoin @user amount").await;
-                    }
-                } else {
-                    let _ = msg.reply(&ctx.http, "❌ รูปแบบคำสั่ง: !addcoin @user amount").await;
-                }
-            } else {
-                let _ = msg.reply(&ctx.http, "❌ คุณไม่มีสิทธิ์ใช้คำสั่งนี้").await;
+        if let Interaction::Modal(modal) = &interaction {
+            if modal.data.custom_id.starts_with("shopedit_") {
+                crate::shopedit::handle_shopedit_modal(&ctx, modal, &self.shared_state).await;
             }
+            return;
         }
         
-        if msg.content == "!help" {
-            let help_message = "
-**📋 คำสั่งที่ใช้ได้:**
-
-**สำหรับผู้เล่น:**
-`!register <steam_id>` - ลงทะเบียนเพื่อใช้งาน bot
-`!coin` - เช็คจำนวน coin ที่มี
-`!destroy` - แสดงปุ่มลบไอเทม
-
-**สำหรับ Admin:**
-`!updateshop` - อัปเดตร้านค้าทั้งหมด
-`!addcoin @user amount` - เพิ่ม coin ให้ผู้เล่น
-
-**วิธีซื้อของ:**
-1. ลงทะเบียนด้วย `!register <steam_id>`
-2. ตรวจสอบ coin ด้วย `!coin`
-3. ไปที่ช่องร้านค้าและกดปุ่มซื้อ
-            ";
-            let _ = msg.reply(&ctx.http, help_message).await;
-        }
-    }
-    
-    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
         if let Interaction::Component(component) = interaction {
             let custom_id = &component.data.custom_id;
             
@@ -229,8 +122,28 @@ impl EventHandler for Handler {
                 return;
             }
             
-            if custom_id == "destroy_type1" || custom_id == "destroy_type2" {
-                self.handle_destroy_command(&ctx, &component, custom_id).await;
+            if let Some(channel) = custom_id.strip_prefix("shop_category:") {
+                crate::catalog::handle_category_select(&ctx, &component, &self.shared_state, channel).await;
+                return;
+            }
+            
+            if let Some(data) = custom_id.strip_prefix("shop_page:") {
+                crate::catalog::handle_page(&ctx, &component, &self.shared_state, data).await;
+                return;
+            }
+            
+            if let Some(token) = custom_id.strip_prefix("buy_confirm:") {
+                self.handle_purchase_confirmation(&ctx, &component, token, true).await;
+                return;
+            }
+            
+            if let Some(token) = custom_id.strip_prefix("buy_cancel:") {
+                self.handle_purchase_confirmation(&ctx, &component, token, false).await;
+                return;
+            }
+            
+            if let Some(profile_id) = custom_id.strip_prefix("destroy:") {
+                self.handle_destroy_command(&ctx, &component, profile_id).await;
                 return;
             }
             
@@ -240,170 +153,305 @@ impl EventHandler for Handler {
 }
 
 impl Handler {
-    async fn handle_destroy_command(&self, ctx: &Context, component: &ComponentInteraction, command_type: &str) {
-        let _ = component.defer_ephemeral(&ctx.http).await;
+    async fn handle_destroy_command(&self, ctx: &Context, component: &ComponentInteraction, profile_id: &str) {
+        let profile = match self.shared_state.config.cleanup_profiles.iter().find(|p| p.id == profile_id) {
+            Some(profile) => profile,
+            None => return,
+        };
         
-        let _guard = self.shared_state.destroy_lock.lock().await;
+        let user_id = component.user.id.to_string();
+        let roles: Vec<u64> = component.member.as_ref()
+            .map(|m| m.roles.iter().map(|r| r.get()).collect())
+            .unwrap_or_default();
         
-        let commands = match command_type {
-            "destroy_type1" => &self.shared_state.config.destroy_commands_type1,
-            "destroy_type2" => &self.shared_state.config.destroy_commands_type2,
-            _ => return,
-        };
+        let is_cleanup_admin = has_permission(&self.shared_state, component.user.id, component.member.as_ref(), Permission::TriggerCleanup);
         
-        let announce_start = match command_type {
-            "destroy_type1" => "#Announce บอทกำลังลบชุด/เสื้อผ้า บอทหยุดชั่วคราว!",
-            "destroy_type2" => "#Announce บอทกำลังลบวัสดุก่อสร้าง/เศษไม้ บอทหยุดชั่วคราว!",
-            _ => return,
-        };
+        if !is_cleanup_admin && !profile.can_trigger(&roles) {
+            respond_ephemeral(ctx, component, "❌ คุณไม่มีสิทธิ์ใช้ปุ่มนี้").await;
+            return;
+        }
+        
+        if let Some(remaining) = self.shared_state.cleanup_cooldown_remaining(profile) {
+            respond_ephemeral(ctx, component, format!(
+                "⏳ {} เพิ่งทำงานไป กรุณารออีก {}",
+                profile.name, format_wait_time(remaining.num_seconds())
+            )).await;
+            return;
+        }
         
-        let announce_end = match command_type {
-            "destroy_type1" => "#Announce บอทลบชุด/เสื้อผ้าสำเร็จ สามารถใช้บอทต่อได้!",
-            "destroy_type2" => "#Announce บอทลบวัสดุก่อสร้าง/เศษไม้สำเร็จ สามารถใช้บอทต่อได้!",
-            _ => return,
+        let trigger = if profile.votes_required > 1 && !is_cleanup_admin {
+            let voters = self.shared_state.add_cleanup_vote(profile, &user_id);
+            if (voters.len() as u32) < profile.votes_required {
+                respond_ephemeral(ctx, component, format!(
+                    "🗳️ โหวต{}แล้ว ({}/{}) ต้องการอีก {} คนภายใน {} วินาที",
+                    profile.name,
+                    voters.len(),
+                    profile.votes_required,
+                    profile.votes_required - voters.len() as u32,
+                    profile.vote_window_seconds
+                )).await;
+                return;
+            }
+            self.shared_state.clear_cleanup_votes(&profile.id);
+            CleanupTrigger::Vote(voters)
+        } else {
+            CleanupTrigger::Manual(user_id)
         };
         
-        send_commands_to_game(vec![announce_start.to_string()], "destroy").await;
-        sleep(Duration::from_secs(1)).await;
-        send_commands_to_game(commands.clone(), "destroy").await;
-        send_commands_to_game(vec![announce_end.to_string()], "destroy").await;
+        let _ = component.defer_ephemeral(&ctx.http).await;
+        
+        let content = match run_cleanup(&ctx.http, &self.shared_state, profile, trigger).await {
+            Ok(()) => "✅ ดำเนินการเรียบร้อย".to_string(),
+            Err(remaining) => format!(
+                "⏳ {} เพิ่งทำงานไป กรุณารออีก {}",
+                profile.name, format_wait_time(remaining.num_seconds())
+            ),
+        };
         
         let content = CreateInteractionResponseFollowup::new()
-            .content("✅ ดำเนินการเรียบร้อย")
+            .content(content)
             .ephemeral(true);
         let _ = component.create_followup(&ctx.http, content).await;
     }
     
     async fn handle_shop_interaction(&self, ctx: &Context, component: &ComponentInteraction, custom_id: &str) {
-        if let Ok(_) = self.shared_state.destroy_lock.try_lock() {
-        } else {
-            let response = CreateInteractionResponse::Message(
-                CreateInteractionResponseMessage::new()
-                    .content("❌ บอทกำลังลบขยะ กรุณาลองใหม่อีกครั้งภายหลัง")
-                    .ephemeral(true)
-            );
-            let _ = component.create_response(&ctx.http, response).await;
-            return;
-        }
-        
-        let (item, button) = {
-            let mut found = None;
-            for shop in &self.shared_state.config.shop_data {
-                for item in &shop.items {
-                    for button in &item.buttons {
-                        if button.trigger == custom_id {
-                            found = Some((item.clone(), button.clone()));
-                            break;
-                        }
-                    }
-                    if found.is_some() { break; }
+        let (item, button) = match find_shop_button(&self.shared_state.shop_file().shops, custom_id) {
+            Some(found) => found,
+            None => {
+                // Cleanup panels posted before their buttons moved to `destroy:`.
+                if let Some(profile_id) = custom_id.strip_prefix("destroy_") {
+                    self.handle_destroy_command(ctx, component, profile_id).await;
                 }
-                if found.is_some() { break; }
-            }
-            
-            match found {
-                Some(f) => f,
-                none => return,
+                return;
             }
         };
         
-        let _ = component.defer_ephemeral(&ctx.http).await;
-        
         let user_id = component.user.id.to_string();
         
-        let player = match self.shared_state.db.get_player_by_discord_id(&user_id).unwrap() {
-            Some(p) => p,
-            none => {
-                let content = CreateInteractionResponseFollowup::new()
-                    .content("ไม่พบข้อมูลผู้เล่นของคุณในระบบ! กรุณาลงทะเบียนด้วย !register <steam_id>")
-                    .ephemeral(true);
-                let _ = component.create_followup(&ctx.http, content).await;
+        if self.shared_state.db.get_cart_mode(&user_id).unwrap_or(false) {
+            let content = match self.shared_state.db.add_cart_item(&user_id, custom_id) {
+                Ok(count) => format!("🛒 เพิ่ม **{}** ({}) ลงตะกร้าแล้ว ({} รายการ) ใช้ /cart view เพื่อดูและชำระเงิน", item.name, button.text, count),
+                Err(e) => format!("❌ เกิดข้อผิดพลาด: {:?}", e),
+            };
+            respond_ephemeral(ctx, component, content).await;
+            return;
+        }
+        
+        if let Err(message) = self.check_purchase_allowed(component, &user_id, &button) {
+            respond_ephemeral(ctx, component, message).await;
+            return;
+        }
+        
+        let player = match self.shared_state.db.get_player_by_discord_id(&user_id) {
+            Ok(Some(p)) => p,
+            _ => {
+                respond_ephemeral(ctx, component, "ไม่พบข้อมูลผู้เล่นของคุณในระบบ! กรุณาลงทะเบียนด้วย /register").await;
                 return;
             }
         };
         
-        if component.channel_id.get() != self.shared_state.config.exempt_channel_id {
-            if let Some(remaining) = self.shared_state.check_cooldown(&user_id, custom_id) {
-                let content = CreateInteractionResponseFollowup::new()
-                    .content(format!("สินค้าดังกล่าวอยู่ในช่วง cooldown กรุณารออีก {} วินาที", remaining.as_secs()))
-                    .ephemeral(true);
-                let _ = component.create_followup(&ctx.http, content).await;
+        let member = match component.member.as_ref() {
+            Some(member) => member,
+            None => {
+                respond_ephemeral(ctx, component, MEMBER_REQUIRED_MESSAGE).await;
                 return;
             }
-        }
-        
-        let vip_info = self.get_vip_tier(&component.member.as_ref().unwrap().roles);
-        let discount = vip_info.as_ref().map(|v| v.discount).unwrap_or(0.0);
-        let (_original_price, discounted_price, _discount_percent) = 
-            calculate_discounted_price(item.price, button.quantity, discount);
+        };
+        let vip_info = get_vip_tier(&self.shared_state.config, &member.roles);
+        let price = quote(&self.shared_state, &item, &button, vip_info);
+        let (discounted_price, voucher) = with_voucher(&self.shared_state, &user_id, price.total);
         
         if player.coin < discounted_price as i32 {
-            let content = CreateInteractionResponseFollowup::new()
-                .content(format!("คุณมี coin ไม่พอ (ต้องการ {}, มี {})", discounted_price, player.coin))
-                .ephemeral(true);
-            let _ = component.create_followup(&ctx.http, content).await;
+            respond_ephemeral(ctx, component, format!("คุณมี coin ไม่พอ (ต้องการ {}, มี {})", discounted_price, player.coin)).await;
             return;
         }
         
-        if !self.shared_state.db.remove_coin(&user_id, discounted_price as i32).unwrap_or(false) {
-            let content = CreateInteractionResponseFollowup::new()
-                .content("เกิดข้อผิดพลาดในการหัก coin!")
-                .ephemeral(true);
-            let _ = component.create_followup(&ctx.http, content).await;
+        let confirm_threshold = self.shared_state.db.get_confirm_threshold(&user_id).unwrap_or(0);
+        if (discounted_price as i64) < confirm_threshold {
+            // Charging, queueing and the receipt DM can outlast the 3 seconds Discord waits.
+            let _ = component.defer_ephemeral(&ctx.http).await;
+            let content = match self.execute_purchase(ctx, component, custom_id).await {
+                Ok(message) | Err(message) => message,
+            };
+            let _ = component.edit_response(&ctx.http, EditInteractionResponse::new().content(content)).await;
             return;
         }
         
-        let mut commands_with_steam = substitute_steam_id_in_commands(&button.commands, &player.steam_id);
+        let token = component.id.to_string();
+        self.shared_state.add_pending_purchase(&token, &user_id, custom_id);
         
-        for cmd in &mut commands_with_steam {
-            if !is_special_command(cmd, &self.shared_state.config.special_commands) && !cmd.contains("Location") {
-                cmd.push_str(&format!(" Location {}", player.steam_id));
-            }
+        let vip_text = match vip_info {
+            Some(vip) if price.vip_percent == 0 && price.sale.is_some() => format!("{} (ไม่รวมกับโปรโมชั่น)", vip.name),
+            Some(vip) => format!("{} (ส่วนลด {}%)", vip.name, price.vip_percent),
+            None => "-".to_string(),
+        };
+        let sale_text = match &price.sale {
+            Some(sale) => format!("{} ({})", sale.name, sale.describe()),
+            None => "-".to_string(),
+        };
+        
+        let mut embed = CreateEmbed::new()
+            .title("🛒 ยืนยันการซื้อ")
+            .color(0x9900cc)
+            .field("🛒 สินค้า", format!("**{}** x{}", item.name, button.quantity), false)
+            .field("💷 ราคาปกติ", format!("{} coin", price.original), true)
+            .field("🔥 โปรโมชั่น", sale_text, true)
+            .field("👑 VIP", vip_text, true);
+        if let Some(voucher) = &voucher {
+            embed = embed.field("🎟️ โค้ดส่วนลด", format!("{} (ลด {}%)", voucher.code, voucher.percent), true);
         }
+        let embed = embed
+            .field("🏷️ ส่วนลด", format!("-{} coin", price.original - discounted_price), true)
+            .field("💰 ราคาสุทธิ", format!("**{}** coin", discounted_price), true)
+            .field("💷 Coin คงเหลือหลังซื้อ", format!("**{}** coin", player.coin - discounted_price as i32), true)
+            .footer(CreateEmbedFooter::new(format!(
+                "กรุณายืนยันภายใน {} วินาที • ตั้งค่าข้ามการยืนยันได้ที่ /settings",
+                PURCHASE_CONFIRM_TIMEOUT_SECS
+            )));
+        
+        let buttons = CreateActionRow::Buttons(vec![
+            CreateButton::new(format!("buy_confirm:{}", token))
+                .label("ยืนยัน")
+                .style(ButtonStyle::Success),
+            CreateButton::new(format!("buy_cancel:{}", token))
+                .label("ยกเลิก")
+                .style(ButtonStyle::Secondary),
+        ]);
+        
+        let response = CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .embed(embed)
+                .components(vec![buttons])
+                .ephemeral(true)
+        );
+        let _ = component.create_response(&ctx.http, response).await;
+        
+        let http = ctx.http.clone();
+        let shared_state = self.shared_state.clone();
+        let component = component.clone();
+        tokio::spawn(async move {
+            sleep(Duration::from_secs(PURCHASE_CONFIRM_TIMEOUT_SECS)).await;
+            if shared_state.take_pending_purchase(&token).is_some() {
+                let edit = EditInteractionResponse::new()
+                    .content("⌛ หมดเวลายืนยันการซื้อ กรุณากดซื้อใหม่อีกครั้ง")
+                    .embeds(vec![])
+                    .components(vec![]);
+                let _ = component.edit_response(&http, edit).await;
+            }
+        });
+    }
+    
+    async fn handle_purchase_confirmation(&self, ctx: &Context, component: &ComponentInteraction, token: &str, confirmed: bool) {
+        let pending = match self.shared_state.take_pending_purchase(token) {
+            Some(pending) if pending.user_id == component.user.id.to_string() => pending,
+            Some(pending) => {
+                // Ephemeral messages can't be clicked by others, but keep the entry intact just in case.
+                self.shared_state.add_pending_purchase(token, &pending.user_id, &pending.trigger);
+                return;
+            },
+            None => {
+                update_message(ctx, component, "⌛ หมดเวลายืนยันการซื้อ กรุณากดซื้อใหม่อีกครั้ง").await;
+                return;
+            }
+        };
         
-        {
-            let mut queue = self.shared_state.command_queue.lock().await;
-            queue.push(commands_with_steam);
+        if !confirmed {
+            update_message(ctx, component, "❎ ยกเลิกการซื้อแล้ว").await;
+            return;
         }
         
-        let _ = self.shared_state.db.log_purchase(&user_id, &player.steam_id, &item.name, discounted_price as i32);
+        let _ = component.create_response(&ctx.http, CreateInteractionResponse::Acknowledge).await;
+        let content = match self.execute_purchase(ctx, component, &pending.trigger).await {
+            Ok(message) | Err(message) => message,
+        };
+        let edit = EditInteractionResponse::new()
+            .content(content)
+            .embeds(vec![])
+            .components(vec![]);
+        let _ = component.edit_response(&ctx.http, edit).await;
+    }
+    
+    /// Checks shared by the first click and the confirmation: cleanup lock and item cooldown.
+    fn check_purchase_allowed(&self, component: &ComponentInteraction, user_id: &str, button: &ShopButton) -> Result<(), String> {
+        if self.shared_state.destroy_lock.try_lock().is_err() {
+            return Err("❌ บอทกำลังลบขยะ กรุณาลองใหม่อีกครั้งภายหลัง".to_string());
+        }
         
         if component.channel_id.get() != self.shared_state.config.exempt_channel_id {
-            self.shared_state.set_cooldown(&user_id, custom_id);
+            if let Some(remaining) = self.shared_state.check_cooldown(user_id, &button.trigger) {
+                return Err(format!("สินค้าดังกล่าวอยู่ในช่วง cooldown กรุณารออีก {} วินาที", remaining.as_secs()));
+            }
         }
         
-        let content = CreateInteractionResponseFollowup::new()
-            .content(format!("✅ ซื้อ {} สำเร็จ! หัก {} coins (เหลือ {} coins)", 
-                item.name, discounted_price, player.coin - discounted_price as i32))
-            .ephemeral(true);
-        let _ = component.create_followup(&ctx.http, content).await;
-        
-        if let Ok(dm_channel) = component.user.create_dm_channel(&ctx.http).await {
-            let embed = CreateEmbed::new()
-                .title("⚡แจ้งเตือนการหัก Coin")
-                .color(0x9900cc)
-                .field("🛒 สินค้าที่ซื้อ", format!("**{}** x{}", item.name, button.quantity), false)
-                .field("💷 ราคา", format!("**{}** coin", discounted_price), true)
-                .field("💷 Coin คงเหลือ", format!("**{}** coin", player.coin - discounted_price as i32), false)
-                .footer(CreateEmbedFooter::new("© powered by TimeSkip"));
-            
-            let message = CreateMessage::new().embed(embed);
-            let _ = dm_channel.send_message(&ctx.http, message).await;
-        }
+        Ok(())
     }
     
-    fn get_vip_tier(&self, roles: &[RoleId]) -> Option<&VipRole> {
-        let mut highest_tier: Option<&VipRole> = None;
+    /// Charges the player and queues the item's commands. Every check is repeated here
+    /// because time may have passed since the confirmation was shown.
+    async fn execute_purchase(&self, ctx: &Context, component: &ComponentInteraction, trigger: &str) -> Result<String, String> {
+        let (item, button) = find_shop_button(&self.shared_state.shop_file().shops, trigger)
+            .ok_or_else(|| "❌ ไม่พบสินค้านี้แล้ว".to_string())?;
         
-        for role_id in roles {
-            if let Some(vip_info) = self.shared_state.config.vip_roles.get(&role_id.get()) {
-                if highest_tier.is_none() || vip_info.tier > highest_tier.unwrap().tier {
-                    highest_tier = Some(vip_info);
-                }
-            }
+        let user_id = component.user.id.to_string();
+        self.check_purchase_allowed(component, &user_id, &button)?;
+        
+        let player = match self.shared_state.db.get_player_by_discord_id(&user_id) {
+            Ok(Some(p)) => p,
+            _ => return Err("ไม่พบข้อมูลผู้เล่นของคุณในระบบ! กรุณาลงทะเบียนด้วย /register".to_string()),
+        };
+        
+        let member = component.member.as_ref().ok_or_else(|| MEMBER_REQUIRED_MESSAGE.to_string())?;
+        let vip_info = get_vip_tier(&self.shared_state.config, &member.roles);
+        let price = quote(&self.shared_state, &item, &button, vip_info).total;
+        let (discounted_price, voucher) = with_voucher(&self.shared_state, &user_id, price);
+        
+        let mut line = purchase_line(&item, &button, discounted_price);
+        line.stash_until = stash_until(&self.shared_state, &user_id);
+        let stashed = line.stash_until.is_some();
+        let voucher_id = voucher.map(|voucher| voucher.redemption_id);
+        let (remaining_coin, purchase_ids) = match self.shared_state.db.charge_purchase(&user_id, &player.steam_id, &[line], voucher_id) {
+            Ok(PurchaseOutcome::Charged { remaining, purchase_ids }) => (remaining, purchase_ids),
+            Ok(outcome) => return Err(rejection_message(&outcome, discounted_price, player.coin)),
+            Err(_) => return Err("เกิดข้อผิดพลาดในการหัก coin!".to_string()),
+        };
+        
+        let draws = if stashed {
+            Vec::new()
+        } else {
+            let (discord_name, player_name) = template_names(&component.user, component.member.as_ref());
+            let context = TemplateContext {
+                steam_id: &player.steam_id,
+                discord_name: &discord_name,
+                player_name: &player_name,
+                quantity: button.quantity,
+                purchase_id: purchase_ids.first().copied().unwrap_or(0),
+            };
+            let (commands, draws) = delivery_commands(&self.shared_state, &item, &button, &context, &user_id);
+            let mut queue = self.shared_state.command_queue.lock().await;
+            queue.push(commands);
+            draws
+        };
+        
+        if component.channel_id.get() != self.shared_state.config.exempt_channel_id {
+            self.shared_state.set_cooldown(&user_id, trigger, button.cooldown());
         }
         
-        highest_tier
+        if button.stock.is_some() {
+            let http = ctx.http.clone();
+            let shared_state = self.shared_state.clone();
+            let triggers = vec![button.trigger.clone()];
+            tokio::spawn(async move {
+                crate::shop::refresh_item_messages(&http, &shared_state, &triggers).await;
+            });
+        }
+        
+        let receipt = ReceiptLine { item_name: item.name.clone(), quantity: button.quantity, price: discounted_price };
+        send_receipt(&ctx.http, &component.user, &[receipt], remaining_coin).await;
+        
+        if stashed {
+            return Ok(format!("📦 ซื้อ {} สำเร็จ! เก็บไว้ในคลังแล้ว ใช้ /claim เพื่อรับของ (เหลือ {} coins)", item.name, remaining_coin));
+        }
+        Ok(append_draws(format!("✅ ซื้อ {} สำเร็จ! หัก {} coins (เหลือ {} coins)", item.name, discounted_price, remaining_coin), &draws))
     }
 }
 
@@ -427,60 +475,33 @@ pub async fn process_command_queue(shared_state: Arc<SharedState>) {
     }
 }
 
-pub async fn auto_destroy_items_type1(shared_state: Arc<SharedState>) {
+/// Keeps the cooldown table from growing without bound.
+pub async fn evict_expired_cooldowns(shared_state: Arc<SharedState>) {
     loop {
-        sleep(Duration::from_secs(1800)).await; 
-        
-        let _guard = shared_state.destroy_lock.lock().await;
+        sleep(Duration::from_secs(COOLDOWN_EVICTION_INTERVAL_SECS)).await;
         
-        info!("👕 Starting automatic clothes/outfit destruction");
-        
-        send_commands_to_game(
-            vec!["#Announce บอทกำลังลบชุด/เสื้อผ้าอัตโนมัติ บอทหยุดชั่วคราว!".to_string()],
-            "destroy"
-        ).await;
-        
-        sleep(Duration::from_secs(1)).await;
-        
-        send_commands_to_game(
-            shared_state.config.destroy_commands_type1.clone(),
-            "destroy"
-        ).await;
-        
-        send_commands_to_game(
-            vec!["#Announce บอทลบชุด/เสื้อผ้าอัตโนมัติสำเร็จ สามารถใช้บอทต่อได้!".to_string()],
-            "destroy"
-        ).await;
-        
-        info!("✅ Automatic clothes/outfit destruction completed");
+        let removed = shared_state.evict_expired_cooldowns();
+        if removed > 0 {
+            info!("Evicted {} expired cooldowns", removed);
+        }
     }
 }
 
-pub async fn auto_destroy_items_type2(shared_state: Arc<SharedState>) {
-    loop {
-        sleep(Duration::from_secs(7200)).await; 
-        
-        let _guard = shared_state.destroy_lock.lock().await;
-        
-        info!("🔨 Starting automatic construction materials destruction");
-        
-        send_commands_to_game(
-            vec!["#Announce บอทกำลังลบวัสดุก่อสร้าง บอทหยุดชั่วคราว!".to_string()],
-            "destroy"
-        ).await;
-        
-        sleep(Duration::from_secs(1)).await;
-        
-        send_commands_to_game(
-            shared_state.config.destroy_commands_type2.clone(),
-            "destroy"
-        ).await;
-        
-        send_commands_to_game(
-            vec!["#Announce บอทลบวัสดุก่อสร้าง สามารถใช้บอทต่อได้!".to_string()],
-            "destroy"
-        ).await;
-        
-        info!("✅ Automatic construction materials destruction completed");
-    }
-}
\ No newline at end of file
+async fn respond_ephemeral(ctx: &Context, component: &ComponentInteraction, content: impl Into<String>) {
+    let response = CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .content(content)
+            .ephemeral(true)
+    );
+    let _ = component.create_response(&ctx.http, response).await;
+}
+
+async fn update_message(ctx: &Context, component: &ComponentInteraction, content: impl Into<String>) {
+    let response = CreateInteractionResponse::UpdateMessage(
+        CreateInteractionResponseMessage::new()
+            .content(content)
+            .embeds(vec![])
+            .components(vec![])
+    );
+    let _ = component.create_response(&ctx.http, response).await;
+}
@@ -0,0 +1,180 @@
+use std::collections::BTreeMap;
+
+use crate::config::{ButtonCommand, ShopConfig};
+
+/// Kit entries are commands or references to other kits.
+pub type Kits = BTreeMap<String, Vec<ButtonCommand>>;
+
+/// Fills every button's `resolved_commands` from its own commands (or the item's shared ones)
+/// with kit references expanded. Every kit is checked too, so an unused broken kit is still
+/// reported.
+pub fn resolve_commands(shops: &mut [ShopConfig], kits: &Kits) -> Result<(), String> {
+    for (name, entries) in kits {
+        let mut path = vec![name.clone()];
+        expand(entries, kits, Some(1), &mut path, &mut Vec::new())
+            .map_err(|e| format!("Kit '{}': {}", name, e))?;
+    }
+
+    for shop in shops.iter_mut() {
+        for item in &mut shop.items {
+            for button in &mut item.buttons {
+                let commands = if button.commands.is_empty() { &item.commands } else { &button.commands };
+                let mut resolved = Vec::new();
+                expand(commands, kits, None, &mut Vec::new(), &mut resolved)
+                    .map_err(|e| format!("Button '{}' of '{}': {}", button.trigger, item.name, e))?;
+                button.resolved_commands = resolved;
+            }
+            if let Some(loot) = &mut item.loot {
+                for outcome in &mut loot.outcomes {
+                    let mut resolved = Vec::new();
+                    expand(&outcome.commands, kits, None, &mut Vec::new(), &mut resolved)
+                        .map_err(|e| format!("Loot outcome '{}' of '{}': {}", outcome.name, item.name, e))?;
+                    outcome.resolved_commands = resolved;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// `count` is the product of the multipliers of the kits we're inside, `None` at the top level
+/// where `{count}` isn't defined.
+fn expand(
+    entries: &[ButtonCommand],
+    kits: &Kits,
+    count: Option<u32>,
+    path: &mut Vec<String>,
+    resolved: &mut Vec<ButtonCommand>,
+) -> Result<(), String> {
+    for entry in entries {
+        match entry {
+            ButtonCommand::Kit { kit, count: multiplier } => {
+                if *multiplier == 0 {
+                    return Err(format!("kit '{}' has count 0", kit));
+                }
+                if path.contains(kit) {
+                    return Err(format!("kit cycle {} -> {}", path.join(" -> "), kit));
+                }
+                let kit_entries = kits.get(kit).ok_or_else(|| format!("unknown kit '{}'", kit))?;
+
+                path.push(kit.clone());
+                let count = count.unwrap_or(1).saturating_mul(*multiplier);
+                expand(kit_entries, kits, Some(count), path, resolved)?;
+                path.pop();
+            },
+            ButtonCommand::Plain(command) => {
+                resolved.push(ButtonCommand::Plain(substitute_count(command, count)));
+            },
+            ButtonCommand::Targeted { command, delivery } => {
+                resolved.push(ButtonCommand::Targeted {
+                    command: substitute_count(command, count),
+                    delivery: delivery.clone(),
+                });
+            },
+        }
+    }
+
+    Ok(())
+}
+
+/// Replaces the word `count` inside `{...}` with the kit multiplier, so `{count}` and
+/// `{count*30}` become plain numbers for the template engine.
+fn substitute_count(command: &str, count: Option<u32>) -> String {
+    let count = match count {
+        Some(count) => count.to_string(),
+        None => return command.to_string(),
+    };
+
+    let mut result = String::with_capacity(command.len());
+    let mut rest = command;
+    while let Some(open) = rest.find('{') {
+        result.push_str(&rest[..open]);
+        if rest[open..].starts_with("{{") {
+            result.push_str("{{");
+            rest = &rest[open + 2..];
+            continue;
+        }
+        let close = match rest[open..].find('}') {
+            Some(close) => open + close,
+            None => {
+                rest = &rest[open..];
+                break;
+            }
+        };
+
+        result.push('{');
+        result.push_str(&replace_word(&rest[open + 1..close], "count", &count));
+        result.push('}');
+        rest = &rest[close + 1..];
+    }
+    result.push_str(rest);
+    result
+}
+
+fn replace_word(text: &str, word: &str, replacement: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut current = String::new();
+    for c in text.chars().chain(std::iter::once(' ')) {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            current.push(c);
+            continue;
+        }
+        result.push_str(if current == word { replacement } else { &current });
+        current.clear();
+        result.push(c);
+    }
+    result.pop();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain(command: &str) -> ButtonCommand {
+        ButtonCommand::Plain(command.to_string())
+    }
+
+    fn kit(name: &str, count: u32) -> ButtonCommand {
+        ButtonCommand::Kit { kit: name.to_string(), count }
+    }
+
+    fn expand_top(entries: &[ButtonCommand], kits: &Kits) -> Result<Vec<String>, String> {
+        let mut resolved = Vec::new();
+        expand(entries, kits, None, &mut Vec::new(), &mut resolved)?;
+        Ok(resolved.iter().map(|command| command.command().to_string()).collect())
+    }
+
+    #[test]
+    fn cycles_are_rejected() {
+        let kits = Kits::from([
+            ("a".to_string(), vec![kit("b", 1)]),
+            ("b".to_string(), vec![plain("#SpawnItem Nails 1"), kit("a", 1)]),
+        ]);
+        let error = resolve_commands(&mut [], &kits).unwrap_err();
+        assert!(error.contains("kit cycle a -> b -> a"), "{}", error);
+    }
+
+    #[test]
+    fn nested_counts_multiply() {
+        let kits = Kits::from([
+            ("outer".to_string(), vec![kit("inner", 3)]),
+            ("inner".to_string(), vec![plain("#SpawnItem Nails {count}"), plain("#SpawnItem Bolts {count*10}")]),
+        ]);
+        assert_eq!(expand_top(&[kit("outer", 2)], &kits).unwrap(), vec![
+            "#SpawnItem Nails {6}",
+            "#SpawnItem Bolts {6*10}",
+        ]);
+        assert!(expand_top(&[kit("outer", 0)], &kits).unwrap_err().contains("count 0"));
+    }
+
+    #[test]
+    fn only_the_count_word_is_replaced() {
+        let kits = Kits::from([
+            ("set".to_string(), vec![plain("#Announce {{count}} {count} {count_x} {quantity}")]),
+        ]);
+        assert_eq!(expand_top(&[kit("set", 2)], &kits).unwrap(), vec!["#Announce {{count}} {2} {count_x} {quantity}"]);
+        assert_eq!(expand_top(&[plain("{count}")], &kits).unwrap(), vec!["{count}"]);
+    }
+}
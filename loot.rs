@@ -0,0 +1,269 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serenity::builder::*;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use log::{error, info};
+
+use crate::commands::{integer_option, reply, require_permission, truncate, user_option};
+use crate::config::{LootTable, ShopButton, ShopItem};
+use crate::database::LootDraw;
+use crate::permissions::Permission;
+use crate::purchase::build_delivery_commands;
+use crate::shared_state::SharedState;
+use crate::stash::discord_time;
+use crate::template::TemplateContext;
+
+/// Picks an outcome of `table` by weight and returns its index. `pity` is the player's rolls
+/// in a row without a rare outcome; on the table's pity roll only rare outcomes can come up.
+/// The same seeded `StdRng` always gives the same outcome.
+pub fn roll(table: &LootTable, pity: u32, rng: &mut impl Rng) -> usize {
+    let guaranteed = table.pity.is_some_and(|after| pity.saturating_add(1) >= after);
+    let candidates: Vec<(usize, u64)> = table.outcomes.iter()
+        .enumerate()
+        .filter(|(_, outcome)| outcome.rare || !guaranteed)
+        .map(|(index, outcome)| (index, outcome.weight as u64))
+        .collect();
+
+    // Tables are checked at load: weights are positive and pity needs a rare outcome.
+    let total: u64 = candidates.iter().map(|(_, weight)| weight).sum();
+    let mut pick = rng.gen_range(0..total);
+    for (index, weight) in &candidates {
+        if pick < *weight {
+            return *index;
+        }
+        pick -= weight;
+    }
+    candidates.last().map(|(index, _)| *index).unwrap_or(0)
+}
+
+/// How live rolls are made, so any roll in the audit log can be made again from its seed
+/// and pity.
+pub fn roll_seeded(table: &LootTable, pity: u32, seed: u64) -> usize {
+    roll(table, pity, &mut StdRng::seed_from_u64(seed))
+}
+
+/// `count` rolls in a row from the player's pity counter, each with a fresh seed.
+fn draw(table: &LootTable, mut pity: u32, count: u32) -> Vec<LootDraw> {
+    let mut rng = rand::thread_rng();
+    (0..count)
+        .map(|_| {
+            let seed = rng.gen();
+            let outcome = &table.outcomes[roll_seeded(table, pity, seed)];
+            let draw = LootDraw {
+                seed,
+                outcome: outcome.name.clone(),
+                rarity: outcome.rarity.clone(),
+                rare: outcome.rare,
+                pity,
+            };
+            pity = if outcome.rare { 0 } else { pity.saturating_add(1) };
+            draw
+        })
+        .collect()
+}
+
+/// What delivering `button` of `item` runs. A loot box also rolls one outcome per unit for
+/// `discord_id`, the player opening it: the rolls are saved, rare drops are announced in game,
+/// and the rolls are returned to show the player.
+pub fn delivery_commands(
+    shared_state: &SharedState,
+    item: &ShopItem,
+    button: &ShopButton,
+    context: &TemplateContext,
+    discord_id: &str,
+) -> (Vec<String>, Vec<LootDraw>) {
+    let mut commands = build_delivery_commands(&shared_state.config, &button.resolved_commands, context);
+    let table = match &item.loot {
+        Some(table) => table,
+        None => return (commands, Vec::new()),
+    };
+
+    let purchase_id = (context.purchase_id != 0).then_some(context.purchase_id);
+    let draws = match shared_state.db.open_loot(discord_id, &item.name, purchase_id, |pity| draw(table, pity, button.quantity)) {
+        Ok(draws) => draws,
+        Err(e) => {
+            // Already paid for, so the player still gets their rolls, just without pity.
+            error!("Failed to save loot rolls of '{}' for {}: {:?}", item.name, discord_id, e);
+            draw(table, 0, button.quantity)
+        }
+    };
+
+    // Each roll is one unit, so `{quantity}` in outcome commands is 1.
+    let outcome_context = TemplateContext { quantity: 1, ..*context };
+    for draw in &draws {
+        info!("{} opened '{}': {} ({})", discord_id, item.name, draw.outcome, draw.rarity);
+        if let Some(outcome) = table.outcomes.iter().find(|outcome| outcome.name == draw.outcome) {
+            commands.extend(build_delivery_commands(&shared_state.config, &outcome.resolved_commands, &outcome_context));
+        }
+        if draw.rare {
+            commands.push(format!("#Announce {} เปิด {} ได้ {} ({})!", context.player_name, item.name, draw.outcome, draw.rarity));
+        }
+    }
+
+    (commands, draws)
+}
+
+/// `⭐ **AK47** (Legendary), **Bandage** (Common)`
+pub fn describe_draws(draws: &[LootDraw]) -> String {
+    let outcomes: Vec<String> = draws.iter()
+        .map(|draw| {
+            let star = if draw.rare { "⭐ " } else { "" };
+            format!("{}**{}** ({})", star, draw.outcome, draw.rarity)
+        })
+        .collect();
+    truncate(&outcomes.join(", "), 1024)
+}
+
+/// Adds what a loot box gave to a purchase reply.
+pub fn append_draws(content: String, draws: &[LootDraw]) -> String {
+    if draws.is_empty() {
+        return content;
+    }
+    format!("{}\n🎲 ได้รับ: {}", content, describe_draws(draws))
+}
+
+/// Drop rates for the shop embed.
+pub fn describe_odds(table: &LootTable) -> String {
+    let total: u64 = table.outcomes.iter().map(|outcome| outcome.weight as u64).sum();
+    let mut lines: Vec<String> = table.outcomes.iter()
+        .map(|outcome| {
+            let star = if outcome.rare { "⭐ " } else { "" };
+            let percent = outcome.weight as f64 * 100.0 / total as f64;
+            format!("{}{} • {}: {:.1}%", star, outcome.rarity, outcome.name, percent)
+        })
+        .collect();
+    if let Some(pity) = table.pity {
+        lines.push(format!("การันตี ⭐ ภายใน {} ครั้ง", pity));
+    }
+    truncate(&lines.join("\n"), 1024)
+}
+
+pub fn lootlog_command() -> CreateCommand {
+    CreateCommand::new("lootlog")
+        .description("ดูประวัติการเปิดกล่องสุ่ม (Admin)")
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::User, "user", "ดูเฉพาะผู้เล่นคนนี้")
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::Boolean, "rare", "แสดงเฉพาะของหายาก")
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::Integer, "limit", "จำนวนรายการ (สูงสุด 25)")
+                .min_int_value(1)
+                .max_int_value(25)
+        )
+}
+
+pub async fn handle_lootlog_command(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState) {
+    if !require_permission(ctx, command, shared_state, Permission::ViewReports).await {
+        return;
+    }
+
+    let options = command.data.options();
+    let user_id = user_option(&options, "user").map(|user| user.id.to_string());
+    let rare_only = options.iter().any(|option| {
+        option.name == "rare" && matches!(option.value, ResolvedValue::Boolean(true))
+    });
+    let limit = integer_option(&options, "limit").unwrap_or(10).clamp(1, 25) as u32;
+
+    match shared_state.db.get_loot_rolls(user_id.as_deref(), rare_only, limit) {
+        Ok(rolls) if rolls.is_empty() => reply(ctx, command, "ไม่มีประวัติการเปิดกล่องสุ่ม").await,
+        Ok(rolls) => {
+            let lines: Vec<String> = rolls.iter()
+                .map(|roll| {
+                    let star = if roll.draw.rare { "⭐ " } else { "" };
+                    let mut line = format!(
+                        "#{} {} <@{}> {} → {}**{}** ({}) • pity {} • seed `{}`",
+                        roll.id, discord_time(&roll.created_at), roll.discord_id, roll.item_name,
+                        star, roll.draw.outcome, roll.draw.rarity, roll.draw.pity, roll.draw.seed
+                    );
+                    if let Some(purchase_id) = roll.purchase_id {
+                        line.push_str(&format!(" `purchase:{}`", purchase_id));
+                    }
+                    line
+                })
+                .collect();
+            let embed = CreateEmbed::new()
+                .title("🎲 ประวัติการเปิดกล่องสุ่ม")
+                .color(0x9900cc)
+                .description(truncate(&lines.join("\n"), 4000))
+                .footer(CreateEmbedFooter::new("© powered by TimeSkip"));
+            let response = CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .embed(embed)
+                    .ephemeral(true)
+            );
+            let _ = command.create_response(&ctx.http, response).await;
+        },
+        Err(e) => reply(ctx, command, format!("❌ เกิดข้อผิดพลาด: {:?}", e)).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LootOutcome;
+
+    const PITY: u32 = 10;
+
+    fn outcome(name: &str, weight: u32, rare: bool) -> LootOutcome {
+        LootOutcome {
+            name: name.to_string(),
+            rarity: name.to_string(),
+            weight,
+            rare,
+            commands: Vec::new(),
+            resolved_commands: Vec::new(),
+        }
+    }
+
+    fn table() -> LootTable {
+        LootTable {
+            outcomes: vec![outcome("Common", 70, false), outcome("Epic", 25, false), outcome("Legendary", 5, true)],
+            pity: Some(PITY),
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_same_draws() {
+        let table = table();
+        for seed in 0..1000 {
+            assert_eq!(roll_seeded(&table, 0, seed), roll_seeded(&table, 0, seed));
+        }
+
+        let mut first = StdRng::seed_from_u64(7);
+        let mut second = StdRng::seed_from_u64(7);
+        let first: Vec<usize> = (0..100).map(|_| roll(&table, 0, &mut first)).collect();
+        let second: Vec<usize> = (0..100).map(|_| roll(&table, 0, &mut second)).collect();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn weights_are_respected() {
+        let table = table();
+        let mut rng = StdRng::seed_from_u64(42);
+        let rolls = 100_000;
+        let mut counts = [0u32; 3];
+        for _ in 0..rolls {
+            counts[roll(&table, 0, &mut rng)] += 1;
+        }
+
+        for (count, outcome) in counts.iter().zip(&table.outcomes) {
+            let share = *count as f64 * 100.0 / rolls as f64;
+            assert!((share - outcome.weight as f64).abs() < 1.0, "{} came up {:.2}%", outcome.name, share);
+        }
+    }
+
+    #[test]
+    fn pity_forces_rare_and_resets() {
+        let table = table();
+        assert!((0..1000).any(|seed| !table.outcomes[roll_seeded(&table, PITY - 2, seed)].rare));
+        assert!((0..1000).all(|seed| table.outcomes[roll_seeded(&table, PITY - 1, seed)].rare));
+
+        let draws = draw(&table, PITY - 1, 2);
+        assert!(draws[0].rare);
+        assert_eq!(draws[0].pity, PITY - 1);
+        assert_eq!(draws[1].pity, 0);
+    }
+}
@@ -5,10 +5,29 @@ mod maintenance;
 mod shared_state;
 mod utils;
 mod handlers;
+mod cleanup;
+mod permissions;
+mod purchase;
+mod cart;
+mod shop;
+mod catalog;
+mod search;
+mod gift;
+mod stash;
+mod template;
+mod kits;
+mod shopfile;
+mod scum_items;
+mod shopedit;
+mod shop_versions;
+mod promotions;
+mod promo_codes;
+mod loot;
 
 use std::env;
 use std::sync::Arc;
 use serenity::prelude::*;
+use serenity::model::id::GuildId;
 use dotenv::dotenv;
 use log::info;
 
@@ -21,6 +40,18 @@ use crate::handlers::Handler;
 async fn main() {
     env_logger::init();
     
+    let args: Vec<String> = env::args().collect();
+    if let Some(result) = shopfile::run_tool(&args).or_else(|| scum_items::run_tool(&args)) {
+        match result {
+            Ok(message) => println!("{}", message),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    
     println!("Starting Discord bot...");
     
     dotenv().ok();
@@ -32,7 +63,7 @@ async fn main() {
     
     println!("Token loaded successfully (length: {})", token.len());
     
-    let _guild_id = env::var("GUILD_ID")
+    let guild_id = env::var("GUILD_ID")
         .expect("Expected GUILD_ID in environment")
         .parse::<u64>()
         .expect("GUILD_ID must be a valid u64");
@@ -44,8 +75,6 @@ async fn main() {
     let db = Database::new().expect("Failed to initialize database");
     db.create_tables().expect("Failed to create tables");
     
-    db.add_authorized_user("").ok(); // เพิ่ม authorized user ตรงนี้ เป็น discord id
-    
     println!("Loading configuration...");
     let config = match Config::load() {
         Ok(cfg) => {
@@ -54,7 +83,7 @@ async fn main() {
         },
         Err(e) => {
             eprintln!("Failed to load configuration: {}", e);
-            eprintln!("Please check your botshop.json file");
+            eprintln!("Please check your shop file ({})", shopfile::SHOP_FILES.join(", "));
             return;
         }
     };
@@ -62,15 +91,21 @@ async fn main() {
     println!("Creating shared state...");
     let shared_state = Arc::new(SharedState::new(db, config));
     println!("Shared state created");
+    
+    // Catches edits made to the shop file while the bot was offline.
+    let summary = format!("พบการแก้ไขใน {} ตอนเริ่มบอท", shared_state.config.shop_path);
+    match shop_versions::record_version(&shared_state.db, shop_versions::FILE_AUTHOR, &summary, &shared_state.shop_file()) {
+        Ok(Some(id)) => println!("Saved shop file as version #{}", id),
+        Ok(None) => println!("Shop file unchanged since the last saved version"),
+        Err(e) => eprintln!("Failed to save shop version: {}", e),
+    }
 
-    let intents = GatewayIntents::GUILDS 
-        | GatewayIntents::GUILD_MESSAGES
-        | GatewayIntents::MESSAGE_CONTENT;
+    let intents = GatewayIntents::GUILDS;
     
     println!("Building Discord client...");
     
     let mut client = match Client::builder(&token, intents)
-        .event_handler(Handler::new(shared_state.clone()))
+        .event_handler(Handler::new(shared_state.clone(), GuildId::new(guild_id)))
         .await {
         Ok(client) => {
             println!("Client built successfully");
@@ -98,7 +133,7 @@ async fn main() {
             eprintln!("Client error: {:?}", why);
             eprintln!("\nCommon issues:");
             eprintln!("1. Invalid token - check DISCORD_TOKEN in .env");
-            eprintln!("2. Missing intents - enable MESSAGE CONTENT INTENT in Discord Developer Portal");
+            eprintln!("2. Bot was invited without the applications.commands scope");
             eprintln!("3. No internet connection");
             eprintln!("4. Discord API is down");
             eprintln!("5. Bot doesn't have access to the guild");
@@ -1,32 +1,17 @@
-use chrono::{NaiveTime, Local, Duration as ChronoDuration};
+use chrono::{DateTime, Local, TimeZone, Duration as ChronoDuration};
 use chrono_tz::Asia::Bangkok;
+use chrono_tz::Tz;
 use tokio::time::{sleep, Duration};
 use std::sync::Arc;
 use log::info;
 
+use crate::config::Config;
 use crate::shared_state::SharedState;
 use crate::utils::send_commands_to_game;
 
 pub async fn start_maintenance_schedule(shared_state: Arc<SharedState>) {
-    let restart_times = vec![
-        NaiveTime::from_hms_opt(23, 58, 0).unwrap(),
-        NaiveTime::from_hms_opt(3, 58, 0).unwrap(),
-        NaiveTime::from_hms_opt(7, 58, 0).unwrap(),
-        NaiveTime::from_hms_opt(11, 58, 0).unwrap(),
-        NaiveTime::from_hms_opt(15, 58, 0).unwrap(),
-        NaiveTime::from_hms_opt(19, 58, 0).unwrap(),
-        NaiveTime::from_hms_opt(21, 58, 0).unwrap(),
-    ];
-    
-    let resume_times = vec![
-        NaiveTime::from_hms_opt(0, 6, 0).unwrap(),
-        NaiveTime::from_hms_opt(4, 6, 0).unwrap(),
-        NaiveTime::from_hms_opt(8, 6, 0).unwrap(),
-        NaiveTime::from_hms_opt(12, 6, 0).unwrap(),
-        NaiveTime::from_hms_opt(16, 6, 0).unwrap(),
-        NaiveTime::from_hms_opt(20, 6, 0).unwrap(),
-        NaiveTime::from_hms_opt(22, 6, 0).unwrap(),
-    ];
+    let restart_times = shared_state.config.restart_times.clone();
+    let resume_times = shared_state.config.resume_times.clone();
     
     loop {
         let now = Local::now().with_timezone(&Bangkok);
@@ -105,4 +90,26 @@ pub async fn start_maintenance_schedule(shared_state: Arc<SharedState>) {
             sleep(Duration::from_secs(sleep_secs)).await;
         }
     }
-}
\ No newline at end of file
+}
+
+/// If `at` falls inside a restart window (widened by `margin` before the restart),
+/// returns the time the bot resumes after that window.
+pub fn maintenance_conflict(config: &Config, at: DateTime<Tz>, margin: ChronoDuration) -> Option<DateTime<Tz>> {
+    let date = at.date_naive();
+    
+    for (restart, resume) in config.restart_times.iter().zip(&config.resume_times) {
+        for day in [date - ChronoDuration::days(1), date] {
+            let resume_day = if resume > restart { day } else { day + ChronoDuration::days(1) };
+            let start = Bangkok.from_local_datetime(&day.and_time(*restart)).single();
+            let end = Bangkok.from_local_datetime(&resume_day.and_time(*resume)).single();
+            
+            if let (Some(start), Some(end)) = (start, end) {
+                if at >= start - margin && at < end {
+                    return Some(end);
+                }
+            }
+        }
+    }
+    
+    None
+}
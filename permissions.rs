@@ -0,0 +1,79 @@
+use serenity::model::prelude::*;
+use log::error;
+
+use crate::shared_state::SharedState;
+
+/// Named admin capabilities that can be granted to Discord users or roles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    ManageCoins,
+    ManageShop,
+    TriggerCleanup,
+    SendRawCommands,
+    ViewReports,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 5] = [
+        Permission::ManageCoins,
+        Permission::ManageShop,
+        Permission::TriggerCleanup,
+        Permission::SendRawCommands,
+        Permission::ViewReports,
+    ];
+
+    pub fn key(&self) -> &'static str {
+        match self {
+            Permission::ManageCoins => "manage_coins",
+            Permission::ManageShop => "manage_shop",
+            Permission::TriggerCleanup => "trigger_cleanup",
+            Permission::SendRawCommands => "send_raw_commands",
+            Permission::ViewReports => "view_reports",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Permission::ManageCoins => "จัดการ coin",
+            Permission::ManageShop => "จัดการร้านค้า",
+            Permission::TriggerCleanup => "สั่งลบไอเทม",
+            Permission::SendRawCommands => "ส่งคำสั่งเข้าเกม",
+            Permission::ViewReports => "ดูรายงาน",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Permission> {
+        Permission::ALL.iter().copied().find(|permission| permission.key() == key)
+    }
+}
+
+/// Guild administrators always pass; everyone else needs a grant for their user ID or
+/// one of their roles in `permission_grants`.
+pub fn has_permission(
+    shared_state: &SharedState,
+    user_id: UserId,
+    member: Option<&Member>,
+    permission: Permission,
+) -> bool {
+    if is_guild_admin(member) {
+        return true;
+    }
+
+    let role_ids: Vec<String> = member
+        .map(|m| m.roles.iter().map(|role| role.to_string()).collect())
+        .unwrap_or_default();
+
+    match shared_state.db.has_permission_grant(permission.key(), &user_id.to_string(), &role_ids) {
+        Ok(granted) => granted,
+        Err(e) => {
+            error!("Failed to check permission {}: {:?}", permission.key(), e);
+            false
+        }
+    }
+}
+
+pub fn is_guild_admin(member: Option<&Member>) -> bool {
+    member
+        .and_then(|m| m.permissions)
+        .is_some_and(|permissions| permissions.administrator())
+}
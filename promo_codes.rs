@@ -0,0 +1,439 @@
+use chrono::{Duration as ChronoDuration, Utc};
+use rand::Rng;
+use serenity::builder::*;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use log::info;
+
+use crate::commands::{integer_option, member_roles, reply, require_permission, role_option, string_option, truncate, user_option};
+use crate::database::{LedgerReason, PromoCode, PromoKind, RedeemOutcome, Voucher, DB_TIME_FORMAT};
+use crate::permissions::Permission;
+use crate::shared_state::SharedState;
+use crate::stash::discord_time;
+
+/// Letters and digits of generated codes, without the ones that are easy to mix up (0/O, 1/I).
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const GENERATED_CODE_LENGTH: usize = 8;
+
+/// A discount code's percentage off `price`.
+pub fn apply_voucher(price: u32, voucher: &Voucher) -> u32 {
+    (price as f64 * (1.0 - voucher.percent.min(100) as f64 / 100.0)).round() as u32
+}
+
+/// The price after the buyer's pending discount code, and the voucher to spend with it.
+pub fn with_voucher(shared_state: &SharedState, user_id: &str, price: u32) -> (u32, Option<Voucher>) {
+    match shared_state.db.get_pending_voucher(user_id) {
+        Ok(Some(voucher)) => (apply_voucher(price, &voucher), Some(voucher)),
+        _ => (price, None),
+    }
+}
+
+pub fn redeem_command() -> CreateCommand {
+    CreateCommand::new("redeem")
+        .description("ใช้โค้ดรับ coin หรือส่วนลด")
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::String, "code", "โค้ด")
+                .required(true)
+        )
+}
+
+pub fn promocode_command() -> CreateCommand {
+    let kind_option = CreateCommandOption::new(CommandOptionType::String, "type", "ประเภทโค้ด")
+        .required(true)
+        .add_string_choice("รับ coin", PromoKind::Coins.key())
+        .add_string_choice("ส่วนลด % การซื้อครั้งถัดไป", PromoKind::Discount.key());
+
+    CreateCommand::new("promocode")
+        .description("จัดการโค้ดโปรโมชั่น (Admin)")
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "create", "สร้างโค้ดใหม่")
+                .add_sub_option(kind_option)
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::Integer, "amount", "จำนวน coin หรือ % ส่วนลด")
+                        .required(true)
+                        .min_int_value(1)
+                )
+                .add_sub_option(CreateCommandOption::new(CommandOptionType::String, "code", "โค้ด (ไม่ระบุ = สุ่มให้)"))
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::Integer, "max_uses", "ใช้ได้ทั้งหมดกี่ครั้ง (ไม่ระบุ = ไม่จำกัด)")
+                        .min_int_value(1)
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::Integer, "days", "หมดอายุในกี่วัน (ไม่ระบุ = ไม่หมดอายุ)")
+                        .min_int_value(1)
+                )
+                .add_sub_option(CreateCommandOption::new(CommandOptionType::Role, "role", "ใช้ได้เฉพาะ role นี้"))
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "list", "ดูโค้ดล่าสุด")
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::Integer, "limit", "จำนวนรายการ (สูงสุด 25)")
+                        .min_int_value(1)
+                        .max_int_value(25)
+                )
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "info", "ดูรายละเอียดและผู้ที่ใช้โค้ด")
+                .add_sub_option(code_option())
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "disable", "ปิดหรือเปิดใช้งานโค้ด")
+                .add_sub_option(code_option())
+                .add_sub_option(CreateCommandOption::new(CommandOptionType::Boolean, "disabled", "ปิดใช้งาน (ค่าเริ่มต้น: ปิด)"))
+        )
+}
+
+fn code_option() -> CreateCommandOption {
+    CreateCommandOption::new(CommandOptionType::String, "code", "โค้ด")
+        .required(true)
+        .set_autocomplete(true)
+}
+
+pub fn ledger_command() -> CreateCommand {
+    CreateCommand::new("ledger")
+        .description("ดูประวัติการเปลี่ยนแปลง coin (Admin)")
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::User, "user", "ดูเฉพาะผู้เล่นคนนี้")
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::Integer, "limit", "จำนวนรายการ (สูงสุด 25)")
+                .min_int_value(1)
+                .max_int_value(25)
+        )
+}
+
+pub async fn handle_redeem_command(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState) {
+    let options = command.data.options();
+    let code = match string_option(&options, "code") {
+        Some(code) => normalize_code(code),
+        None => return,
+    };
+
+    let user_id = command.user.id.to_string();
+    let role_ids: Vec<String> = member_roles(command).iter().map(|role| role.to_string()).collect();
+    let outcome = match shared_state.db.redeem_promo_code(&code, &user_id, &role_ids) {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            reply(ctx, command, format!("❌ เกิดข้อผิดพลาด: {:?}", e)).await;
+            return;
+        }
+    };
+
+    let content = match outcome {
+        RedeemOutcome::Coins { amount, balance } => {
+            info!("{} redeemed code {} for {} coins", user_id, code, amount);
+            format!("✅ ใช้โค้ด **{}** สำเร็จ! ได้รับ {} coins (รวม: {} coins)", code, amount, balance)
+        },
+        RedeemOutcome::Discount { percent } => {
+            info!("{} redeemed code {} for {}% off", user_id, code, percent);
+            format!("🎟️ ใช้โค้ด **{}** สำเร็จ! ได้ส่วนลด {}% สำหรับการซื้อครั้งถัดไป", code, percent)
+        },
+        RedeemOutcome::NotFound => "❌ ไม่พบโค้ดนี้".to_string(),
+        RedeemOutcome::Disabled => "❌ โค้ดนี้ถูกปิดใช้งานแล้ว".to_string(),
+        RedeemOutcome::Expired => "❌ โค้ดนี้หมดอายุแล้ว".to_string(),
+        RedeemOutcome::UsedUp => "❌ โค้ดนี้ถูกใช้ครบจำนวนแล้ว".to_string(),
+        RedeemOutcome::AlreadyRedeemed => "❌ คุณใช้โค้ดนี้ไปแล้ว".to_string(),
+        RedeemOutcome::RoleRequired { role_id } => format!("❌ โค้ดนี้ใช้ได้เฉพาะผู้ที่มี role <@&{}>", role_id),
+        RedeemOutcome::NotRegistered => "ไม่พบข้อมูลผู้เล่นของคุณในระบบ! กรุณาลงทะเบียนด้วย /register".to_string(),
+        RedeemOutcome::DiscountPending { code } => format!("❌ คุณมีโค้ดส่วนลด **{}** ที่ยังไม่ได้ใช้อยู่ กรุณาซื้อสินค้าให้ใช้โค้ดนั้นก่อน", code),
+    };
+    reply(ctx, command, content).await;
+}
+
+pub async fn handle_promocode_command(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState) {
+    if !require_permission(ctx, command, shared_state, Permission::ManageCoins).await {
+        return;
+    }
+
+    let options = command.data.options();
+    let (subcommand, sub_options) = match options.first() {
+        Some(ResolvedOption { name, value: ResolvedValue::SubCommand(sub_options), .. }) => (*name, sub_options),
+        _ => return,
+    };
+
+    match subcommand {
+        "create" => handle_create(ctx, command, shared_state, sub_options).await,
+        "list" => {
+            let limit = integer_option(sub_options, "limit").unwrap_or(10).clamp(1, 25) as u32;
+            handle_list(ctx, command, shared_state, limit).await;
+        },
+        "info" | "disable" => {
+            let code = match string_option(sub_options, "code") {
+                Some(code) => normalize_code(code),
+                None => return,
+            };
+            let promo = match shared_state.db.get_promo_code(&code) {
+                Ok(Some(promo)) => promo,
+                Ok(None) => {
+                    reply(ctx, command, "❌ ไม่พบโค้ดนี้").await;
+                    return;
+                },
+                Err(e) => {
+                    reply(ctx, command, format!("❌ เกิดข้อผิดพลาด: {:?}", e)).await;
+                    return;
+                }
+            };
+
+            if subcommand == "info" {
+                handle_info(ctx, command, shared_state, promo).await;
+                return;
+            }
+
+            let disabled = !sub_options.iter().any(|option| {
+                option.name == "disabled" && matches!(option.value, ResolvedValue::Boolean(false))
+            });
+            let content = match shared_state.db.set_promo_code_disabled(&promo.code, disabled) {
+                Ok(_) => {
+                    info!("{} set code {} disabled = {}", command.user.id, promo.code, disabled);
+                    if disabled {
+                        format!("✅ ปิดใช้งานโค้ด **{}** แล้ว", promo.code)
+                    } else {
+                        format!("✅ เปิดใช้งานโค้ด **{}** แล้ว", promo.code)
+                    }
+                },
+                Err(e) => format!("❌ เกิดข้อผิดพลาด: {:?}", e),
+            };
+            reply(ctx, command, content).await;
+        },
+        _ => {}
+    }
+}
+
+pub async fn handle_promocode_autocomplete(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState) {
+    let query = command.data.autocomplete()
+        .map(|focused| focused.value.trim().to_uppercase())
+        .unwrap_or_default();
+
+    let mut response = CreateAutocompleteResponse::new();
+    let codes = shared_state.db.list_promo_codes(100).unwrap_or_default();
+    let choices = codes.iter()
+        .filter(|promo| query.is_empty() || promo.code.contains(&query))
+        .take(25);
+    for promo in choices {
+        let label = format!("{} • {} • ใช้แล้ว {}", promo.code, describe_reward(promo), describe_uses(promo));
+        response = response.add_string_choice(truncate(&label, 100), promo.code.clone());
+    }
+
+    let _ = command.create_response(&ctx.http, CreateInteractionResponse::Autocomplete(response)).await;
+}
+
+async fn handle_create(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState, options: &[ResolvedOption<'_>]) {
+    let kind = match string_option(options, "type").and_then(PromoKind::from_key) {
+        Some(kind) => kind,
+        None => return,
+    };
+    let amount = match integer_option(options, "amount").and_then(|amount| i32::try_from(amount).ok()) {
+        Some(amount) if kind == PromoKind::Discount && !(1..=100).contains(&amount) => {
+            reply(ctx, command, "❌ ส่วนลดต้องอยู่ระหว่าง 1-100%").await;
+            return;
+        },
+        Some(amount) if amount > 0 => amount,
+        _ => {
+            reply(ctx, command, "❌ จำนวนไม่ถูกต้อง").await;
+            return;
+        }
+    };
+
+    let custom_code = string_option(options, "code").map(normalize_code);
+    if let Some(code) = &custom_code {
+        let valid = (3..=32).contains(&code.len())
+            && code.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            reply(ctx, command, "❌ โค้ดต้องยาว 3-32 ตัว ใช้ได้เฉพาะ A-Z, 0-9, - และ _").await;
+            return;
+        }
+    }
+
+    let mut promo = PromoCode {
+        code: String::new(),
+        kind,
+        amount,
+        max_uses: integer_option(options, "max_uses").and_then(|max_uses| u32::try_from(max_uses).ok()),
+        uses: 0,
+        expires_at: integer_option(options, "days")
+            .map(|days| (Utc::now() + ChronoDuration::days(days)).format(DB_TIME_FORMAT).to_string()),
+        required_role: role_option(options, "role").map(|role| role.id.to_string()),
+        created_by: command.user.id.to_string(),
+        created_at: String::new(),
+        disabled: false,
+    };
+
+    // A generated code that collides is simply drawn again; a chosen one is reported.
+    let attempts = if custom_code.is_some() { 1 } else { 5 };
+    for _ in 0..attempts {
+        promo.code = custom_code.clone().unwrap_or_else(generate_code);
+        match shared_state.db.create_promo_code(&promo) {
+            Ok(true) => {
+                info!("{} created code {} ({} {})", command.user.id, promo.code, promo.kind.key(), promo.amount);
+                let mut details = vec![describe_reward(&promo), format!("ใช้ได้ {} ครั้ง", describe_max_uses(&promo))];
+                if let Some(expires_at) = &promo.expires_at {
+                    details.push(format!("หมดอายุ {}", discord_time(expires_at)));
+                }
+                if let Some(role_id) = &promo.required_role {
+                    details.push(format!("เฉพาะ <@&{}>", role_id));
+                }
+                reply(ctx, command, format!("✅ สร้างโค้ด **{}** แล้ว\n{}", promo.code, details.join(" • "))).await;
+                return;
+            },
+            Ok(false) => continue,
+            Err(e) => {
+                reply(ctx, command, format!("❌ เกิดข้อผิดพลาด: {:?}", e)).await;
+                return;
+            }
+        }
+    }
+
+    reply(ctx, command, format!("❌ มีโค้ด **{}** อยู่แล้ว", promo.code)).await;
+}
+
+async fn handle_list(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState, limit: u32) {
+    let codes = match shared_state.db.list_promo_codes(limit) {
+        Ok(codes) => codes,
+        Err(e) => {
+            reply(ctx, command, format!("❌ เกิดข้อผิดพลาด: {:?}", e)).await;
+            return;
+        }
+    };
+    if codes.is_empty() {
+        reply(ctx, command, "ยังไม่มีโค้ดโปรโมชั่น").await;
+        return;
+    }
+
+    let lines: Vec<String> = codes.iter()
+        .map(|promo| format!(
+            "{} **{}** • {} • ใช้แล้ว {}{}",
+            if promo.disabled { "⛔" } else { "🎟️" },
+            promo.code,
+            describe_reward(promo),
+            describe_uses(promo),
+            promo.expires_at.as_deref().map(|expires_at| format!(" • หมดอายุ {}", discord_time(expires_at))).unwrap_or_default()
+        ))
+        .collect();
+
+    let embed = CreateEmbed::new()
+        .title("🎟️ โค้ดโปรโมชั่น")
+        .color(0x9900cc)
+        .description(truncate(&lines.join("\n"), 4000))
+        .footer(CreateEmbedFooter::new("ดูผู้ที่ใช้โค้ดด้วย /promocode info • © powered by TimeSkip"));
+
+    let response = CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .embed(embed)
+            .ephemeral(true)
+    );
+    let _ = command.create_response(&ctx.http, response).await;
+}
+
+async fn handle_info(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState, promo: PromoCode) {
+    let redemptions = shared_state.db.get_promo_redemptions(&promo.code).unwrap_or_default();
+    let lines: Vec<String> = redemptions.iter()
+        .map(|redemption| {
+            let status = match (promo.kind, &redemption.used_at) {
+                (PromoKind::Coins, _) => String::new(),
+                (PromoKind::Discount, Some(used_at)) => match redemption.purchase_id {
+                    Some(purchase_id) => format!(" • ใช้ซื้อแล้ว {} (#{})", discord_time(used_at), purchase_id),
+                    None => format!(" • ใช้ซื้อแล้ว {}", discord_time(used_at)),
+                },
+                (PromoKind::Discount, None) => " • ยังไม่ได้ใช้ซื้อ".to_string(),
+            };
+            format!("<@{}> {}{}", redemption.discord_id, discord_time(&redemption.redeemed_at), status)
+        })
+        .collect();
+
+    let mut embed = CreateEmbed::new()
+        .title(format!("🎟️ {}", promo.code))
+        .color(0x9900cc)
+        .field("รางวัล", describe_reward(&promo), true)
+        .field("ใช้แล้ว", describe_uses(&promo), true)
+        .field("สถานะ", if promo.disabled { "ปิดใช้งาน" } else { "เปิดใช้งาน" }, true)
+        .field("หมดอายุ", promo.expires_at.as_deref().map(discord_time).unwrap_or_else(|| "-".to_string()), true)
+        .field("เฉพาะ role", promo.required_role.as_deref().map(|role_id| format!("<@&{}>", role_id)).unwrap_or_else(|| "-".to_string()), true)
+        .field("สร้างโดย", format!("<@{}> {}", promo.created_by, discord_time(&promo.created_at)), true)
+        .footer(CreateEmbedFooter::new("© powered by TimeSkip"));
+    if !lines.is_empty() {
+        embed = embed.description(truncate(&lines.join("\n"), 4000));
+    }
+
+    let response = CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .embed(embed)
+            .ephemeral(true)
+    );
+    let _ = command.create_response(&ctx.http, response).await;
+}
+
+pub async fn handle_ledger_command(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState) {
+    if !require_permission(ctx, command, shared_state, Permission::ViewReports).await {
+        return;
+    }
+
+    let options = command.data.options();
+    let user_id = user_option(&options, "user").map(|user| user.id.to_string());
+    let limit = integer_option(&options, "limit").unwrap_or(10).clamp(1, 25) as u32;
+
+    match shared_state.db.get_ledger(user_id.as_deref(), limit) {
+        Ok(entries) if entries.is_empty() => reply(ctx, command, "ไม่มีประวัติการเปลี่ยนแปลง coin").await,
+        Ok(entries) => {
+            let lines: Vec<String> = entries.iter()
+                .map(|entry| {
+                    let reason = LedgerReason::from_key(&entry.reason)
+                        .map(|reason| reason.label().to_string())
+                        .unwrap_or_else(|| entry.reason.clone());
+                    let mut line = format!(
+                        "#{} {} <@{}> **{:+}** → {} • {}",
+                        entry.id, discord_time(&entry.created_at), entry.discord_id, entry.amount, entry.balance, reason
+                    );
+                    if let Some(reference) = &entry.reference {
+                        line.push_str(&format!(" `{}`", reference));
+                    }
+                    if let Some(actor) = &entry.actor {
+                        line.push_str(&format!(" โดย <@{}>", actor));
+                    }
+                    line
+                })
+                .collect();
+            let embed = CreateEmbed::new()
+                .title("📒 ประวัติ coin")
+                .color(0x9900cc)
+                .description(truncate(&lines.join("\n"), 4000))
+                .footer(CreateEmbedFooter::new("© powered by TimeSkip"));
+            let response = CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .embed(embed)
+                    .ephemeral(true)
+            );
+            let _ = command.create_response(&ctx.http, response).await;
+        },
+        Err(e) => reply(ctx, command, format!("❌ เกิดข้อผิดพลาด: {:?}", e)).await,
+    }
+}
+
+/// Codes are case-insensitive for players; they're stored upper-case.
+fn normalize_code(code: &str) -> String {
+    code.trim().to_uppercase()
+}
+
+fn generate_code() -> String {
+    let mut rng = rand::thread_rng();
+    (0..GENERATED_CODE_LENGTH)
+        .map(|_| CODE_ALPHABET[rng.gen_range(0..CODE_ALPHABET.len())] as char)
+        .collect()
+}
+
+fn describe_reward(promo: &PromoCode) -> String {
+    match promo.kind {
+        PromoKind::Coins => format!("{} coins", promo.amount),
+        PromoKind::Discount => format!("ลด {}% ครั้งถัดไป", promo.amount),
+    }
+}
+
+fn describe_max_uses(promo: &PromoCode) -> String {
+    match promo.max_uses {
+        Some(max_uses) => max_uses.to_string(),
+        None => "ไม่จำกัด".to_string(),
+    }
+}
+
+fn describe_uses(promo: &PromoCode) -> String {
+    format!("{}/{}", promo.uses, describe_max_uses(promo))
+}
@@ -0,0 +1,312 @@
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, Local, NaiveDate, NaiveTime, TimeZone, Weekday};
+use chrono_tz::Asia::Bangkok;
+use chrono_tz::Tz;
+use serde::Deserialize;
+use serenity::http::Http;
+use std::collections::HashMap;
+use std::sync::Arc;
+use log::info;
+use tokio::time::{sleep, Duration};
+
+use crate::config::{ShopButton, ShopItem, VipRole};
+use crate::shared_state::SharedState;
+use crate::shop::refresh_stale_items;
+
+/// Start and end of a promotion, in the server timezone.
+pub const PROMOTION_TIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+const PROMOTION_TICK_SECS: u64 = 60;
+
+/// `promotions.json`: time-bound sales applied on top of the shop file's prices.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Promotions {
+    #[serde(default)]
+    pub vip_stacking: VipStacking,
+    #[serde(default)]
+    pub promotions: Vec<Promotion>,
+}
+
+/// How a sale combines with the buyer's VIP discount.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VipStacking {
+    /// One after the other: 20% off for the sale then 30% for VIP is 44% off.
+    #[default]
+    Multiply,
+    /// Percentages are added, capped at 100%.
+    Add,
+    /// Only the bigger of the two applies.
+    Best,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Promotion {
+    pub id: String,
+    pub name: String,
+    pub percent_off: u32,
+    /// `YYYY-MM-DD HH:MM`; open-ended when missing.
+    #[serde(default)]
+    pub start: Option<String>,
+    #[serde(default)]
+    pub end: Option<String>,
+    /// Happy hours: when set, the promotion only runs inside these daily windows.
+    #[serde(default)]
+    pub hours: Vec<HappyHour>,
+    /// Shop channel IDs; empty means every shop.
+    #[serde(default)]
+    pub shops: Vec<String>,
+    /// Item names; empty means every item of the shops above.
+    #[serde(default)]
+    pub items: Vec<String>,
+    #[serde(default = "default_announce")]
+    pub announce: bool,
+    #[serde(default)]
+    pub announce_start: Option<String>,
+    #[serde(default)]
+    pub announce_end: Option<String>,
+    #[serde(skip)]
+    pub starts_at: Option<DateTime<Tz>>,
+    #[serde(skip)]
+    pub ends_at: Option<DateTime<Tz>>,
+}
+
+fn default_announce() -> bool { true }
+
+/// A daily window such as `{"start": "20:00", "end": "22:00", "days": ["sat", "sun"]}`.
+/// It may run past midnight; `days` are the days it starts on.
+#[derive(Debug, Clone, Deserialize)]
+pub struct HappyHour {
+    pub start: String,
+    pub end: String,
+    #[serde(default)]
+    pub days: Vec<String>,
+    #[serde(skip)]
+    pub start_time: NaiveTime,
+    #[serde(skip)]
+    pub end_time: NaiveTime,
+    #[serde(skip)]
+    pub weekdays: Vec<Weekday>,
+}
+
+/// A promotion running right now.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sale {
+    pub id: String,
+    pub name: String,
+    pub percent_off: u32,
+    /// When this run ends: the end of the promotion or of the current happy hour.
+    pub until: Option<DateTime<Tz>>,
+}
+
+/// What one button costs a buyer after the sale and their VIP discount.
+pub struct Quote {
+    pub original: u32,
+    pub total: u32,
+    /// The sale that was applied; `None` when there's none or VIP beat it under `best`.
+    pub sale: Option<Sale>,
+    /// The VIP discount that was applied, 0 when a sale beat it under `best`.
+    pub vip_percent: u32,
+}
+
+pub fn server_now() -> DateTime<Tz> {
+    Local::now().with_timezone(&Bangkok)
+}
+
+impl HappyHour {
+    fn runs_on(&self, day: NaiveDate) -> bool {
+        self.weekdays.is_empty() || self.weekdays.contains(&day.weekday())
+    }
+
+    /// End of the window `now` falls in, if any.
+    fn window_end(&self, now: DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let today = now.date_naive();
+        let time = now.time();
+        let (day, end_day) = if self.start_time < self.end_time {
+            if time < self.start_time || time >= self.end_time {
+                return None;
+            }
+            (today, today)
+        } else if time >= self.start_time {
+            (today, today + ChronoDuration::days(1))
+        } else if time < self.end_time {
+            (today - ChronoDuration::days(1), today)
+        } else {
+            return None;
+        };
+
+        if !self.runs_on(day) {
+            return None;
+        }
+        Bangkok.from_local_datetime(&end_day.and_time(self.end_time)).single()
+    }
+}
+
+impl Promotion {
+    pub fn covers(&self, channel: &str, item: &ShopItem) -> bool {
+        (self.shops.is_empty() || self.shops.iter().any(|shop| shop == channel))
+            && (self.items.is_empty() || self.items.contains(&item.name))
+    }
+
+    pub fn running(&self, now: DateTime<Tz>) -> Option<Sale> {
+        if self.starts_at.is_some_and(|at| now < at) || self.ends_at.is_some_and(|at| now >= at) {
+            return None;
+        }
+
+        let window_end = if self.hours.is_empty() {
+            None
+        } else {
+            Some(self.hours.iter().filter_map(|hours| hours.window_end(now)).max()?)
+        };
+        let until = match (window_end, self.ends_at) {
+            (Some(window_end), Some(ends_at)) => Some(window_end.min(ends_at)),
+            (window_end, ends_at) => window_end.or(ends_at),
+        };
+
+        Some(Sale { id: self.id.clone(), name: self.name.clone(), percent_off: self.percent_off, until })
+    }
+
+    pub fn start_announcement(&self, sale: &Sale) -> String {
+        let text = match &self.announce_start {
+            Some(text) => text.clone(),
+            None => match sale.until {
+                Some(until) => format!("{} ลด {}% ที่ BOTSHOP ถึง {}", self.name, self.percent_off, until.format("%d/%m %H:%M")),
+                None => format!("{} ลด {}% ที่ BOTSHOP", self.name, self.percent_off),
+            },
+        };
+        format!("#Announce {}", text)
+    }
+
+    pub fn end_announcement(&self) -> String {
+        let text = match &self.announce_end {
+            Some(text) => text.clone(),
+            None => format!("{} จบแล้ว ขอบคุณที่อุดหนุน", self.name),
+        };
+        format!("#Announce {}", text)
+    }
+}
+
+impl Sale {
+    pub fn apply(&self, price: u32) -> u32 {
+        (price as f64 * (1.0 - self.percent_off as f64 / 100.0)).round() as u32
+    }
+
+    /// `ลด 20% • หมดเขต <t:...:R>` for embeds.
+    pub fn describe(&self) -> String {
+        match self.until {
+            Some(until) => format!("ลด {}% • หมดเขต <t:{}:R>", self.percent_off, until.timestamp()),
+            None => format!("ลด {}%", self.percent_off),
+        }
+    }
+
+    /// Changes whenever the rendered sale would, for the shop messages' content hashes.
+    pub fn key(&self) -> String {
+        format!("{}:{}:{}", self.id, self.percent_off, self.until.map(|until| until.timestamp()).unwrap_or(0))
+    }
+}
+
+impl Promotions {
+    /// The biggest sale running on `item` in the shop posted to `channel`.
+    pub fn sale_for(&self, channel: &str, item: &ShopItem, now: DateTime<Tz>) -> Option<Sale> {
+        self.promotions.iter()
+            .filter(|promotion| promotion.covers(channel, item))
+            .filter_map(|promotion| promotion.running(now))
+            .max_by_key(|sale| sale.percent_off)
+    }
+
+    pub fn quote(&self, channel: &str, item: &ShopItem, button: &ShopButton, vip: Option<&VipRole>, now: DateTime<Tz>) -> Quote {
+        let original = item.price * button.quantity;
+        let mut sale = self.sale_for(channel, item, now);
+        let mut vip_rate = vip.map(|vip| vip.discount as f64).unwrap_or(0.0);
+
+        if self.vip_stacking == VipStacking::Best {
+            let sale_rate = sale.as_ref().map(|sale| sale.percent_off as f64 / 100.0).unwrap_or(0.0);
+            if vip_rate >= sale_rate {
+                sale = None;
+            } else {
+                vip_rate = 0.0;
+            }
+        }
+
+        let sale_rate = sale.as_ref().map(|sale| sale.percent_off as f64 / 100.0).unwrap_or(0.0);
+        let factor = match self.vip_stacking {
+            VipStacking::Add => (1.0 - sale_rate - vip_rate).max(0.0),
+            VipStacking::Multiply | VipStacking::Best => (1.0 - sale_rate) * (1.0 - vip_rate),
+        };
+
+        Quote {
+            original,
+            total: (original as f64 * factor).round() as u32,
+            sale,
+            vip_percent: (vip_rate * 100.0).round() as u32,
+        }
+    }
+}
+
+/// Prices a button for a buyer at the current time, using the sale of the shop it's in.
+pub fn quote(shared_state: &SharedState, item: &ShopItem, button: &ShopButton, vip: Option<&VipRole>) -> Quote {
+    let file = shared_state.shop_file();
+    let channel = file.shops.iter()
+        .find(|shop| shop.items.iter().any(|shop_item| shop_item.buttons.iter().any(|other| other.trigger == button.trigger)))
+        .map(|shop| shop.channel.as_str())
+        .unwrap_or_default();
+    shared_state.config.promotions.quote(channel, item, button, vip, server_now())
+}
+
+/// Checks every minute which promotions are running. When that changes, starts and ends are
+/// announced in game and the posted item messages whose sale changed are edited to show
+/// current prices.
+pub async fn start_promotion_scheduler(http: Arc<Http>, shared_state: Arc<SharedState>) {
+    let promotions = &shared_state.config.promotions.promotions;
+    if promotions.is_empty() {
+        info!("No promotions configured");
+        return;
+    }
+
+    // Only changes seen while the bot runs count; sales that started or ended while it was
+    // down are picked up by the next /updateshop.
+    let mut previous: Option<HashMap<String, Sale>> = None;
+
+    loop {
+        let now = server_now();
+        let running: HashMap<String, Sale> = promotions.iter()
+            .filter_map(|promotion| promotion.running(now).map(|sale| (promotion.id.clone(), sale)))
+            .collect();
+
+        if let Some(previous) = previous.as_ref().filter(|previous| **previous != running) {
+            let mut announcements = Vec::new();
+            for promotion in promotions {
+                match (previous.contains_key(&promotion.id), running.get(&promotion.id)) {
+                    (false, Some(sale)) => {
+                        info!("Promotion '{}' started", promotion.id);
+                        if promotion.announce {
+                            announcements.push(promotion.start_announcement(sale));
+                        }
+                    },
+                    (true, None) => {
+                        info!("Promotion '{}' ended", promotion.id);
+                        if promotion.announce {
+                            announcements.push(promotion.end_announcement());
+                        }
+                    },
+                    _ => {}
+                }
+            }
+
+            if !announcements.is_empty() {
+                if shared_state.is_bot_active().await {
+                    shared_state.command_queue.lock().await.push(announcements);
+                } else {
+                    info!("Skipping promotion announcements while the bot is paused");
+                }
+            }
+
+            let file = shared_state.shop_file();
+            for shop in &file.shops {
+                refresh_stale_items(&http, &shared_state, shop).await;
+            }
+        }
+        previous = Some(running);
+
+        sleep(Duration::from_secs(PROMOTION_TICK_SECS)).await;
+    }
+}
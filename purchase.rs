@@ -0,0 +1,138 @@
+use serenity::builder::{CreateEmbed, CreateEmbedFooter, CreateMessage};
+use serenity::http::Http;
+use serenity::model::prelude::*;
+
+use crate::config::{ButtonCommand, Config, Delivery, ShopButton, ShopConfig, ShopItem, VipRole};
+use crate::database::{PurchaseLine, PurchaseOutcome};
+use crate::template::{render, TemplateContext};
+
+/// One line of a DM receipt.
+pub struct ReceiptLine {
+    pub item_name: String,
+    pub quantity: u32,
+    pub price: u32,
+}
+
+/// A button that's for sale; disabled items and buttons aren't.
+pub fn find_shop_button(shops: &[ShopConfig], trigger: &str) -> Option<(ShopItem, ShopButton)> {
+    find_button(shops, trigger).filter(|(item, button)| !item.disabled && !button.disabled)
+}
+
+/// Any button, including disabled ones, for things already paid for like stash items.
+pub fn find_button(shops: &[ShopConfig], trigger: &str) -> Option<(ShopItem, ShopButton)> {
+    for shop in shops {
+        for item in &shop.items {
+            for button in &item.buttons {
+                if button.trigger == trigger {
+                    return Some((item.clone(), button.clone()));
+                }
+            }
+        }
+    }
+    None
+}
+
+pub fn get_vip_tier<'a>(config: &'a Config, roles: &[RoleId]) -> Option<&'a VipRole> {
+    let mut highest_tier: Option<&VipRole> = None;
+
+    for role_id in roles {
+        if let Some(vip_info) = config.vip_roles.get(&role_id.get()) {
+            if highest_tier.is_none() || vip_info.tier > highest_tier.unwrap().tier {
+                highest_tier = Some(vip_info);
+            }
+        }
+    }
+
+    highest_tier
+}
+
+pub fn purchase_line(item: &ShopItem, button: &ShopButton, price: u32) -> PurchaseLine {
+    PurchaseLine {
+        item_name: item.name.clone(),
+        trigger: button.trigger.clone(),
+        price: price as i32,
+        daily_limit: button.daily_limit,
+        weekly_limit: button.weekly_limit,
+        limited_stock: button.stock.is_some(),
+        recipient_id: None,
+        gift_message: None,
+        stash_until: None,
+        quantity: button.quantity,
+        commands: serde_json::to_string(&button.resolved_commands).unwrap_or_default(),
+        loot_box: item.loot.is_some(),
+    }
+}
+
+/// User-facing reason for a purchase the database refused.
+pub fn rejection_message(outcome: &PurchaseOutcome, total: u32, coin: i32) -> String {
+    match outcome {
+        PurchaseOutcome::Charged { .. } => String::new(),
+        PurchaseOutcome::InsufficientCoins => format!("คุณมี coin ไม่พอ (ต้องการ {}, มี {})", total, coin),
+        PurchaseOutcome::LimitReached { item_name, period, limit } => {
+            format!("❌ {} ซื้อได้ {} ครั้ง{} คุณซื้อครบแล้ว", item_name, limit, period.label())
+        },
+        PurchaseOutcome::OutOfStock { item_name } => format!("❌ {} หมดแล้ว กรุณารอการเติมสินค้า", item_name),
+        PurchaseOutcome::VoucherUsed => "❌ โค้ดส่วนลดของคุณถูกใช้กับการซื้ออื่นไปแล้ว กรุณากดซื้อใหม่อีกครั้ง".to_string(),
+    }
+}
+
+/// Resolved commands of a button or loot outcome rendered for this purchase, each with its
+/// delivery target appended.
+pub fn build_delivery_commands(config: &Config, resolved_commands: &[ButtonCommand], context: &TemplateContext) -> Vec<String> {
+    let mut commands = Vec::new();
+    for command in resolved_commands {
+        let delivery = command.delivery();
+        for text in render(command.command(), context) {
+            commands.push(match &delivery {
+                Delivery::Player => format!("{} Location {}", text, context.steam_id),
+                Delivery::Coords(coords) => format!("{} {}", text, location_argument(coords)),
+                // Unknown presets are rejected when the config loads.
+                Delivery::Preset(name) => match config.locations.get(name) {
+                    Some(coords) => format!("{} {}", text, location_argument(coords)),
+                    None => text,
+                },
+                Delivery::None => text,
+            });
+        }
+    }
+    commands
+}
+
+/// `{discord_name}` and `{player_name}` for command templates: the account's username and
+/// the name shown in the server.
+pub fn template_names(user: &User, member: Option<&Member>) -> (String, String) {
+    let player_name = match member {
+        Some(member) => member.display_name().to_string(),
+        None => user.display_name().to_string(),
+    };
+    (user.name.clone(), player_name)
+}
+
+fn location_argument(coords: &[f64; 3]) -> String {
+    format!("Location \"X={} Y={} Z={}\"", coords[0], coords[1], coords[2])
+}
+
+pub async fn send_receipt(http: &Http, user: &User, lines: &[ReceiptLine], remaining_coin: i32) {
+    let total: u32 = lines.iter().map(|line| line.price).sum();
+    let items = if lines.len() == 1 {
+        format!("**{}** x{}", lines[0].item_name, lines[0].quantity)
+    } else {
+        lines.iter()
+            .map(|line| format!("**{}** x{} — {} coin", line.item_name, line.quantity, line.price))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    if let Ok(dm_channel) = user.create_dm_channel(http).await {
+        let embed = CreateEmbed::new()
+            .title("⚡แจ้งเตือนการหัก Coin")
+            .color(0x9900cc)
+            .field("🛒 สินค้าที่ซื้อ", items, false)
+            .field("💷 ราคา", format!("**{}** coin", total), true)
+            .field("💷 Coin คงเหลือ", format!("**{}** coin", remaining_coin), false)
+            .footer(CreateEmbedFooter::new("© powered by TimeSkip"));
+
+        let message = CreateMessage::new().embed(embed);
+        let _ = dm_channel.send_message(http, message).await;
+    }
+}
@@ -0,0 +1,231 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::config::{ButtonCommand, CleanupProfile, ShopConfig};
+use crate::search::edit_distance;
+
+/// The item list the bot checks spawn names against. A copy is compiled in; a file next to
+/// the bot (written by `refresh-items`) takes precedence.
+pub const ITEM_CATALOG_FILE: &str = "scum_items.csv";
+
+const BUNDLED_CATALOG: &str = include_str!("scum_items.csv");
+
+const CATALOG_HEADER: &str = "# SCUM spawnable items used to check #SpawnItem IDs and cleanup lists.\n\
+                              # Generated from the game's spawnable item list by: refresh-items <export>\n\
+                              # Run it again after game updates that add or rename items.\n";
+
+const MAX_SUGGESTIONS: usize = 3;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScumItem {
+    pub id: String,
+    pub category: String,
+    pub name: String,
+}
+
+pub struct ItemCatalog {
+    items: Vec<ScumItem>,
+    /// Lowercased ID to index; the game doesn't care about case in spawn names.
+    by_id: HashMap<String, usize>,
+}
+
+/// A spawn or destroy name that isn't in the catalog.
+pub struct UnknownItem {
+    /// Where it's used, e.g. `Button 'botshop:ak,1' of 'AK47'`.
+    pub used_in: String,
+    pub id: String,
+    pub suggestions: Vec<String>,
+}
+
+impl ItemCatalog {
+    pub fn load() -> Result<Self, String> {
+        if std::path::Path::new(ITEM_CATALOG_FILE).exists() {
+            let text = std::fs::read_to_string(ITEM_CATALOG_FILE)
+                .map_err(|e| format!("Failed to read {}: {}", ITEM_CATALOG_FILE, e))?;
+            Self::parse(&text).map_err(|e| format!("Failed to parse {}: {}", ITEM_CATALOG_FILE, e))
+        } else {
+            Self::parse(BUNDLED_CATALOG).map_err(|e| format!("Failed to parse bundled item catalog: {}", e))
+        }
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut reader = csv::ReaderBuilder::new()
+            .comment(Some(b'#'))
+            .trim(csv::Trim::All)
+            .from_reader(text.as_bytes());
+        let items = reader.deserialize::<ScumItem>()
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        Ok(Self::from_items(items))
+    }
+
+    fn from_items(mut items: Vec<ScumItem>) -> Self {
+        items.sort_by_key(|item| item.id.to_lowercase());
+        items.dedup_by_key(|item| item.id.to_lowercase());
+        let by_id = items.iter().enumerate().map(|(index, item)| (item.id.to_lowercase(), index)).collect();
+        ItemCatalog { items, by_id }
+    }
+
+    pub fn item_count(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn get(&self, id: &str) -> Option<&ScumItem> {
+        self.by_id.get(&id.to_lowercase()).map(|index| &self.items[*index])
+    }
+
+    /// Closest catalog IDs, allowing roughly one typo per four characters.
+    pub fn suggestions(&self, id: &str) -> Vec<String> {
+        let id = id.to_lowercase();
+        let max_distance = (id.chars().count() / 4).max(2);
+        let mut candidates: Vec<(usize, &ScumItem)> = self.items.iter()
+            .map(|item| (edit_distance(&id, &item.id.to_lowercase()), item))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .collect();
+        candidates.sort_by_key(|(distance, _)| *distance);
+        candidates.into_iter().take(MAX_SUGGESTIONS).map(|(_, item)| item.id.clone()).collect()
+    }
+}
+
+/// Every `#SpawnItem` ID of the shops and every cleanup item that the catalog doesn't know.
+/// IDs built from templates (containing `{`) can't be checked and are skipped.
+pub fn find_unknown_items(catalog: &ItemCatalog, shops: &[ShopConfig], cleanup_profiles: &[CleanupProfile]) -> Vec<UnknownItem> {
+    let mut unknown = Vec::new();
+    let mut check = |used_in: String, id: &str| {
+        if !id.contains('{') && catalog.get(id).is_none() {
+            unknown.push(UnknownItem { used_in, id: id.to_string(), suggestions: catalog.suggestions(id) });
+        }
+    };
+
+    for shop in shops {
+        for item in &shop.items {
+            for button in &item.buttons {
+                for id in button.resolved_commands.iter().filter_map(spawn_item_id) {
+                    check(format!("Button '{}' of '{}'", button.trigger, item.name), id);
+                }
+            }
+            for outcome in item.loot.iter().flat_map(|loot| &loot.outcomes) {
+                for id in outcome.resolved_commands.iter().filter_map(spawn_item_id) {
+                    check(format!("Loot outcome '{}' of '{}'", outcome.name, item.name), id);
+                }
+            }
+        }
+    }
+
+    for profile in cleanup_profiles {
+        for id in &profile.items {
+            check(format!("Cleanup profile '{}'", profile.id), id);
+        }
+    }
+
+    unknown
+}
+
+fn spawn_item_id(command: &ButtonCommand) -> Option<&str> {
+    let mut parts = command.command().split_whitespace();
+    if parts.next().is_some_and(|name| name.eq_ignore_ascii_case("#SpawnItem")) {
+        parts.next()
+    } else {
+        None
+    }
+}
+
+pub fn describe(unknown: &UnknownItem) -> String {
+    if unknown.suggestions.is_empty() {
+        format!("{}: unknown item '{}'", unknown.used_in, unknown.id)
+    } else {
+        format!("{}: unknown item '{}', did you mean {}?", unknown.used_in, unknown.id, unknown.suggestions.join(", "))
+    }
+}
+
+/// `check-items` and `refresh-items <export>`. `None` when `args` isn't one of these.
+pub fn run_tool(args: &[String]) -> Option<Result<String, String>> {
+    let result = match args.get(1).map(String::as_str) {
+        Some("check-items") => check_items(),
+        Some("refresh-items") => match args.get(2) {
+            Some(input) => refresh_items(input),
+            None => Err("Usage: refresh-items <export>".to_string()),
+        },
+        _ => return None,
+    };
+    Some(result)
+}
+
+fn check_items() -> Result<String, String> {
+    let catalog = ItemCatalog::load()?;
+    let shop_path = crate::shopfile::find_shop_file()?;
+    let mut file = crate::shopfile::read_shop_file(shop_path)?;
+    crate::kits::resolve_commands(&mut file.shops, &file.kits)?;
+    let cleanup_profiles = crate::config::load_cleanup_profiles().map_err(|e| e.to_string())?;
+
+    let unknown = find_unknown_items(&catalog, &file.shops, &cleanup_profiles);
+    if unknown.is_empty() {
+        return Ok(format!("All item names are in the catalog ({} items)", catalog.item_count()));
+    }
+    let lines: Vec<String> = unknown.iter().map(describe).collect();
+    Err(format!("{}\n{} unknown item names", lines.join("\n"), unknown.len()))
+}
+
+/// Rebuilds the catalog file from an exported list: one item per line, either a bare ID or
+/// `id,category,name`. Categories and names we already have are kept for bare IDs.
+fn refresh_items(input: &str) -> Result<String, String> {
+    let text = std::fs::read_to_string(input).map_err(|e| format!("Failed to read {}: {}", input, e))?;
+    let current = ItemCatalog::load()?;
+
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .comment(Some(b'#'))
+        .trim(csv::Trim::All)
+        .from_reader(text.as_bytes());
+
+    let mut items = Vec::new();
+    for (index, record) in reader.records().enumerate() {
+        let record = record.map_err(|e| format!("Line {}: {}", index + 1, e))?;
+        let id = match record.get(0) {
+            Some(id) if !id.is_empty() && !id.eq_ignore_ascii_case("id") => id,
+            _ => continue,
+        };
+        let known = current.get(id);
+        let category = record.get(1).filter(|category| !category.is_empty()).map(str::to_string)
+            .or_else(|| known.map(|item| item.category.clone()))
+            .unwrap_or_else(|| guess_category(id).to_string());
+        let name = record.get(2).filter(|name| !name.is_empty()).map(str::to_string)
+            .or_else(|| known.map(|item| item.name.clone()))
+            .unwrap_or_else(|| id.replace('_', " "));
+        items.push(ScumItem { id: id.to_string(), category, name });
+    }
+
+    let refreshed = ItemCatalog::from_items(items);
+    let added = refreshed.items.iter().filter(|item| current.get(&item.id).is_none()).count();
+    let removed = current.items.iter().filter(|item| refreshed.get(&item.id).is_none()).count();
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for item in &refreshed.items {
+        writer.serialize(item).map_err(|e| e.to_string())?;
+    }
+    let data = writer.into_inner().map_err(|e| e.to_string())?;
+    let body = String::from_utf8(data).map_err(|e| e.to_string())?;
+    std::fs::write(ITEM_CATALOG_FILE, format!("{}{}", CATALOG_HEADER, body))
+        .map_err(|e| format!("Failed to write {}: {}", ITEM_CATALOG_FILE, e))?;
+
+    Ok(format!(
+        "Wrote {} items to {} ({} added, {} removed)",
+        refreshed.item_count(), ITEM_CATALOG_FILE, added, removed
+    ))
+}
+
+fn guess_category(id: &str) -> &'static str {
+    let id = id.to_lowercase();
+    if id.starts_with("weapon_") {
+        "Weapon"
+    } else if id.starts_with("magazine_") || id.ends_with("_clip") {
+        "Magazine"
+    } else if ["weaponscope_", "weaponsights_", "weaponsuppressor_", "scoperail_", "improvisedrail_"].iter().any(|prefix| id.starts_with(prefix)) {
+        "Attachment"
+    } else if id.starts_with("1h_") || id.starts_with("2h_") {
+        "Melee"
+    } else {
+        "Misc"
+    }
+}
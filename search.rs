@@ -0,0 +1,262 @@
+use serenity::builder::*;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+
+use crate::commands::{reply, string_option, truncate};
+use crate::config::{ShopConfig, ShopItem};
+use crate::shared_state::SharedState;
+
+/// Results below this score are dropped.
+const MIN_SCORE: f32 = 0.55;
+
+/// One row of buy buttons per result, and a message holds at most five rows.
+const MAX_RESULTS: usize = 5;
+
+pub struct SearchHit<'a> {
+    pub shop: &'a ShopConfig,
+    pub item: &'a ShopItem,
+    pub score: f32,
+}
+
+pub fn shop_command() -> CreateCommand {
+    CreateCommand::new("shop")
+        .description("ร้านค้า")
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "search", "ค้นหาสินค้าว่าซื้อได้ที่ไหน")
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::String, "text", "ชื่อสินค้า เช่น ak47, แม็ก m16")
+                        .required(true)
+                        .set_autocomplete(true)
+                )
+        )
+}
+
+/// Items ranked by how well `query` matches their display name and the item IDs their
+/// buttons spawn. Matching tolerates typos, `_`/`-` separators and text typed with the Thai
+/// keyboard layout still active (e.g. `ฟา47` for `ak47`). Unlisted items are left out.
+pub fn search<'a>(shops: &'a [ShopConfig], query: &str, limit: usize) -> Vec<SearchHit<'a>> {
+    let query_tokens = tokenize(query);
+    if query_tokens.is_empty() {
+        return Vec::new();
+    }
+    let latin_tokens = tokenize(&thai_layout_to_latin(query));
+
+    let mut hits: Vec<SearchHit> = shops.iter()
+        .flat_map(|shop| shop.items.iter().filter(|item| item.is_listed()).map(move |item| (shop, item)))
+        .filter_map(|(shop, item)| {
+            let terms = item_terms(item);
+            let score = query_score(&query_tokens, &terms).max(query_score(&latin_tokens, &terms));
+            (score >= MIN_SCORE).then_some(SearchHit { shop, item, score })
+        })
+        .collect();
+
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+    hits.truncate(limit);
+    hits
+}
+
+pub async fn handle_shop_command(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState) {
+    let options = command.data.options();
+    let sub_options = match options.first() {
+        Some(ResolvedOption { name: "search", value: ResolvedValue::SubCommand(sub_options), .. }) => sub_options,
+        _ => return,
+    };
+    let text = string_option(sub_options, "text").unwrap_or("").trim();
+
+    let shop_file = shared_state.shop_file();
+    let hits = search(&shop_file.shops, text, MAX_RESULTS);
+    if hits.is_empty() {
+        reply(ctx, command, format!("🔍 ไม่พบสินค้าที่ตรงกับ \"{}\"", truncate(text, 100))).await;
+        return;
+    }
+
+    let stock = shared_state.db.get_stock_levels().unwrap_or_default();
+    let mut lines = Vec::new();
+    let mut components = Vec::new();
+
+    for (index, hit) in hits.iter().enumerate() {
+        lines.push(format!(
+            "**{}. {}** — {} COIN • <#{}>",
+            index + 1, hit.item.name, hit.item.price, hit.shop.channel
+        ));
+
+        let buttons: Vec<CreateButton> = hit.item.active_buttons()
+            .take(5)
+            .map(|button| {
+                let sold_out = button.stock.is_some()
+                    && stock.get(&button.trigger).is_some_and(|remaining| *remaining <= 0);
+                CreateButton::new(&button.trigger)
+                    .label(truncate(&format!("{}. {}", index + 1, button.text), 80))
+                    .style(ButtonStyle::Danger)
+                    .disabled(sold_out)
+            })
+            .collect();
+        if !buttons.is_empty() {
+            components.push(CreateActionRow::Buttons(buttons));
+        }
+    }
+
+    let embed = CreateEmbed::new()
+        .title(format!("🔍 ผลการค้นหา \"{}\"", truncate(text, 100)))
+        .color(0x9900cc)
+        .description(lines.join("\n"))
+        .footer(CreateEmbedFooter::new("กดปุ่มด้านล่างเพื่อซื้อได้ทันที • © powered by TimeSkip"));
+
+    let response = CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .embed(embed)
+            .components(components)
+            .ephemeral(true)
+    );
+    let _ = command.create_response(&ctx.http, response).await;
+}
+
+pub async fn handle_shop_autocomplete(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState) {
+    let query = command.data.autocomplete()
+        .map(|focused| focused.value.to_string())
+        .unwrap_or_default();
+
+    let mut response = CreateAutocompleteResponse::new();
+    let shop_file = shared_state.shop_file();
+    if query.trim().is_empty() {
+        let items = shop_file.shops.iter().flat_map(|shop| &shop.items).filter(|item| item.is_listed()).take(25);
+        for item in items {
+            response = response.add_string_choice(truncate(&item.name, 100), truncate(&item.name, 100));
+        }
+    } else {
+        for hit in search(&shop_file.shops, &query, 25) {
+            response = response.add_string_choice(truncate(&hit.item.name, 100), truncate(&hit.item.name, 100));
+        }
+    }
+
+    let _ = command.create_response(&ctx.http, CreateInteractionResponse::Autocomplete(response)).await;
+}
+
+/// Lowercased words; `_`, `-`, `/` and `,` count as spaces, and Thai runs are split from
+/// Latin ones so `แม็กm16` reads as `แม็ก m16`.
+fn tokenize(text: &str) -> Vec<String> {
+    let mut spaced = String::with_capacity(text.len());
+    let mut previous_thai = None;
+    for c in text.to_lowercase().chars() {
+        let thai = is_thai(c);
+        if c.is_alphanumeric() && previous_thai.is_some_and(|previous| previous != thai) {
+            spaced.push(' ');
+        }
+        previous_thai = c.is_alphanumeric().then_some(thai).or(previous_thai);
+        spaced.push(c);
+    }
+
+    spaced
+        .split(|c: char| c.is_whitespace() || matches!(c, '_' | '-' | '/' | ','))
+        .filter(|token| !token.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn is_thai(c: char) -> bool {
+    ('\u{0E00}'..='\u{0E7F}').contains(&c)
+}
+
+/// Words of the item name plus every `#SpawnItem` ID, both split and joined
+/// (`Weapon_SCAR_DMR` gives `weapon`, `scar`, `dmr` and `weaponscardmr`).
+fn item_terms(item: &ShopItem) -> Vec<String> {
+    let mut terms = tokenize(&item.name);
+    terms.push(tokenize(&item.name).concat());
+
+    for command in item.all_commands() {
+        let mut parts = command.command().split_whitespace();
+        if parts.next() == Some("#SpawnItem") {
+            if let Some(spawn_id) = parts.next() {
+                terms.extend(tokenize(spawn_id));
+                terms.push(tokenize(spawn_id).concat());
+            }
+        }
+    }
+
+    terms.sort();
+    terms.dedup();
+    terms
+}
+
+/// Average of each query word's best match, so every word has to match something.
+fn query_score(query_tokens: &[String], terms: &[String]) -> f32 {
+    if query_tokens.is_empty() {
+        return 0.0;
+    }
+
+    let total: f32 = query_tokens.iter()
+        .map(|token| terms.iter().map(|term| token_score(token, term)).fold(0.0, f32::max))
+        .sum();
+    total / query_tokens.len() as f32
+}
+
+fn token_score(token: &str, term: &str) -> f32 {
+    if token == term {
+        return 1.0;
+    }
+    if term.starts_with(token) {
+        return 0.9;
+    }
+    if term.contains(token) {
+        return 0.8;
+    }
+
+    let longest = token.chars().count().max(term.chars().count());
+    if longest == 0 {
+        return 0.0;
+    }
+    let similarity = 1.0 - edit_distance(token, term) as f32 / longest as f32;
+    // Prefix typos ("scra" for "scar...") compare against the same-length start of the term.
+    let prefix: String = term.chars().take(token.chars().count()).collect();
+    let prefix_similarity = if token.chars().count() >= 3 {
+        0.85 * (1.0 - edit_distance(token, &prefix) as f32 / token.chars().count() as f32)
+    } else {
+        0.0
+    };
+    similarity.max(prefix_similarity)
+}
+
+/// Edit distance counting a swap of two neighbouring characters as one edit.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = distance;
+        }
+    }
+
+    rows[a.len()][b.len()]
+}
+
+/// Maps characters typed with the Thai Kedmanee layout back to the QWERTY keys they sit on.
+fn thai_layout_to_latin(text: &str) -> String {
+    const LAYOUT: [(char, char); 44] = [
+        ('ๅ', '1'), ('ภ', '4'), ('ถ', '5'), ('ุ', '6'), ('ึ', '7'), ('ค', '8'), ('ต', '9'), ('จ', '0'),
+        ('ๆ', 'q'), ('ไ', 'w'), ('ำ', 'e'), ('พ', 'r'), ('ะ', 't'), ('ั', 'y'), ('ี', 'u'), ('ร', 'i'),
+        ('น', 'o'), ('ย', 'p'), ('บ', '['), ('ล', ']'),
+        ('ฟ', 'a'), ('ห', 's'), ('ก', 'd'), ('ด', 'f'), ('เ', 'g'), ('้', 'h'), ('่', 'j'), ('า', 'k'),
+        ('ส', 'l'), ('ว', ';'), ('ง', '\''),
+        ('ผ', 'z'), ('ป', 'x'), ('แ', 'c'), ('อ', 'v'), ('ิ', 'b'), ('ื', 'n'), ('ท', 'm'), ('ม', ','),
+        ('ใ', '.'), ('ฝ', '/'), ('ข', '-'), ('ช', '='), ('ฃ', '\\'),
+    ];
+
+    text.chars()
+        .map(|c| LAYOUT.iter().find(|(thai, _)| *thai == c).map(|(_, latin)| *latin).unwrap_or(c))
+        .collect()
+}
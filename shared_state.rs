@@ -1,31 +1,69 @@
-use std::sync::Arc;
+use std::sync::{Arc, RwLock as StdRwLock};
 use tokio::sync::{Mutex, RwLock};
 use dashmap::DashMap;
 use std::time::{Duration, Instant};
-use crate::config::Config;
-use crate::database::Database;
+use chrono::{DateTime, NaiveDateTime, Utc, Duration as ChronoDuration};
+use crate::config::{CleanupProfile, Config, ShopFile};
+use crate::database::{Database, DB_TIME_FORMAT};
+
+pub struct PendingPurchase {
+    pub user_id: String,
+    pub trigger: String,
+}
 
 pub struct SharedState {
     pub db: Arc<Database>,
     pub config: Arc<Config>,
+    /// The live shops. `/shopedit` swaps in a new copy, so take a snapshot with `shop_file()`
+    /// instead of holding the lock.
+    pub shops: Arc<StdRwLock<Arc<ShopFile>>>,
+    /// Held while `/shopedit` applies a change so two edits can't overwrite each other.
+    pub shop_edit_lock: Arc<Mutex<()>>,
     pub destroy_lock: Arc<Mutex<()>>,
     pub bot_active: Arc<RwLock<bool>>,
-    pub item_cooldowns: Arc<DashMap<(String, String), Instant>>,
+    /// (user, trigger) → when the cooldown ends. Mirrors the `cooldowns` table.
+    pub item_cooldowns: Arc<DashMap<(String, String), DateTime<Utc>>>,
     pub command_queue: Arc<Mutex<Vec<Vec<String>>>>,
+    pub cleanup_last_run: Arc<DashMap<String, DateTime<Utc>>>,
+    pub cleanup_votes: Arc<DashMap<String, Vec<(String, Instant)>>>,
+    pub pending_purchases: Arc<DashMap<String, PendingPurchase>>,
 }
 
 impl SharedState {
-    pub fn new(db: Database, config: Config) -> Self {
+    pub fn new(db: Database, mut config: Config) -> Self {
+        let item_cooldowns = DashMap::new();
+        for (user_id, trigger, expires_at) in db.get_active_cooldowns().unwrap_or_default() {
+            if let Ok(expires_at) = NaiveDateTime::parse_from_str(&expires_at, DB_TIME_FORMAT) {
+                item_cooldowns.insert((user_id, trigger), expires_at.and_utc());
+            }
+        }
+        println!("Restored {} active cooldowns", item_cooldowns.len());
+        
+        let shop_file = std::mem::take(&mut config.shop_file);
+        
         SharedState {
             db: Arc::new(db),
             config: Arc::new(config),
+            shops: Arc::new(StdRwLock::new(Arc::new(shop_file))),
+            shop_edit_lock: Arc::new(Mutex::new(())),
             destroy_lock: Arc::new(Mutex::new(())),
             bot_active: Arc::new(RwLock::new(true)),
-            item_cooldowns: Arc::new(DashMap::new()),
+            item_cooldowns: Arc::new(item_cooldowns),
             command_queue: Arc::new(Mutex::new(Vec::new())),
+            cleanup_last_run: Arc::new(DashMap::new()),
+            cleanup_votes: Arc::new(DashMap::new()),
+            pending_purchases: Arc::new(DashMap::new()),
         }
     }
     
+    pub fn shop_file(&self) -> Arc<ShopFile> {
+        self.shops.read().unwrap().clone()
+    }
+    
+    pub fn replace_shop_file(&self, file: ShopFile) {
+        *self.shops.write().unwrap() = Arc::new(file);
+    }
+    
     pub async fn is_bot_active(&self) -> bool {
         *self.bot_active.read().await
     }
@@ -36,17 +74,84 @@ impl SharedState {
     
     pub fn check_cooldown(&self, user_id: &str, item_id: &str) -> Option<Duration> {
         let key = (user_id.to_string(), item_id.to_string());
-        if let Some(last_use) = self.item_cooldowns.get(&key) {
-            let elapsed = last_use.elapsed();
-            if elapsed < Duration::from_secs(20) {
-                return Some(Duration::from_secs(20) - elapsed);
-            }
-        }
-        None
+        let expires_at = *self.item_cooldowns.get(&key)?;
+        (expires_at - Utc::now()).to_std().ok()
     }
     
-    pub fn set_cooldown(&self, user_id: &str, item_id: &str) {
+    /// Starts a cooldown and persists it so restarting the bot doesn't reset it.
+    pub fn set_cooldown(&self, user_id: &str, item_id: &str, cooldown: Duration) {
+        let expires_at = Utc::now() + ChronoDuration::from_std(cooldown).unwrap_or_else(|_| ChronoDuration::zero());
         let key = (user_id.to_string(), item_id.to_string());
-        self.item_cooldowns.insert(key, Instant::now());
+        self.item_cooldowns.insert(key, expires_at);
+        let _ = self.db.set_cooldown(user_id, item_id, &expires_at.format(DB_TIME_FORMAT).to_string());
+    }
+    
+    /// Active cooldowns of one user as (trigger, time left), soonest first.
+    pub fn user_cooldowns(&self, user_id: &str) -> Vec<(String, Duration)> {
+        let now = Utc::now();
+        let mut cooldowns: Vec<(String, Duration)> = self.item_cooldowns.iter()
+            .filter(|entry| entry.key().0 == user_id)
+            .filter_map(|entry| Some((entry.key().1.clone(), (*entry.value() - now).to_std().ok()?)))
+            .collect();
+        cooldowns.sort_by_key(|(_, remaining)| *remaining);
+        cooldowns
+    }
+    
+    pub fn clear_cooldowns(&self, user_id: &str, item_id: Option<&str>) -> usize {
+        self.item_cooldowns.retain(|(user, item), _| {
+            user != user_id || item_id.is_some_and(|item_id| item != item_id)
+        });
+        self.db.clear_cooldowns(user_id, item_id).unwrap_or(0)
+    }
+    
+    /// Drops expired entries from memory and the database; returns how many rows were removed.
+    pub fn evict_expired_cooldowns(&self) -> usize {
+        let now = Utc::now();
+        self.item_cooldowns.retain(|_, expires_at| *expires_at > now);
+        self.db.delete_expired_cooldowns().unwrap_or(0)
     }
-}
\ No newline at end of file
+    
+    /// Time left before a cleanup profile may run again, counted from the last run of
+    /// any kind (automatic, button or vote).
+    pub fn cleanup_cooldown_remaining(&self, profile: &CleanupProfile) -> Option<ChronoDuration> {
+        let last_run = *self.cleanup_last_run.get(&profile.id)?;
+        let ready_at = last_run + ChronoDuration::minutes(profile.cooldown_minutes as i64);
+        let now = Utc::now();
+        if now < ready_at {
+            Some(ready_at - now)
+        } else {
+            None
+        }
+    }
+    
+    pub fn set_cleanup_last_run(&self, profile_id: &str, at: DateTime<Utc>) {
+        self.cleanup_last_run.insert(profile_id.to_string(), at);
+    }
+    
+    /// Records a vote and returns the distinct voters still inside the profile's window.
+    pub fn add_cleanup_vote(&self, profile: &CleanupProfile, user_id: &str) -> Vec<String> {
+        let window = Duration::from_secs(profile.vote_window_seconds);
+        let mut votes = self.cleanup_votes.entry(profile.id.clone()).or_default();
+        votes.retain(|(_, at)| at.elapsed() < window);
+        if !votes.iter().any(|(voter, _)| voter == user_id) {
+            votes.push((user_id.to_string(), Instant::now()));
+        }
+        votes.iter().map(|(voter, _)| voter.clone()).collect()
+    }
+    
+    pub fn clear_cleanup_votes(&self, profile_id: &str) {
+        self.cleanup_votes.remove(profile_id);
+    }
+    
+    pub fn add_pending_purchase(&self, token: &str, user_id: &str, trigger: &str) {
+        self.pending_purchases.insert(token.to_string(), PendingPurchase {
+            user_id: user_id.to_string(),
+            trigger: trigger.to_string(),
+        });
+    }
+    
+    /// Removes and returns a pending purchase; each confirmation can only be used once.
+    pub fn take_pending_purchase(&self, token: &str) -> Option<PendingPurchase> {
+        self.pending_purchases.remove(token).map(|(_, pending)| pending)
+    }
+}
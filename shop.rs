@@ -0,0 +1,580 @@
+use serenity::builder::{CreateActionRow, CreateAttachment, CreateButton, CreateEmbed, CreateEmbedFooter, CreateMessage, EditMessage, GetMessages};
+use serenity::http::Http;
+use serenity::model::prelude::*;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+use log::{error, warn};
+use tokio::time::{sleep, Duration};
+
+use crate::catalog::{catalog_fingerprint, render_catalog, CATALOG_MESSAGE_KEY};
+use crate::config::{ShopConfig, ShopItem, ShopLayout};
+use crate::database::ShopMessage;
+use crate::loot::describe_odds;
+use crate::promotions::{server_now, Sale};
+use crate::shared_state::SharedState;
+
+/// Thumbnail used when neither the item nor its shop has a usable image.
+const DEFAULT_IMAGE: &str = "https://cdn.discordapp.com/attachments/1347264410087067709/1364553843316363304/raw.png";
+
+pub enum ImageSource {
+    Url(String),
+    File { path: String, fingerprint: String },
+}
+
+impl ImageSource {
+    /// Changes whenever the rendered image would, so edited files get re-uploaded.
+    fn key(&self) -> String {
+        match self {
+            ImageSource::Url(url) => url.clone(),
+            ImageSource::File { path, fingerprint } => format!("{}#{}", path, fingerprint),
+        }
+    }
+}
+
+pub struct PreparedItem {
+    pub embed: CreateEmbed,
+    pub components: Vec<CreateActionRow>,
+    pub upload: Option<PendingUpload>,
+    /// The thumbnail is the file already attached to the message being edited.
+    pub keeps_attachment: bool,
+}
+
+/// A local image sent with the message; its CDN URL is cached for that message once Discord
+/// returns it.
+pub struct PendingUpload {
+    pub attachment: CreateAttachment,
+    path: String,
+    fingerprint: String,
+}
+
+/// Embed and buttons for one shop item, without the thumbnail. Buttons with limited stock
+/// show what's left and are disabled once sold out; a running sale shows both prices.
+fn render_item(item: &ShopItem, stock: &HashMap<String, i64>, sale: Option<&Sale>) -> (CreateEmbed, Vec<CreateActionRow>) {
+    let mut embed = CreateEmbed::new()
+        .title(&item.name)
+        .footer(CreateEmbedFooter::new("© powered by TimeSkip"));
+    embed = match sale {
+        Some(sale) => embed
+            .color(0xFF4500)
+            .field("💰 ราคา", format!("~~{}~~ **{}** COIN", item.price, sale.apply(item.price)), true)
+            .field(format!("🔥 {}", sale.name), sale.describe(), true),
+        None => embed
+            .color(0xFF00FF)
+            .field("💰 ราคา", format!("{} COIN", item.price), true),
+    };
+
+    let stock_lines: Vec<String> = item.active_buttons()
+        .filter_map(|button| {
+            let max = button.stock?;
+            let remaining = stock.get(&button.trigger).copied().unwrap_or(max as i64);
+            Some(format!("{}: {}/{}", button.text, remaining, max))
+        })
+        .collect();
+    if !stock_lines.is_empty() {
+        embed = embed.field("📦 คงเหลือ", stock_lines.join("\n"), true);
+    }
+
+    let limit_lines: Vec<String> = item.active_buttons()
+        .filter_map(|button| {
+            let limits: Vec<String> = [(button.daily_limit, "วันละ"), (button.weekly_limit, "สัปดาห์ละ")]
+                .iter()
+                .filter_map(|(limit, label)| limit.map(|limit| format!("{} {} ครั้ง", label, limit)))
+                .collect();
+            (!limits.is_empty()).then(|| format!("{}: {}", button.text, limits.join(", ")))
+        })
+        .collect();
+    if !limit_lines.is_empty() {
+        embed = embed.field("⏱️ จำกัดการซื้อ", limit_lines.join("\n"), true);
+    }
+    if let Some(loot) = &item.loot {
+        embed = embed.field("🎲 โอกาสที่จะได้รับ", describe_odds(loot), false);
+    }
+
+    let mut components = vec![];
+    let mut buttons = vec![];
+
+    for button in item.active_buttons() {
+        let sold_out = button.stock.is_some() && stock.get(&button.trigger).is_some_and(|remaining| *remaining <= 0);
+        let label = if sold_out {
+            format!("{} (หมด)", button.text)
+        } else {
+            button.text.clone()
+        };
+
+        buttons.push(
+            CreateButton::new(&button.trigger)
+                .label(label)
+                .style(ButtonStyle::Danger)
+                .disabled(sold_out)
+        );
+
+        if buttons.len() == 5 {
+            components.push(CreateActionRow::Buttons(buttons.clone()));
+            buttons.clear();
+        }
+    }
+
+    if !buttons.is_empty() {
+        components.push(CreateActionRow::Buttons(buttons));
+    }
+
+    (embed, components)
+}
+
+/// The item's image, then the shop's `fallback_image`, then `DEFAULT_IMAGE`. Local paths are
+/// relative to the working directory and may use Windows separators.
+pub fn image_source(shop: &ShopConfig, item: &ShopItem) -> ImageSource {
+    for image in [&item.image, &shop.fallback_image].into_iter().flatten() {
+        if image.starts_with("http://") || image.starts_with("https://") {
+            return ImageSource::Url(image.clone());
+        }
+
+        let path = normalize_image_path(image);
+        match std::fs::metadata(&path) {
+            Ok(metadata) => {
+                let modified = metadata.modified()
+                    .ok()
+                    .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                    .map(|duration| duration.as_secs())
+                    .unwrap_or(0);
+                return ImageSource::File { path, fingerprint: format!("{}-{}", metadata.len(), modified) };
+            },
+            Err(_) => warn!("Image for {} not found: {}", item.name, path),
+        }
+    }
+
+    ImageSource::Url(DEFAULT_IMAGE.to_string())
+}
+
+/// `.\Image\BotShop\Rifle\SCAR-DMR.png` → `Image/BotShop/Rifle/SCAR-DMR.png`
+fn normalize_image_path(image: &str) -> String {
+    let path = image.replace('\\', "/");
+    match path.strip_prefix("./") {
+        Some(stripped) => stripped.to_string(),
+        None => path,
+    }
+}
+
+/// Renders an item with its thumbnail for `message_id`, or for a new message. The image file is
+/// read only when that message doesn't already have its current version attached.
+pub async fn prepare_item(
+    shared_state: &SharedState,
+    shop: &ShopConfig,
+    item: &ShopItem,
+    stock: &HashMap<String, i64>,
+    message_id: Option<MessageId>,
+) -> PreparedItem {
+    let sale = shared_state.config.promotions.sale_for(&shop.channel, item, server_now());
+    let (embed, components) = render_item(item, stock, sale.as_ref());
+    let source = image_source(shop, item);
+    let (path, fingerprint) = match &source {
+        ImageSource::Url(url) => return PreparedItem { embed: embed.thumbnail(url), components, upload: None, keeps_attachment: false },
+        ImageSource::File { path, fingerprint } => (path, fingerprint),
+    };
+
+    let cached = message_id.and_then(|id| shared_state.db.get_cached_image(&id.to_string(), path, fingerprint).ok().flatten());
+    if let Some(url) = cached {
+        return PreparedItem { embed: embed.thumbnail(url), components, upload: None, keeps_attachment: true };
+    }
+
+    match tokio::fs::read(path).await {
+        Ok(data) => {
+            // Keep the attachment name ASCII so the attachment:// reference always resolves.
+            let extension = Path::new(path)
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .unwrap_or("png")
+                .to_lowercase();
+            let filename = format!("item.{}", extension);
+            PreparedItem {
+                embed: embed.thumbnail(format!("attachment://{}", filename)),
+                components,
+                upload: Some(PendingUpload {
+                    attachment: CreateAttachment::bytes(data, filename),
+                    path: path.clone(),
+                    fingerprint: fingerprint.clone(),
+                }),
+                keeps_attachment: false,
+            }
+        },
+        Err(e) => {
+            error!("Failed to read image {}: {:?}", path, e);
+            PreparedItem { embed: embed.thumbnail(DEFAULT_IMAGE), components, upload: None, keeps_attachment: false }
+        }
+    }
+}
+
+async fn post_item(http: &Http, shared_state: &SharedState, channel_id: ChannelId, prepared: PreparedItem) -> serenity::Result<Message> {
+    let mut message = CreateMessage::new().embed(prepared.embed).components(prepared.components);
+    if let Some(upload) = &prepared.upload {
+        message = message.add_file(upload.attachment.clone());
+    }
+
+    let message = channel_id.send_message(http, message).await?;
+    remember_upload(shared_state, &message, prepared.upload.as_ref());
+    Ok(message)
+}
+
+async fn edit_item(http: &Http, shared_state: &SharedState, channel_id: ChannelId, message_id: MessageId, prepared: PreparedItem) -> serenity::Result<Message> {
+    let mut edit = EditMessage::new().embed(prepared.embed).components(prepared.components);
+    if let Some(upload) = &prepared.upload {
+        // The new attachment replaces the old one.
+        edit = edit.new_attachment(upload.attachment.clone());
+    } else if !prepared.keeps_attachment {
+        // The image is a URL now; a file uploaded for an earlier image would still show.
+        edit = edit.remove_all_attachments();
+    }
+    if !prepared.keeps_attachment {
+        let _ = shared_state.db.forget_images_of_message(&message_id.to_string());
+    }
+
+    let message = channel_id.edit_message(http, message_id, edit).await?;
+    remember_upload(shared_state, &message, prepared.upload.as_ref());
+    Ok(message)
+}
+
+fn remember_upload(shared_state: &SharedState, message: &Message, upload: Option<&PendingUpload>) {
+    let upload = match upload {
+        Some(upload) => upload,
+        None => return,
+    };
+
+    let url = message.embeds.first()
+        .and_then(|embed| embed.thumbnail.as_ref())
+        .map(|thumbnail| thumbnail.url.as_str())
+        .filter(|url| url.starts_with("https://"));
+    if let Some(url) = url {
+        let _ = shared_state.db.cache_image(&message.id.to_string(), &upload.path, &upload.fingerprint, url);
+    }
+}
+
+/// What `/updateshop` changed in one shop channel.
+#[derive(Default)]
+pub struct SyncSummary {
+    pub added: Vec<String>,
+    pub edited: Vec<String>,
+    pub removed: Vec<String>,
+    pub unchanged: usize,
+    pub failed: Vec<String>,
+}
+
+/// One tracked message of a shop channel.
+enum ShopEntry<'a> {
+    Item(&'a ShopItem),
+    Catalog,
+}
+
+impl ShopEntry<'_> {
+    fn key(&self) -> &str {
+        match self {
+            ShopEntry::Item(item) => &item.name,
+            ShopEntry::Catalog => CATALOG_MESSAGE_KEY,
+        }
+    }
+
+    fn content_hash(&self, shared_state: &SharedState, shop: &ShopConfig) -> String {
+        match self {
+            ShopEntry::Item(item) => content_hash(&[
+                serde_json::to_string(item).unwrap_or_default(),
+                image_source(shop, item).key(),
+                shared_state.config.promotions.sale_for(&shop.channel, item, server_now())
+                    .map(|sale| sale.key())
+                    .unwrap_or_default(),
+            ]),
+            ShopEntry::Catalog => content_hash(&[catalog_fingerprint(shop)]),
+        }
+    }
+
+    /// Whether `message_id` still shows the entry's image. A file image is only current while
+    /// the message has its own upload of it cached; otherwise it may show a URL that's gone.
+    fn image_is_current(&self, shared_state: &SharedState, shop: &ShopConfig, message_id: &str) -> bool {
+        match self {
+            ShopEntry::Item(item) => match image_source(shop, item) {
+                ImageSource::File { path, fingerprint } => {
+                    matches!(shared_state.db.get_cached_image(message_id, &path, &fingerprint), Ok(Some(_)))
+                },
+                ImageSource::Url(_) => true,
+            },
+            ShopEntry::Catalog => true,
+        }
+    }
+
+    async fn prepare(&self, shared_state: &SharedState, shop: &ShopConfig, stock: &HashMap<String, i64>, message_id: Option<MessageId>) -> PreparedItem {
+        match self {
+            ShopEntry::Item(item) => prepare_item(shared_state, shop, item, stock, message_id).await,
+            ShopEntry::Catalog => {
+                let (embed, components) = render_catalog(shop);
+                PreparedItem { embed, components, upload: None, keeps_attachment: false }
+            },
+        }
+    }
+}
+
+/// Brings one shop channel in line with the catalog: edits changed messages in place, posts
+/// new ones and deletes the messages of removed ones. Only the bot's own messages are touched.
+pub async fn sync_shop(http: &Http, shared_state: &SharedState, shop: &ShopConfig, bot_id: UserId, force: bool) -> SyncSummary {
+    let mut summary = SyncSummary::default();
+    let channel_id = match shop.channel.parse::<u64>() {
+        Ok(id) => ChannelId::new(id),
+        Err(_) => return summary,
+    };
+
+    let stock = shared_state.db.get_stock_levels().unwrap_or_default();
+    let mut stored: HashMap<String, ShopMessage> = shared_state.db.get_shop_messages(&shop.channel)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|message| (message.item_key.clone(), message))
+        .collect();
+
+    // Channels posted before message IDs were tracked: adopt the old messages by their buttons.
+    let mut legacy: Vec<Message> = if stored.is_empty() {
+        channel_id.messages(http, GetMessages::new().limit(100))
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|message| message.author.id == bot_id)
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let entries: Vec<ShopEntry> = match shop.layout {
+        ShopLayout::Items => shop.items.iter().filter(|item| item.is_listed()).map(ShopEntry::Item).collect(),
+        ShopLayout::Catalog => vec![ShopEntry::Catalog],
+    };
+
+    for entry in &entries {
+        let key = entry.key();
+        let hash = entry.content_hash(shared_state, shop);
+        let existing = match stored.remove(key) {
+            Some(message) => message.message_id.parse::<u64>()
+                .ok()
+                .map(|id| {
+                    let same = message.content_hash == hash && entry.image_is_current(shared_state, shop, &message.message_id);
+                    (MessageId::new(id), same)
+                }),
+            None => {
+                let position = match entry {
+                    ShopEntry::Item(item) => item.active_buttons().next().and_then(|button| {
+                        legacy.iter().position(|message| message_has_button(message, &button.trigger))
+                    }),
+                    ShopEntry::Catalog => None,
+                };
+                position.map(|index| (legacy.remove(index).id, false))
+            },
+        };
+
+        if let Some((message_id, same)) = existing {
+            if same && !force {
+                summary.unchanged += 1;
+                continue;
+            }
+
+            let prepared = entry.prepare(shared_state, shop, &stock, Some(message_id)).await;
+            if edit_item(http, shared_state, channel_id, message_id, prepared).await.is_ok() {
+                let _ = shared_state.db.upsert_shop_message(&shop.channel, key, &message_id.to_string(), &hash);
+                summary.edited.push(entry_label(key));
+                continue;
+            }
+            // Most likely deleted by hand; post it again.
+            let _ = shared_state.db.forget_images_of_message(&message_id.to_string());
+        }
+
+        let prepared = entry.prepare(shared_state, shop, &stock, None).await;
+        match post_item(http, shared_state, channel_id, prepared).await {
+            Ok(message) => {
+                let _ = shared_state.db.upsert_shop_message(&shop.channel, key, &message.id.to_string(), &hash);
+                summary.added.push(entry_label(key));
+            },
+            Err(e) => {
+                error!("Failed to post shop message {}: {:?}", key, e);
+                summary.failed.push(entry_label(key));
+            }
+        }
+    }
+
+    let mut stale: Vec<MessageId> = legacy.iter().map(|message| message.id).collect();
+    for (item_key, message) in stored {
+        if let Ok(id) = message.message_id.parse::<u64>() {
+            stale.push(MessageId::new(id));
+        }
+        let _ = shared_state.db.delete_shop_message(&shop.channel, &item_key);
+        summary.removed.push(entry_label(&item_key));
+    }
+    for message_id in &stale {
+        let _ = shared_state.db.forget_images_of_message(&message_id.to_string());
+    }
+    delete_messages(http, channel_id, &stale).await;
+
+    summary
+}
+
+/// Edits the posted item messages of `shop` that are out of date, e.g. because a sale started
+/// or ended. Unlike `sync_shop`, nothing is posted, adopted or deleted.
+pub async fn refresh_stale_items(http: &Http, shared_state: &SharedState, shop: &ShopConfig) {
+    let channel_id = match shop.channel.parse::<u64>() {
+        Ok(id) => ChannelId::new(id),
+        Err(_) => return,
+    };
+    let stored = shared_state.db.get_shop_messages(&shop.channel).unwrap_or_default();
+    let stock = shared_state.db.get_stock_levels().unwrap_or_default();
+
+    for item in shop.items.iter().filter(|item| item.is_listed()) {
+        let message = match stored.iter().find(|message| message.item_key == item.name) {
+            Some(message) => message,
+            None => continue,
+        };
+        let hash = ShopEntry::Item(item).content_hash(shared_state, shop);
+        let message_id = match message.message_id.parse::<u64>() {
+            Ok(id) if message.content_hash != hash => MessageId::new(id),
+            _ => continue,
+        };
+
+        let prepared = prepare_item(shared_state, shop, item, &stock, Some(message_id)).await;
+        match edit_item(http, shared_state, channel_id, message_id, prepared).await {
+            Ok(_) => {
+                let _ = shared_state.db.upsert_shop_message(&shop.channel, &item.name, &message.message_id, &hash);
+            },
+            Err(e) => error!("Failed to refresh shop message for {}: {:?}", item.name, e),
+        }
+    }
+}
+
+fn entry_label(key: &str) -> String {
+    if key == CATALOG_MESSAGE_KEY {
+        "📂 เมนูหมวดหมู่".to_string()
+    } else {
+        key.to_string()
+    }
+}
+
+/// Bulk-deletes in batches of 100, falling back to one-by-one for batches Discord refuses
+/// (single messages, or messages older than 14 days).
+async fn delete_messages(http: &Http, channel_id: ChannelId, message_ids: &[MessageId]) {
+    for chunk in message_ids.chunks(100) {
+        if chunk.len() >= 2 && channel_id.delete_messages(http, chunk).await.is_ok() {
+            continue;
+        }
+        for message_id in chunk {
+            let _ = channel_id.delete_message(http, *message_id).await;
+        }
+    }
+}
+
+/// 64-bit FNV-1a of the parts, each prefixed with its length so neighbours can't run into
+/// each other. Hashes are saved in `shop_messages`, so unlike std's `DefaultHasher` this has
+/// to give the same result on every build.
+fn content_hash(parts: &[String]) -> String {
+    const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+    let mut hash = FNV_OFFSET;
+    for part in parts {
+        for byte in (part.len() as u64).to_le_bytes().iter().chain(part.as_bytes()) {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+    format!("{:016x}", hash)
+}
+
+/// Re-renders the posted shop messages that carry any of `triggers`, so stock counts and
+/// sold-out buttons stay current.
+pub async fn refresh_item_messages(http: &Http, shared_state: &SharedState, triggers: &[String]) {
+    let stock = shared_state.db.get_stock_levels().unwrap_or_default();
+
+    for shop in &shared_state.shop_file().shops {
+        let channel_id = match shop.channel.parse::<u64>() {
+            Ok(id) => ChannelId::new(id),
+            Err(_) => continue,
+        };
+        let stored = shared_state.db.get_shop_messages(&shop.channel).unwrap_or_default();
+
+        for item in &shop.items {
+            if !item.is_listed() || !item.buttons.iter().any(|button| triggers.contains(&button.trigger)) {
+                continue;
+            }
+
+            let message_id = stored.iter()
+                .find(|message| message.item_key == item.name)
+                .and_then(|message| message.message_id.parse::<u64>().ok());
+            if let Some(message_id) = message_id.map(MessageId::new) {
+                let prepared = prepare_item(shared_state, shop, item, &stock, Some(message_id)).await;
+                if let Err(e) = edit_item(http, shared_state, channel_id, message_id, prepared).await {
+                    error!("Failed to refresh shop message for {}: {:?}", item.name, e);
+                }
+            }
+        }
+    }
+}
+
+fn message_has_button(message: &Message, custom_id: &str) -> bool {
+    message.components.iter()
+        .flat_map(|row| &row.components)
+        .any(|component| matches!(
+            component,
+            ActionRowComponent::Button(Button { data: ButtonKind::NonLink { custom_id: id, .. }, .. }) if id == custom_id
+        ))
+}
+
+/// Creates stock rows for limited buttons and caps them at a lowered maximum.
+pub fn sync_stock_rows(shared_state: &SharedState) {
+    for shop in &shared_state.shop_file().shops {
+        for item in &shop.items {
+            for button in &item.buttons {
+                if let Some(max) = button.stock {
+                    if let Err(e) = shared_state.db.sync_stock(&button.trigger, max) {
+                        error!("Failed to initialize stock for {}: {:?}", button.trigger, e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Creates stock rows for limited buttons, then refills them every minute once their
+/// `restock_hours` have passed.
+pub async fn start_restock_task(http: Arc<Http>, shared_state: Arc<SharedState>) {
+    sync_stock_rows(&shared_state);
+
+    loop {
+        let mut restocked = Vec::new();
+
+        for shop in &shared_state.shop_file().shops {
+            for item in &shop.items {
+                for button in &item.buttons {
+                    if let (Some(max), Some(hours)) = (button.stock, button.restock_hours) {
+                        match shared_state.db.restock_if_due(&button.trigger, max, hours) {
+                            Ok(true) => {
+                                println!("Restocked {} ({}) to {}", item.name, button.text, max);
+                                restocked.push(button.trigger.clone());
+                            },
+                            Ok(false) => {},
+                            Err(e) => error!("Failed to restock {}: {:?}", button.trigger, e),
+                        }
+                    }
+                }
+            }
+        }
+
+        if !restocked.is_empty() {
+            refresh_item_messages(&http, &shared_state, &restocked).await;
+        }
+
+        sleep(Duration::from_secs(60)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_hash_is_stable() {
+        let parts = ["AK47", "Image/BotShop/Rifle/AK47.png#1024-1700000000", ""].map(String::from);
+        assert_eq!(content_hash(&parts), "8a6500c28cae9af6");
+        assert_ne!(content_hash(&["ab".to_string(), "c".to_string()]), content_hash(&["a".to_string(), "bc".to_string()]));
+    }
+}
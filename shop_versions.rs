@@ -0,0 +1,215 @@
+use serenity::builder::*;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use similar::TextDiff;
+
+use crate::commands::{integer_option, reply, require_permission, truncate};
+use crate::config::ShopFile;
+use crate::database::{Database, ShopVersion};
+use crate::permissions::Permission;
+use crate::shared_state::SharedState;
+use crate::shopedit::{run_command_edit, Change};
+use crate::shopfile::{parse_shop_file, serialize_shop_file, ShopFormat};
+use crate::stash::discord_time;
+
+/// Author of the versions saved when the shop file was changed on disk between runs.
+pub const FILE_AUTHOR: &str = "file";
+
+const DIFF_CONTEXT_LINES: usize = 3;
+
+/// Saves `file` unless it matches the latest version, and returns the new version's ID.
+/// Versions are stored as JSON whatever the shop file's format, so diffs stay comparable.
+pub fn record_version(db: &Database, author: &str, summary: &str, file: &ShopFile) -> Result<Option<i64>, String> {
+    let content = serialize_shop_file(file, ShopFormat::Json)?;
+    let latest = db.get_latest_shop_version().map_err(|e| e.to_string())?;
+    let diff = match &latest {
+        Some(latest) if latest.content == content => return Ok(None),
+        Some(latest) => TextDiff::from_lines(&latest.content, &content)
+            .unified_diff()
+            .context_radius(DIFF_CONTEXT_LINES)
+            .to_string(),
+        None => String::new(),
+    };
+    db.add_shop_version(author, summary, &content, &diff)
+        .map(Some)
+        .map_err(|e| e.to_string())
+}
+
+pub fn shopversions_command() -> CreateCommand {
+    CreateCommand::new("shopversions")
+        .description("ประวัติการแก้ไขร้านค้า (Admin)")
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "list", "ดูเวอร์ชันล่าสุดของร้านค้า")
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::Integer, "limit", "จำนวนรายการ (สูงสุด 25)")
+                        .min_int_value(1)
+                        .max_int_value(25)
+                )
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "diff", "ดูสิ่งที่เปลี่ยนในเวอร์ชันนี้")
+                .add_sub_option(version_option())
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "rollback", "ย้อนร้านค้ากลับไปเป็นเวอร์ชันนี้")
+                .add_sub_option(version_option())
+        )
+}
+
+fn version_option() -> CreateCommandOption {
+    CreateCommandOption::new(CommandOptionType::Integer, "version", "เวอร์ชัน")
+        .required(true)
+        .min_int_value(1)
+        .set_autocomplete(true)
+}
+
+pub async fn handle_shopversions_command(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState) {
+    if !require_permission(ctx, command, shared_state, Permission::ManageShop).await {
+        return;
+    }
+
+    let options = command.data.options();
+    let (subcommand, sub_options) = match options.first() {
+        Some(ResolvedOption { name, value: ResolvedValue::SubCommand(sub_options), .. }) => (*name, sub_options),
+        _ => return,
+    };
+
+    match subcommand {
+        "list" => {
+            let limit = integer_option(sub_options, "limit").unwrap_or(10).clamp(1, 25) as u32;
+            handle_list(ctx, command, shared_state, limit).await;
+        },
+        "diff" | "rollback" => {
+            let version = match integer_option(sub_options, "version").map(|id| shared_state.db.get_shop_version(id)) {
+                Some(Ok(Some(version))) => version,
+                Some(Err(e)) => {
+                    reply(ctx, command, format!("❌ เกิดข้อผิดพลาด: {:?}", e)).await;
+                    return;
+                },
+                _ => {
+                    reply(ctx, command, "❌ ไม่พบเวอร์ชันนี้").await;
+                    return;
+                }
+            };
+            if subcommand == "diff" {
+                handle_diff(ctx, command, version).await;
+            } else {
+                handle_rollback(ctx, command, shared_state, version).await;
+            }
+        },
+        _ => {}
+    }
+}
+
+pub async fn handle_shopversions_autocomplete(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState) {
+    let query = command.data.autocomplete()
+        .map(|focused| focused.value.trim().to_lowercase())
+        .unwrap_or_default();
+
+    let mut response = CreateAutocompleteResponse::new();
+    let versions = shared_state.db.list_shop_versions(100).unwrap_or_default();
+    let choices = versions.iter()
+        .map(|version| (format!("#{} • {} • {}", version.id, version.created_at, version.summary), version.id))
+        .filter(|(label, _)| query.is_empty() || label.to_lowercase().contains(&query))
+        .take(25);
+    for (label, id) in choices {
+        response = response.add_int_choice(truncate(&label, 100), id);
+    }
+
+    let _ = command.create_response(&ctx.http, CreateInteractionResponse::Autocomplete(response)).await;
+}
+
+async fn handle_list(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState, limit: u32) {
+    let versions = match shared_state.db.list_shop_versions(limit) {
+        Ok(versions) => versions,
+        Err(e) => {
+            reply(ctx, command, format!("❌ เกิดข้อผิดพลาด: {:?}", e)).await;
+            return;
+        }
+    };
+    if versions.is_empty() {
+        reply(ctx, command, "ยังไม่มีประวัติการแก้ไขร้านค้า").await;
+        return;
+    }
+
+    let lines: Vec<String> = versions.iter()
+        .map(|version| format!(
+            "**#{}** {} • {} • {}\n└ {}",
+            version.id, discord_time(&version.created_at), author_label(&version.author), diff_stats(version), truncate(&version.summary, 150)
+        ))
+        .collect();
+
+    let embed = CreateEmbed::new()
+        .title("🗂️ ประวัติร้านค้า")
+        .color(0x9900cc)
+        .description(truncate(&lines.join("\n"), 4000))
+        .footer(CreateEmbedFooter::new("ดูรายละเอียดด้วย /shopversions diff • © powered by TimeSkip"));
+
+    let response = CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .embed(embed)
+            .ephemeral(true)
+    );
+    let _ = command.create_response(&ctx.http, response).await;
+}
+
+/// The diff inline when it fits in a message, otherwise as an attached `.diff` file.
+async fn handle_diff(ctx: &Context, command: &CommandInteraction, version: ShopVersion) {
+    let header = format!(
+        "**#{}** {} • {} • {}\n{}",
+        version.id, discord_time(&version.created_at), author_label(&version.author), diff_stats(&version), version.summary
+    );
+    if version.diff.is_empty() {
+        reply(ctx, command, format!("{}\nเวอร์ชันแรก ไม่มีเวอร์ชันก่อนหน้าให้เทียบ", header)).await;
+        return;
+    }
+
+    let inline = format!("{}\n```diff\n{}```", header, version.diff);
+    let message = if inline.chars().count() <= 2000 {
+        CreateInteractionResponseMessage::new().content(inline)
+    } else {
+        let attachment = CreateAttachment::bytes(version.diff.into_bytes(), format!("shop-v{}.diff", version.id));
+        CreateInteractionResponseMessage::new()
+            .content(truncate(&format!("{}\n📎 diff ยาวเกินไป ดูในไฟล์แนบ", header), 2000))
+            .add_file(attachment)
+    };
+    let _ = command.create_response(&ctx.http, CreateInteractionResponse::Message(message.ephemeral(true))).await;
+}
+
+/// Goes through the same path as `/shopedit`, so the rollback is validated, backed up, saved
+/// as a new version and synced to every shop channel.
+async fn handle_rollback(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState, version: ShopVersion) {
+    let target = match parse_shop_file(&version.content, ShopFormat::Json) {
+        Ok(target) => target,
+        Err(e) => {
+            reply(ctx, command, format!("❌ อ่านเวอร์ชัน #{} ไม่ได้: {}", version.id, e)).await;
+            return;
+        }
+    };
+    if serialize_shop_file(&shared_state.shop_file(), ShopFormat::Json).is_ok_and(|current| current == version.content) {
+        reply(ctx, command, format!("ร้านค้าเป็นเวอร์ชัน #{} อยู่แล้ว", version.id)).await;
+        return;
+    }
+
+    run_command_edit(ctx, command, shared_state, |file| {
+        *file = target;
+        Ok(Change { channel: None, description: format!("ย้อนร้านค้ากลับไปเป็นเวอร์ชัน #{}", version.id) })
+    }).await;
+}
+
+fn author_label(author: &str) -> String {
+    if author == FILE_AUTHOR {
+        "แก้ไขในไฟล์".to_string()
+    } else {
+        format!("<@{}>", author)
+    }
+}
+
+fn diff_stats(version: &ShopVersion) -> String {
+    if version.diff.is_empty() {
+        return "เวอร์ชันแรก".to_string();
+    }
+    let added = version.diff.lines().filter(|line| line.starts_with('+') && !line.starts_with("+++")).count();
+    let removed = version.diff.lines().filter(|line| line.starts_with('-') && !line.starts_with("---")).count();
+    format!("+{} -{} บรรทัด", added, removed)
+}
@@ -0,0 +1,569 @@
+use serenity::builder::*;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use log::{error, info};
+
+use crate::commands::{reply, require_permission, string_option, truncate};
+use crate::config::{validate_shop_file, ButtonCommand, Delivery, ShopButton, ShopConfig, ShopFile, ShopItem};
+use crate::permissions::{has_permission, Permission};
+use crate::scum_items::{describe, find_unknown_items, ItemCatalog};
+use crate::shared_state::SharedState;
+use crate::shop_versions::record_version;
+use crate::shop::{sync_shop, sync_stock_rows};
+use crate::shopfile::{backup_shop_file, format_delivery, parse_delivery, write_shop_file};
+
+/// Splits a command line in the edit modals from its delivery: `#ChangeFamePoints 50 {steam_id} => none`.
+const DELIVERY_SEPARATOR: &str = "=>";
+
+const COMMANDS_PLACEHOLDER: &str = "#SpawnItem Weapon_AK47 {quantity}\n#ChangeFamePoints 50 {steam_id} => none\nkit:ak_set*2";
+
+/// Discord's limit for modal and button custom IDs.
+const MAX_CUSTOM_ID: usize = 100;
+
+/// What an edit did, for the reply and the version history, and which shop channel needs
+/// its messages updated (`None` for all of them).
+pub struct Change {
+    pub channel: Option<String>,
+    pub description: String,
+}
+
+pub fn shopedit_command() -> CreateCommand {
+    CreateCommand::new("shopedit")
+        .description("แก้ไขสินค้าในร้านค้า (Admin)")
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommandGroup, "item", "จัดการสินค้า")
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::SubCommand, "add", "เพิ่มสินค้าใหม่")
+                        .add_sub_option(
+                            CreateCommandOption::new(CommandOptionType::String, "shop", "ร้านที่จะเพิ่มสินค้า")
+                                .required(true)
+                                .set_autocomplete(true)
+                        )
+                )
+                .add_sub_option(item_subcommand("edit", "แก้ไขชื่อ ราคา รูป หมวดหมู่ และคำสั่งของสินค้า"))
+                .add_sub_option(item_subcommand("disable", "ซ่อนหรือเปิดขายสินค้า").add_sub_option(disabled_option()))
+                .add_sub_option(item_subcommand("delete", "ลบสินค้าออกจากร้าน"))
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommandGroup, "button", "จัดการปุ่มซื้อ")
+                .add_sub_option(item_subcommand("add", "เพิ่มปุ่มซื้อให้สินค้า"))
+                .add_sub_option(button_subcommand("edit", "แก้ไขข้อความ จำนวน และคำสั่งของปุ่ม"))
+                .add_sub_option(button_subcommand("disable", "ซ่อนหรือเปิดใช้ปุ่ม").add_sub_option(disabled_option()))
+                .add_sub_option(button_subcommand("delete", "ลบปุ่มซื้อ"))
+        )
+}
+
+fn item_subcommand(name: &str, description: &str) -> CreateCommandOption {
+    CreateCommandOption::new(CommandOptionType::SubCommand, name, description)
+        .add_sub_option(
+            CreateCommandOption::new(CommandOptionType::String, "item", "สินค้า")
+                .required(true)
+                .set_autocomplete(true)
+        )
+}
+
+fn button_subcommand(name: &str, description: &str) -> CreateCommandOption {
+    CreateCommandOption::new(CommandOptionType::SubCommand, name, description)
+        .add_sub_option(
+            CreateCommandOption::new(CommandOptionType::String, "button", "ปุ่มซื้อ")
+                .required(true)
+                .set_autocomplete(true)
+        )
+}
+
+fn disabled_option() -> CreateCommandOption {
+    CreateCommandOption::new(CommandOptionType::Boolean, "disabled", "True = ซ่อน, False = เปิดขาย")
+        .required(true)
+}
+
+pub async fn handle_shopedit_command(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState) {
+    if !require_permission(ctx, command, shared_state, Permission::ManageShop).await {
+        return;
+    }
+
+    let options = command.data.options();
+    let (group, action, sub_options) = match options.first() {
+        Some(ResolvedOption { name: group, value: ResolvedValue::SubCommandGroup(actions), .. }) => match actions.first() {
+            Some(ResolvedOption { name: action, value: ResolvedValue::SubCommand(sub_options), .. }) => (*group, *action, sub_options),
+            _ => return,
+        },
+        _ => return,
+    };
+    let item = string_option(sub_options, "item").unwrap_or("");
+    let trigger = string_option(sub_options, "button").unwrap_or("");
+    let disabled = sub_options.iter().any(|option| {
+        option.name == "disabled" && matches!(option.value, ResolvedValue::Boolean(true))
+    });
+    let shop_file = shared_state.shop_file();
+
+    match (group, action) {
+        ("item", "add") => {
+            let channel = string_option(sub_options, "shop").unwrap_or("");
+            if !shop_file.shops.iter().any(|shop| shop.channel == channel) {
+                reply(ctx, command, "❌ ไม่พบร้านนี้ กรุณาเลือกจากรายการ").await;
+                return;
+            }
+            open_modal(ctx, command, item_modal(format!("shopedit_item_add:{}", channel), "เพิ่มสินค้า", None)).await;
+        },
+        ("item", "edit") | ("button", "add") => {
+            let found = locate_item(&shop_file, item).map(|(shop, index)| &shop_file.shops[shop].items[index]);
+            let found = match found {
+                Some(found) => found,
+                None => {
+                    reply(ctx, command, "❌ ไม่พบสินค้านี้ กรุณาเลือกจากรายการ").await;
+                    return;
+                }
+            };
+            let modal = if group == "item" {
+                item_modal(format!("shopedit_item_edit:{}", item), &format!("แก้ไข {}", found.name), Some(found))
+            } else {
+                button_modal(format!("shopedit_button_add:{}", item), &format!("เพิ่มปุ่ม {}", found.name), None)
+            };
+            open_modal(ctx, command, modal).await;
+        },
+        ("button", "edit") => {
+            let found = match locate_button(&shop_file, trigger) {
+                Some((shop, item, button)) => &shop_file.shops[shop].items[item].buttons[button],
+                None => {
+                    reply(ctx, command, "❌ ไม่พบปุ่มนี้ กรุณาเลือกจากรายการ").await;
+                    return;
+                }
+            };
+            let custom_id = format!("shopedit_button_edit:{}", trigger);
+            if custom_id.chars().count() > MAX_CUSTOM_ID {
+                reply(ctx, command, "❌ trigger ของปุ่มนี้ยาวเกินไป กรุณาแก้ไขในไฟล์ร้านค้าแทน").await;
+                return;
+            }
+            open_modal(ctx, command, button_modal(custom_id, &format!("แก้ไขปุ่ม {}", found.text), Some(found))).await;
+        },
+        ("item", "disable") => {
+            run_command_edit(ctx, command, shared_state, |file| {
+                let (shop, index) = locate_item(file, item).ok_or("ไม่พบสินค้านี้")?;
+                let shop = &mut file.shops[shop];
+                let target = &mut shop.items[index];
+                target.disabled = disabled;
+                let state = if disabled { "ซ่อน" } else { "เปิดขาย" };
+                Ok(Change { channel: Some(shop.channel.clone()), description: format!("{}สินค้า {} แล้ว", state, target.name) })
+            }).await;
+        },
+        ("item", "delete") => {
+            run_command_edit(ctx, command, shared_state, |file| {
+                let (shop, index) = locate_item(file, item).ok_or("ไม่พบสินค้านี้")?;
+                let shop = &mut file.shops[shop];
+                let removed = shop.items.remove(index);
+                Ok(Change { channel: Some(shop.channel.clone()), description: format!("ลบสินค้า {} แล้ว", removed.name) })
+            }).await;
+        },
+        ("button", "disable") => {
+            run_command_edit(ctx, command, shared_state, |file| {
+                let (shop, item, button) = locate_button(file, trigger).ok_or("ไม่พบปุ่มนี้")?;
+                let shop = &mut file.shops[shop];
+                let item = &mut shop.items[item];
+                let target = &mut item.buttons[button];
+                target.disabled = disabled;
+                let state = if disabled { "ซ่อน" } else { "เปิดใช้" };
+                Ok(Change { channel: Some(shop.channel.clone()), description: format!("{}ปุ่ม {} ของ {} แล้ว", state, target.text, item.name) })
+            }).await;
+        },
+        ("button", "delete") => {
+            run_command_edit(ctx, command, shared_state, |file| {
+                let (shop, item, button) = locate_button(file, trigger).ok_or("ไม่พบปุ่มนี้")?;
+                let shop = &mut file.shops[shop];
+                let item = &mut shop.items[item];
+                let removed = item.buttons.remove(button);
+                Ok(Change { channel: Some(shop.channel.clone()), description: format!("ลบปุ่ม {} ของ {} แล้ว", removed.text, item.name) })
+            }).await;
+        },
+        _ => {}
+    }
+}
+
+pub async fn handle_shopedit_autocomplete(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState) {
+    let focused = match command.data.autocomplete() {
+        Some(focused) => focused,
+        None => return,
+    };
+    let query = focused.value.to_lowercase();
+    let is_match = |label: &str| query.is_empty() || label.to_lowercase().contains(&query);
+    let shop_file = shared_state.shop_file();
+
+    let choices: Vec<(String, String)> = match focused.name {
+        "shop" => shop_file.shops.iter()
+            .enumerate()
+            .map(|(index, shop)| {
+                let first_item = shop.items.first().map(|item| item.name.as_str()).unwrap_or("-");
+                (format!("ร้าน {} ({} รายการ) เช่น {}", index + 1, shop.items.len(), first_item), shop.channel.clone())
+            })
+            .filter(|(label, _)| is_match(label))
+            .take(25)
+            .collect(),
+        "item" => shop_file.shops.iter()
+            .flat_map(|shop| shop.items.iter().map(move |item| (shop, item)))
+            .map(|(shop, item)| {
+                let hidden = if item.disabled { " (ซ่อน)" } else { "" };
+                (format!("{}{} — {} coin", item.name, hidden, item.price), item_key(&shop.channel, item))
+            })
+            .filter(|(label, _)| is_match(label))
+            .take(25)
+            .collect(),
+        "button" => shop_file.shops.iter()
+            .flat_map(|shop| &shop.items)
+            .flat_map(|item| item.buttons.iter().map(move |button| (item, button)))
+            .map(|(item, button)| {
+                let hidden = if button.disabled { " (ซ่อน)" } else { "" };
+                (format!("{} ({}){} [{}]", item.name, button.text, hidden, button.trigger), button.trigger.clone())
+            })
+            .filter(|(label, _)| is_match(label))
+            .take(25)
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    let mut response = CreateAutocompleteResponse::new();
+    for (label, value) in choices {
+        response = response.add_string_choice(truncate(&label, 100), value);
+    }
+    let _ = command.create_response(&ctx.http, CreateInteractionResponse::Autocomplete(response)).await;
+}
+
+/// Submitted add/edit forms. The permission is checked again since a modal outlives the
+/// command that opened it.
+pub async fn handle_shopedit_modal(ctx: &Context, modal: &ModalInteraction, shared_state: &SharedState) {
+    if !has_permission(shared_state, modal.user.id, modal.member.as_ref(), Permission::ManageShop) {
+        let response = CreateInteractionResponseMessage::new()
+            .content(format!("❌ คุณไม่มีสิทธิ์ใช้คำสั่งนี้ (ต้องการสิทธิ์: {})", Permission::ManageShop.label()))
+            .ephemeral(true);
+        let _ = modal.create_response(&ctx.http, CreateInteractionResponse::Message(response)).await;
+        return;
+    }
+
+    let _ = modal.defer_ephemeral(&ctx.http).await;
+
+    let values = modal_values(modal);
+    let custom_id = modal.data.custom_id.as_str();
+    let result = if let Some(channel) = custom_id.strip_prefix("shopedit_item_add:") {
+        apply_edit(ctx, shared_state, modal.user.id, |file| {
+            let shop = file.shops.iter_mut().find(|shop| shop.channel == channel).ok_or("ไม่พบร้านนี้แล้ว")?;
+            let mut item = ShopItem {
+                name: String::new(),
+                price: 0,
+                image: None,
+                category: None,
+                commands: Vec::new(),
+                buttons: Vec::new(),
+                disabled: false,
+                loot: None,
+            };
+            read_item_fields(&mut item, &values)?;
+            if shop.items.iter().any(|other| other.name == item.name) {
+                return Err(format!("มีสินค้าชื่อ {} ในร้านนี้แล้ว", item.name));
+            }
+            let description = format!("เพิ่มสินค้า {} แล้ว สินค้าจะแสดงในร้านเมื่อเพิ่มปุ่มซื้อด้วย /shopedit button add", item.name);
+            shop.items.push(item);
+            Ok(Change { channel: Some(channel.to_string()), description })
+        }).await
+    } else if let Some(key) = custom_id.strip_prefix("shopedit_item_edit:") {
+        apply_edit(ctx, shared_state, modal.user.id, |file| {
+            let (shop, index) = locate_item(file, key).ok_or("ไม่พบสินค้านี้แล้ว อาจถูกแก้ไขไปก่อนหน้า")?;
+            let shop = &mut file.shops[shop];
+            let item = &mut shop.items[index];
+            read_item_fields(item, &values)?;
+            let name = item.name.clone();
+            if shop.items.iter().filter(|other| other.name == name).count() > 1 {
+                return Err(format!("มีสินค้าชื่อ {} ในร้านนี้แล้ว", name));
+            }
+            Ok(Change { channel: Some(shop.channel.clone()), description: format!("แก้ไขสินค้า {} แล้ว", name) })
+        }).await
+    } else if let Some(key) = custom_id.strip_prefix("shopedit_button_add:") {
+        apply_edit(ctx, shared_state, modal.user.id, |file| {
+            let (shop, index) = locate_item(file, key).ok_or("ไม่พบสินค้านี้แล้ว อาจถูกแก้ไขไปก่อนหน้า")?;
+            let shop = &mut file.shops[shop];
+            let item = &mut shop.items[index];
+            let mut button = ShopButton {
+                text: String::new(),
+                trigger: String::new(),
+                commands: Vec::new(),
+                resolved_commands: Vec::new(),
+                quantity: 1,
+                cooldown_seconds: None,
+                daily_limit: None,
+                weekly_limit: None,
+                stock: None,
+                restock_hours: None,
+                disabled: false,
+            };
+            read_button_fields(&mut button, &values)?;
+            let description = format!("เพิ่มปุ่ม {} ให้ {} แล้ว", button.text, item.name);
+            item.buttons.push(button);
+            Ok(Change { channel: Some(shop.channel.clone()), description })
+        }).await
+    } else if let Some(trigger) = custom_id.strip_prefix("shopedit_button_edit:") {
+        apply_edit(ctx, shared_state, modal.user.id, |file| {
+            let (shop, item, button) = locate_button(file, trigger).ok_or("ไม่พบปุ่มนี้แล้ว อาจถูกแก้ไขไปก่อนหน้า")?;
+            let shop = &mut file.shops[shop];
+            let item = &mut shop.items[item];
+            let button = &mut item.buttons[button];
+            read_button_fields(button, &values)?;
+            Ok(Change { channel: Some(shop.channel.clone()), description: format!("แก้ไขปุ่ม {} ของ {} แล้ว", button.text, item.name) })
+        }).await
+    } else {
+        return;
+    };
+
+    let content = match result {
+        Ok(message) | Err(message) => message,
+    };
+    let _ = modal.edit_response(&ctx.http, EditInteractionResponse::new().content(truncate(&content, 2000))).await;
+}
+
+async fn open_modal(ctx: &Context, command: &CommandInteraction, modal: CreateModal) {
+    let _ = command.create_response(&ctx.http, CreateInteractionResponse::Modal(modal)).await;
+}
+
+pub async fn run_command_edit(
+    ctx: &Context,
+    command: &CommandInteraction,
+    shared_state: &SharedState,
+    change: impl FnOnce(&mut ShopFile) -> Result<Change, String>,
+) {
+    let _ = command.defer_ephemeral(&ctx.http).await;
+    let content = match apply_edit(ctx, shared_state, command.user.id, change).await {
+        Ok(message) | Err(message) => message,
+    };
+    let _ = command.edit_response(&ctx.http, EditInteractionResponse::new().content(truncate(&content, 2000))).await;
+}
+
+/// Applies `change` to a copy of the live shops and validates it like a restart would. Only
+/// then is the shop file backed up and rewritten, the result saved as a version, the copy
+/// swapped in and the affected shop channels updated.
+pub async fn apply_edit(
+    ctx: &Context,
+    shared_state: &SharedState,
+    user_id: UserId,
+    change: impl FnOnce(&mut ShopFile) -> Result<Change, String>,
+) -> Result<String, String> {
+    let _guard = shared_state.shop_edit_lock.lock().await;
+
+    let previous = shared_state.shop_file();
+    let mut file = (*previous).clone();
+    let change = change(&mut file).map_err(|e| format!("❌ {}", e))?;
+    validate_shop_file(&mut file, &shared_state.config.locations).map_err(|e| format!("❌ บันทึกไม่ได้: {}", e))?;
+
+    let shop_path = shared_state.config.shop_path;
+    let backup = backup_shop_file(shop_path).map_err(|e| format!("❌ {}", e))?;
+    write_shop_file(shop_path, &file).map_err(|e| format!("❌ {}", e))?;
+    info!("Shop edit by {}: {} (backup: {})", user_id, change.description, backup);
+
+    if let Err(e) = record_version(&shared_state.db, &user_id.to_string(), &change.description, &file) {
+        error!("Failed to save shop version: {}", e);
+    }
+
+    shared_state.replace_shop_file(file);
+    sync_stock_rows(shared_state);
+
+    let mut lines = vec![
+        format!("✅ {}", change.description),
+        format!("💾 สำรองไฟล์เดิมไว้ที่ {}", backup),
+    ];
+
+    // A rollback can drop whole shops; syncing them without items removes their messages.
+    let shop_file = shared_state.shop_file();
+    let removed: Vec<ShopConfig> = previous.shops.iter()
+        .filter(|old| !shop_file.shops.iter().any(|shop| shop.channel == old.channel))
+        .map(|old| ShopConfig {
+            channel: old.channel.clone(),
+            layout: old.layout,
+            title: None,
+            fallback_image: None,
+            items: Vec::new(),
+        })
+        .collect();
+    let targets = shop_file.shops.iter()
+        .chain(&removed)
+        .filter(|shop| change.channel.is_none() || change.channel.as_deref() == Some(shop.channel.as_str()));
+
+    let catalog = ItemCatalog::load().ok();
+    let bot_id = ctx.cache.current_user().id;
+    for shop in targets {
+        let summary = sync_shop(&ctx.http, shared_state, shop, bot_id, false).await;
+        lines.push(format!(
+            "🔄 อัปเดตร้าน <#{}>: เพิ่ม {} • แก้ไข {} • ลบ {}",
+            shop.channel, summary.added.len(), summary.edited.len(), summary.removed.len()
+        ));
+        if !summary.failed.is_empty() {
+            lines.push(format!("❌ ล้มเหลว: {}", summary.failed.join(", ")));
+        }
+
+        if let Some(catalog) = &catalog {
+            for unknown in find_unknown_items(catalog, std::slice::from_ref(shop), &[]) {
+                lines.push(format!("⚠️ {}", describe(&unknown)));
+            }
+        }
+    }
+
+    Ok(lines.join("\n"))
+}
+
+/// Autocomplete value and modal reference for an item: `<channel>:<hash of name>`. Item
+/// names can be too long for a custom ID, and the hash stops a form from editing whatever
+/// item took the old one's place.
+fn item_key(channel: &str, item: &ShopItem) -> String {
+    let mut hasher = DefaultHasher::new();
+    item.name.hash(&mut hasher);
+    format!("{}:{:016x}", channel, hasher.finish())
+}
+
+fn locate_item(file: &ShopFile, key: &str) -> Option<(usize, usize)> {
+    file.shops.iter().enumerate().find_map(|(shop_index, shop)| {
+        shop.items.iter()
+            .position(|item| item_key(&shop.channel, item) == key)
+            .map(|item_index| (shop_index, item_index))
+    })
+}
+
+fn locate_button(file: &ShopFile, trigger: &str) -> Option<(usize, usize, usize)> {
+    for (shop_index, shop) in file.shops.iter().enumerate() {
+        for (item_index, item) in shop.items.iter().enumerate() {
+            if let Some(button_index) = item.buttons.iter().position(|button| button.trigger == trigger) {
+                return Some((shop_index, item_index, button_index));
+            }
+        }
+    }
+    None
+}
+
+fn item_modal(custom_id: String, title: &str, item: Option<&ShopItem>) -> CreateModal {
+    let value = |get: fn(&ShopItem) -> String| item.map(get).unwrap_or_default();
+    CreateModal::new(custom_id, truncate(title, 45)).components(vec![
+        input(InputTextStyle::Short, "ชื่อสินค้า", "name", value(|item| item.name.clone()), true),
+        input(InputTextStyle::Short, "ราคา (coin)", "price", value(|item| item.price.to_string()), true),
+        input(InputTextStyle::Short, "รูปภาพ (path หรือ URL)", "image", value(|item| item.image.clone().unwrap_or_default()), false),
+        input(InputTextStyle::Short, "หมวดหมู่", "category", value(|item| item.category.clone().unwrap_or_default()), false),
+        commands_input("คำสั่งที่ใช้ร่วมกันทุกปุ่ม (บรรทัดละคำสั่ง)", value(|item| format_commands(&item.commands))),
+    ])
+}
+
+fn button_modal(custom_id: String, title: &str, button: Option<&ShopButton>) -> CreateModal {
+    let value = |get: fn(&ShopButton) -> String| button.map(get).unwrap_or_default();
+    CreateModal::new(custom_id, truncate(title, 45)).components(vec![
+        input(InputTextStyle::Short, "ข้อความบนปุ่ม", "text", value(|button| button.text.clone()), true),
+        input(InputTextStyle::Short, "Trigger (ห้ามซ้ำกับปุ่มอื่น)", "trigger", value(|button| button.trigger.clone()), true),
+        input(InputTextStyle::Short, "จำนวน ({quantity})", "quantity", button.map(|button| button.quantity).unwrap_or(1).to_string(), true),
+        input(InputTextStyle::Short, "Cooldown วินาที (ว่าง = ค่าเริ่มต้น)", "cooldown", value(|button| button.cooldown_seconds.map(|seconds| seconds.to_string()).unwrap_or_default()), false),
+        commands_input("คำสั่ง (ว่าง = ใช้คำสั่งของสินค้า)", value(|button| format_commands(&button.commands))),
+    ])
+}
+
+fn input(style: InputTextStyle, label: &str, custom_id: &str, value: String, required: bool) -> CreateActionRow {
+    CreateActionRow::InputText(input_text(style, label, custom_id, value, required))
+}
+
+fn commands_input(label: &str, value: String) -> CreateActionRow {
+    let input = input_text(InputTextStyle::Paragraph, label, "commands", value, false)
+        .placeholder(COMMANDS_PLACEHOLDER);
+    CreateActionRow::InputText(input)
+}
+
+fn input_text(style: InputTextStyle, label: &str, custom_id: &str, value: String, required: bool) -> CreateInputText {
+    let input = CreateInputText::new(style, label, custom_id).required(required);
+    if value.is_empty() {
+        input
+    } else {
+        input.value(value)
+    }
+}
+
+fn modal_values(modal: &ModalInteraction) -> HashMap<&str, &str> {
+    modal.data.components.iter()
+        .flat_map(|row| &row.components)
+        .filter_map(|component| match component {
+            ActionRowComponent::InputText(input) => Some((input.custom_id.as_str(), input.value.as_deref().unwrap_or("").trim())),
+            _ => None,
+        })
+        .collect()
+}
+
+fn read_item_fields(item: &mut ShopItem, values: &HashMap<&str, &str>) -> Result<(), String> {
+    let field = |name: &str| values.get(name).copied().unwrap_or("");
+    if field("name").is_empty() {
+        return Err("กรุณาใส่ชื่อสินค้า".to_string());
+    }
+    item.name = field("name").to_string();
+    item.price = parse_number(field("price"), "ราคา")?;
+    item.image = Some(field("image").to_string()).filter(|image| !image.is_empty());
+    item.category = Some(field("category").to_string()).filter(|category| !category.is_empty());
+    item.commands = parse_commands(field("commands"))?;
+    Ok(())
+}
+
+fn read_button_fields(button: &mut ShopButton, values: &HashMap<&str, &str>) -> Result<(), String> {
+    let field = |name: &str| values.get(name).copied().unwrap_or("");
+    if field("text").is_empty() || field("trigger").is_empty() {
+        return Err("กรุณาใส่ข้อความบนปุ่มและ trigger".to_string());
+    }
+    if field("trigger").chars().count() > MAX_CUSTOM_ID {
+        return Err(format!("trigger ยาวได้ไม่เกิน {} ตัวอักษร", MAX_CUSTOM_ID));
+    }
+    button.text = field("text").to_string();
+    button.trigger = field("trigger").to_string();
+    button.quantity = parse_number(field("quantity"), "จำนวน")?;
+    if button.quantity == 0 {
+        return Err("จำนวนต้องมากกว่า 0".to_string());
+    }
+    button.cooldown_seconds = match field("cooldown") {
+        "" => None,
+        seconds => Some(parse_number(seconds, "Cooldown")?),
+    };
+    button.commands = parse_commands(field("commands"))?;
+    Ok(())
+}
+
+fn parse_number<T: FromStr>(text: &str, label: &str) -> Result<T, String> {
+    text.parse().map_err(|_| format!("{} ต้องเป็นตัวเลข: '{}'", label, text))
+}
+
+/// One command per line: a plain command, `command => <delivery>` with the deliveries of
+/// `import-csv`, or `kit:<name>` / `kit:<name>*<count>`.
+fn parse_commands(text: &str) -> Result<Vec<ButtonCommand>, String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| parse_command(line).map_err(|e| format!("'{}': {}", line, e)))
+        .collect()
+}
+
+fn parse_command(line: &str) -> Result<ButtonCommand, String> {
+    if let Some(kit) = line.strip_prefix("kit:") {
+        let (kit, count) = match kit.split_once('*') {
+            Some((kit, count)) => (kit.trim(), parse_number(count.trim(), "จำนวน kit")?),
+            None => (kit.trim(), 1),
+        };
+        return Ok(ButtonCommand::Kit { kit: kit.to_string(), count });
+    }
+
+    let (command, delivery) = match line.rsplit_once(DELIVERY_SEPARATOR) {
+        Some((command, delivery)) => (command.trim(), parse_delivery(delivery)?),
+        None => (line, Delivery::Player),
+    };
+    Ok(match delivery {
+        Delivery::Player => ButtonCommand::Plain(command.to_string()),
+        delivery => ButtonCommand::Targeted { command: command.to_string(), delivery },
+    })
+}
+
+fn format_commands(commands: &[ButtonCommand]) -> String {
+    commands.iter()
+        .map(|command| match command {
+            ButtonCommand::Plain(command) => command.clone(),
+            ButtonCommand::Targeted { command, delivery: Delivery::Player } => command.clone(),
+            ButtonCommand::Targeted { command, delivery } => format!("{} {} {}", command, DELIVERY_SEPARATOR, format_delivery(delivery)),
+            ButtonCommand::Kit { kit, count: 1 } => format!("kit:{}", kit),
+            ButtonCommand::Kit { kit, count } => format!("kit:{}*{}", kit, count),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
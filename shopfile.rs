@@ -0,0 +1,280 @@
+use serde::Deserialize;
+use std::path::Path;
+
+use crate::config::{ButtonCommand, Delivery, ShopButton, ShopConfig, ShopFile, ShopItem, ShopLayout};
+use crate::kits::Kits;
+
+/// Shop files the bot looks for, in this order. Only one may exist.
+pub const SHOP_FILES: [&str; 4] = ["botshop.json", "botshop.toml", "botshop.yaml", "botshop.yml"];
+
+/// Where the shop file is copied before `/shopedit` overwrites it.
+pub const SHOP_BACKUP_DIR: &str = "shop_backups";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShopFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl ShopFormat {
+    pub fn from_path(path: &str) -> Result<Self, String> {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_lowercase);
+        match extension.as_deref() {
+            Some("json") => Ok(ShopFormat::Json),
+            Some("toml") => Ok(ShopFormat::Toml),
+            Some("yaml") | Some("yml") => Ok(ShopFormat::Yaml),
+            _ => Err(format!("Unsupported shop file '{}' (expected .json, .toml, .yaml or .yml)", path)),
+        }
+    }
+}
+
+/// The shop file in the working directory.
+pub fn find_shop_file() -> Result<&'static str, String> {
+    let found: Vec<&str> = SHOP_FILES.iter().copied().filter(|path| Path::new(path).exists()).collect();
+    match found.as_slice() {
+        [] => Err(format!("No shop file found! Expected one of: {}", SHOP_FILES.join(", "))),
+        [path] => Ok(*path),
+        _ => Err(format!("Found several shop files ({}), keep only one", found.join(", "))),
+    }
+}
+
+pub fn read_shop_file(path: &str) -> Result<ShopFile, String> {
+    let format = ShopFormat::from_path(path)?;
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    parse_shop_file(&text, format).map_err(|e| format!("Failed to parse {}: {}", path, e))
+}
+
+pub fn write_shop_file(path: &str, file: &ShopFile) -> Result<(), String> {
+    let format = ShopFormat::from_path(path)?;
+    let text = serialize_shop_file(file, format)?;
+    std::fs::write(path, text).map_err(|e| format!("Failed to write {}: {}", path, e))
+}
+
+/// Copies the shop file to `shop_backups/<name>-<timestamp>.<ext>` and returns the copy's path.
+pub fn backup_shop_file(path: &str) -> Result<String, String> {
+    let source = Path::new(path);
+    let stem = source.file_stem().and_then(|stem| stem.to_str()).unwrap_or("botshop");
+    let extension = source.extension().and_then(|extension| extension.to_str()).unwrap_or("json");
+    let timestamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+    let backup = Path::new(SHOP_BACKUP_DIR).join(format!("{}-{}.{}", stem, timestamp, extension));
+
+    std::fs::create_dir_all(SHOP_BACKUP_DIR).map_err(|e| format!("Failed to create {}: {}", SHOP_BACKUP_DIR, e))?;
+    std::fs::copy(source, &backup).map_err(|e| format!("Failed to back up {}: {}", path, e))?;
+    Ok(backup.display().to_string())
+}
+
+/// JSON and YAML files may be a plain list of shops; TOML needs the `shops`/`kits` form
+/// since its top level is always a table.
+pub fn parse_shop_file(text: &str, format: ShopFormat) -> Result<ShopFile, String> {
+    match format {
+        ShopFormat::Json => {
+            if text.trim_start().starts_with('[') {
+                serde_json::from_str(text).map(|shops| ShopFile { kits: Kits::new(), shops })
+            } else {
+                serde_json::from_str(text)
+            }
+            .map_err(|e| e.to_string())
+        },
+        ShopFormat::Toml => toml::from_str(text).map_err(|e| e.to_string()),
+        ShopFormat::Yaml => {
+            let value: serde_yaml::Value = serde_yaml::from_str(text).map_err(|e| e.to_string())?;
+            if value.is_sequence() {
+                serde_yaml::from_value(value).map(|shops| ShopFile { kits: Kits::new(), shops })
+            } else {
+                serde_yaml::from_value(value)
+            }
+            .map_err(|e| e.to_string())
+        },
+    }
+}
+
+/// JSON without kits is written as a plain list, like the original `botshop.json`.
+pub fn serialize_shop_file(file: &ShopFile, format: ShopFormat) -> Result<String, String> {
+    match format {
+        ShopFormat::Json if file.kits.is_empty() => serde_json::to_string_pretty(&file.shops).map_err(|e| e.to_string()),
+        ShopFormat::Json => serde_json::to_string_pretty(file).map_err(|e| e.to_string()),
+        ShopFormat::Toml => toml::to_string_pretty(file).map_err(|e| e.to_string()),
+        // Through a JSON value, so enums come out as `coords: [...]` maps rather than YAML
+        // `!coords` tags, which the untagged `ButtonCommand` can't read back.
+        ShopFormat::Yaml => {
+            let value = serde_json::to_value(file).map_err(|e| e.to_string())?;
+            serde_yaml::to_string(&value).map_err(|e| e.to_string())
+        },
+    }
+}
+
+/// `convert <input> <output>` and `import-csv <input.csv> <output>`. `None` when `args` isn't
+/// a tool invocation and the bot should start normally.
+pub fn run_tool(args: &[String]) -> Option<Result<String, String>> {
+    let result = match args.get(1).map(String::as_str) {
+        Some("convert") => match (args.get(2), args.get(3)) {
+            (Some(input), Some(output)) => convert(input, output),
+            _ => Err("Usage: convert <input> <output>".to_string()),
+        },
+        Some("import-csv") => match (args.get(2), args.get(3)) {
+            (Some(input), Some(output)) => import_csv(input, output),
+            _ => Err("Usage: import-csv <input.csv> <output>".to_string()),
+        },
+        _ => return None,
+    };
+    Some(result)
+}
+
+fn convert(input: &str, output: &str) -> Result<String, String> {
+    let file = read_shop_file(input)?;
+    write_shop_file(output, &file)?;
+    Ok(format!("Converted {} shops and {} kits from {} to {}", file.shops.len(), file.kits.len(), input, output))
+}
+
+/// One spreadsheet row per button. Item columns (`price`, `image`, `category`) are taken from
+/// the first row of each item.
+#[derive(Debug, Deserialize)]
+struct CsvRow {
+    channel: String,
+    item: String,
+    price: u32,
+    #[serde(default)]
+    image: Option<String>,
+    #[serde(default)]
+    category: Option<String>,
+    button: String,
+    trigger: String,
+    #[serde(default)]
+    quantity: Option<u32>,
+    /// One command per line, or separated by `;`.
+    commands: String,
+    /// `player` (default), `none`, `preset:<name>` or `coords:<x>,<y>,<z>`.
+    #[serde(default)]
+    delivery: Option<String>,
+    #[serde(default)]
+    cooldown_seconds: Option<u64>,
+    #[serde(default)]
+    daily_limit: Option<u32>,
+    #[serde(default)]
+    weekly_limit: Option<u32>,
+    #[serde(default)]
+    stock: Option<u32>,
+    #[serde(default)]
+    restock_hours: Option<u64>,
+}
+
+fn import_csv(input: &str, output: &str) -> Result<String, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_path(input)
+        .map_err(|e| format!("Failed to read {}: {}", input, e))?;
+
+    let mut shops: Vec<ShopConfig> = Vec::new();
+    let mut buttons = 0;
+    for (index, row) in reader.deserialize::<CsvRow>().enumerate() {
+        // Row 1 is the header.
+        let line = index + 2;
+        let row = row.map_err(|e| format!("Row {}: {}", line, e))?;
+        let button = csv_button(&row).map_err(|e| format!("Row {}: {}", line, e))?;
+
+        let shop_index = match shops.iter().position(|shop| shop.channel == row.channel) {
+            Some(position) => position,
+            None => {
+                shops.push(ShopConfig {
+                    channel: row.channel.clone(),
+                    layout: ShopLayout::default(),
+                    title: None,
+                    fallback_image: None,
+                    items: Vec::new(),
+                });
+                shops.len() - 1
+            }
+        };
+        let shop = &mut shops[shop_index];
+
+        match shop.items.iter_mut().find(|item| item.name == row.item) {
+            Some(item) if item.price != row.price => {
+                return Err(format!("Row {}: '{}' has price {} but an earlier row says {}", line, row.item, row.price, item.price));
+            },
+            Some(item) => item.buttons.push(button),
+            None => shop.items.push(ShopItem {
+                name: row.item.clone(),
+                price: row.price,
+                image: row.image.clone().filter(|image| !image.is_empty()),
+                category: row.category.clone().filter(|category| !category.is_empty()),
+                commands: Vec::new(),
+                buttons: vec![button],
+                disabled: false,
+                loot: None,
+            }),
+        }
+        buttons += 1;
+    }
+
+    let file = ShopFile { kits: Kits::new(), shops };
+    write_shop_file(output, &file)?;
+    Ok(format!("Imported {} buttons into {} shops, written to {}", buttons, file.shops.len(), output))
+}
+
+fn csv_button(row: &CsvRow) -> Result<ShopButton, String> {
+    let delivery = parse_delivery(row.delivery.as_deref().unwrap_or(""))?;
+    let commands: Vec<ButtonCommand> = row.commands
+        .split(['\n', ';'])
+        .map(str::trim)
+        .filter(|command| !command.is_empty())
+        .map(|command| match &delivery {
+            Delivery::Player => ButtonCommand::Plain(command.to_string()),
+            _ => ButtonCommand::Targeted { command: command.to_string(), delivery: delivery.clone() },
+        })
+        .collect();
+    if commands.is_empty() {
+        return Err(format!("button '{}' has no commands", row.trigger));
+    }
+
+    Ok(ShopButton {
+        text: row.button.clone(),
+        trigger: row.trigger.clone(),
+        commands,
+        resolved_commands: Vec::new(),
+        quantity: row.quantity.unwrap_or(1),
+        cooldown_seconds: row.cooldown_seconds,
+        daily_limit: row.daily_limit,
+        weekly_limit: row.weekly_limit,
+        stock: row.stock,
+        restock_hours: row.restock_hours,
+        disabled: false,
+    })
+}
+
+pub fn parse_delivery(text: &str) -> Result<Delivery, String> {
+    let text = text.trim();
+    if text.is_empty() || text.eq_ignore_ascii_case("player") {
+        return Ok(Delivery::Player);
+    }
+    if text.eq_ignore_ascii_case("none") {
+        return Ok(Delivery::None);
+    }
+    if let Some(name) = text.strip_prefix("preset:") {
+        return Ok(Delivery::Preset(name.trim().to_string()));
+    }
+    if let Some(coords) = text.strip_prefix("coords:") {
+        let values: Vec<f64> = coords.split(',')
+            .map(|value| value.trim().parse::<f64>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("invalid coords '{}'", coords))?;
+        if let [x, y, z] = values.as_slice() {
+            return Ok(Delivery::Coords([*x, *y, *z]));
+        }
+        return Err(format!("coords need x,y,z: '{}'", coords));
+    }
+    Err(format!("unknown delivery '{}' (player, none, preset:<name> or coords:<x>,<y>,<z>)", text))
+}
+
+/// The inverse of `parse_delivery`.
+pub fn format_delivery(delivery: &Delivery) -> String {
+    match delivery {
+        Delivery::Player => "player".to_string(),
+        Delivery::None => "none".to_string(),
+        Delivery::Preset(name) => format!("preset:{}", name),
+        Delivery::Coords([x, y, z]) => format!("coords:{},{},{}", x, y, z),
+    }
+}
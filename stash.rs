@@ -0,0 +1,302 @@
+use chrono::{Duration as ChronoDuration, NaiveDateTime, Utc};
+use serenity::builder::*;
+use serenity::http::Http;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use std::sync::Arc;
+use log::{error, info};
+use tokio::time::{sleep, Duration};
+
+use crate::commands::{integer_option, reply, truncate};
+use crate::config::{ButtonCommand, ShopButton, ShopConfig, ShopItem};
+use crate::database::{StashItem, DB_TIME_FORMAT};
+use crate::loot::{append_draws, delivery_commands};
+use crate::purchase::{find_button, template_names};
+use crate::shared_state::SharedState;
+use crate::template::TemplateContext;
+
+const STASH_EXPIRY_CHECK_SECS: u64 = 600;
+
+pub fn stash_command() -> CreateCommand {
+    CreateCommand::new("stash")
+        .description("คลังเก็บของที่ซื้อไว้")
+        .add_option(CreateCommandOption::new(CommandOptionType::SubCommand, "view", "ดูของในคลัง"))
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "mode", "เปิด/ปิดโหมดคลัง (ของที่ซื้อจะเก็บไว้จนกว่าจะ /claim)")
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::Boolean, "enabled", "เปิดโหมดคลัง")
+                        .required(true)
+                )
+        )
+}
+
+pub fn claim_command() -> CreateCommand {
+    CreateCommand::new("claim")
+        .description("รับของจากคลังเข้าเกม")
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::Integer, "id", "ของที่ต้องการรับ")
+                .required(true)
+                .set_autocomplete(true)
+        )
+}
+
+/// Expiry for a new stash entry, or `None` when `owner_id` hasn't turned stash mode on.
+pub fn stash_until(shared_state: &SharedState, owner_id: &str) -> Option<String> {
+    if !shared_state.db.get_stash_mode(owner_id).unwrap_or(false) {
+        return None;
+    }
+    let until = Utc::now() + ChronoDuration::days(shared_state.config.stash_expiry_days as i64);
+    Some(until.format(DB_TIME_FORMAT).to_string())
+}
+
+pub async fn handle_stash_command(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState) {
+    let options = command.data.options();
+    let (subcommand, sub_options) = match options.first() {
+        Some(ResolvedOption { name, value: ResolvedValue::SubCommand(sub_options), .. }) => (*name, sub_options),
+        _ => return,
+    };
+
+    let user_id = command.user.id.to_string();
+
+    match subcommand {
+        "view" => {
+            let items = shared_state.db.get_stash_items(&user_id).unwrap_or_default();
+            if items.is_empty() {
+                reply(ctx, command, "📦 คลังของคุณว่างอยู่").await;
+                return;
+            }
+
+            let lines: Vec<String> = items.iter()
+                .map(|item| format!(
+                    "`#{}` **{}** — ซื้อเมื่อ {} • หมดอายุ {}",
+                    item.id, stash_label(shared_state, item), discord_time(&item.created_at), discord_time(&item.expires_at)
+                ))
+                .collect();
+            let embed = CreateEmbed::new()
+                .title("📦 คลังของคุณ")
+                .color(0x9900cc)
+                .description(truncate(&lines.join("\n"), 4000))
+                .footer(CreateEmbedFooter::new(format!(
+                    "ใช้ /claim เพื่อรับของเข้าเกม • ของที่หมดอายุจะคืน coin {}% • © powered by TimeSkip",
+                    shared_state.config.stash_expiry_refund_percent
+                )));
+            let response = CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .embed(embed)
+                    .ephemeral(true)
+            );
+            let _ = command.create_response(&ctx.http, response).await;
+        },
+        "mode" => {
+            let enabled = sub_options.iter().any(|option| {
+                option.name == "enabled" && matches!(option.value, ResolvedValue::Boolean(true))
+            });
+            match shared_state.db.set_stash_mode(&user_id, enabled) {
+                Ok(()) if enabled => reply(ctx, command, format!(
+                    "📦 เปิดโหมดคลังแล้ว ของที่ซื้อจะเก็บไว้ {} วัน ใช้ /claim เมื่อพร้อมรับของ",
+                    shared_state.config.stash_expiry_days
+                )).await,
+                Ok(()) => reply(ctx, command, "📦 ปิดโหมดคลังแล้ว ของที่ซื้อจะส่งเข้าเกมทันที").await,
+                Err(e) => reply(ctx, command, format!("❌ เกิดข้อผิดพลาด: {:?}", e)).await,
+            }
+        },
+        _ => {}
+    }
+}
+
+pub async fn handle_claim_command(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState) {
+    if !shared_state.is_bot_active().await {
+        reply(ctx, command, "❌ ระบบ BOTSHOP หยุดทำงานชั่วคราวเนื่องจาก SERVER กำลังจะ RESTART").await;
+        return;
+    }
+    if shared_state.destroy_lock.try_lock().is_err() {
+        reply(ctx, command, "❌ บอทกำลังลบขยะ กรุณาลองใหม่อีกครั้งภายหลัง").await;
+        return;
+    }
+
+    let user_id = command.user.id.to_string();
+    let player = match shared_state.db.get_player_by_discord_id(&user_id) {
+        Ok(Some(player)) => player,
+        _ => {
+            reply(ctx, command, "ไม่พบข้อมูลผู้เล่นของคุณในระบบ! กรุณาลงทะเบียนด้วย /register").await;
+            return;
+        }
+    };
+
+    let options = command.data.options();
+    let id = match integer_option(&options, "id") {
+        Some(id) => id,
+        None => return,
+    };
+
+    let item = match shared_state.db.claim_stash_item(&user_id, id) {
+        Ok(Some(item)) => item,
+        Ok(None) => {
+            reply(ctx, command, "❌ ไม่พบของชิ้นนี้ในคลัง (อาจรับไปแล้วหรือหมดอายุ)").await;
+            return;
+        },
+        Err(e) => {
+            reply(ctx, command, format!("❌ เกิดข้อผิดพลาด: {:?}", e)).await;
+            return;
+        }
+    };
+
+    let shop_file = shared_state.shop_file();
+    let found = find_button(&shop_file.shops, &item.trigger).or_else(|| stored_button(&shop_file.shops, &item));
+    let (shop_item, mut button) = match found {
+        Some(found) => found,
+        None => {
+            let content = match shared_state.db.refund_stash_item(item.id) {
+                Ok(()) if item.paid_by != user_id => format!(
+                    "❌ {} ไม่มีในร้านแล้ว คืน {} coins ให้ <@{}> ผู้ส่งของขวัญแล้ว",
+                    item.item_name, item.price, item.paid_by
+                ),
+                Ok(()) => format!("❌ {} ไม่มีในร้านแล้ว คืน {} coins ให้แล้ว", item.item_name, item.price),
+                Err(e) => format!("❌ เกิดข้อผิดพลาด: {:?}", e),
+            };
+            reply(ctx, command, content).await;
+            return;
+        }
+    };
+
+    // Deliver what was paid for even if the button was edited since. Loot boxes roll on the
+    // table as it is now.
+    if let Some(quantity) = item.quantity {
+        button.quantity = quantity;
+    }
+    if let Some(commands) = item.commands.as_deref().and_then(|json| serde_json::from_str::<Vec<ButtonCommand>>(json).ok()) {
+        button.resolved_commands = commands;
+    }
+
+    let (discord_name, player_name) = template_names(&command.user, command.member.as_deref());
+    let context = TemplateContext {
+        steam_id: &player.steam_id,
+        discord_name: &discord_name,
+        player_name: &player_name,
+        quantity: button.quantity,
+        purchase_id: item.purchase_id.unwrap_or(0),
+    };
+    let (commands, draws) = delivery_commands(shared_state, &shop_item, &button, &context, &user_id);
+    shared_state.command_queue.lock().await.push(commands);
+    info!("{} claimed stash item #{} ({})", user_id, item.id, item.item_name);
+
+    reply(ctx, command, append_draws(format!("✅ กำลังส่ง {} เข้าเกม", item.item_name), &draws)).await;
+}
+
+/// Stands in for a button removed from the shop since the item was stashed, built from the
+/// quantity and commands kept at purchase. `None` when those weren't kept, or for a loot box
+/// whose item, and so its table, is gone too.
+fn stored_button(shops: &[ShopConfig], stashed: &StashItem) -> Option<(ShopItem, ShopButton)> {
+    let commands: Vec<ButtonCommand> = serde_json::from_str(stashed.commands.as_deref()?).ok()?;
+    let loot = if stashed.loot_box {
+        let item = shops.iter().flat_map(|shop| &shop.items).find(|item| item.name == stashed.item_name)?;
+        Some(item.loot.clone()?)
+    } else {
+        None
+    };
+
+    let button = ShopButton {
+        text: stashed.item_name.clone(),
+        trigger: stashed.trigger.clone(),
+        commands: Vec::new(),
+        resolved_commands: commands,
+        quantity: stashed.quantity.unwrap_or(1),
+        cooldown_seconds: None,
+        daily_limit: None,
+        weekly_limit: None,
+        stock: None,
+        restock_hours: None,
+        disabled: true,
+    };
+    let item = ShopItem {
+        name: stashed.item_name.clone(),
+        price: stashed.price.max(0) as u32,
+        image: None,
+        category: None,
+        commands: Vec::new(),
+        buttons: Vec::new(),
+        disabled: true,
+        loot,
+    };
+    Some((item, button))
+}
+
+pub async fn handle_claim_autocomplete(ctx: &Context, command: &CommandInteraction, shared_state: &SharedState) {
+    let mut response = CreateAutocompleteResponse::new();
+    let items = shared_state.db.get_stash_items(&command.user.id.to_string()).unwrap_or_default();
+    for item in items.iter().take(25) {
+        let label = format!("#{} {}", item.id, stash_label(shared_state, item));
+        response = response.add_int_choice(truncate(&label, 100), item.id);
+    }
+
+    let _ = command.create_response(&ctx.http, CreateInteractionResponse::Autocomplete(response)).await;
+}
+
+/// Closes expired stash entries, refunds part of their price to whoever paid and tells them,
+/// and the recipient of an expired gift.
+pub async fn start_stash_expiry(http: Arc<Http>, shared_state: Arc<SharedState>) {
+    loop {
+        match shared_state.db.expire_stash_items(shared_state.config.stash_expiry_refund_percent) {
+            Ok(expired) => {
+                for (item, refund) in expired {
+                    info!("Stash item #{} of {} expired, refunded {} to {}", item.id, item.discord_id, refund, item.paid_by);
+                    notify_expired(&http, &item, refund).await;
+                }
+            },
+            Err(e) => error!("Failed to expire stash items: {:?}", e),
+        }
+
+        sleep(Duration::from_secs(STASH_EXPIRY_CHECK_SECS)).await;
+    }
+}
+
+async fn notify_expired(http: &Http, item: &StashItem, refund: i32) {
+    let gift = item.paid_by != item.discord_id;
+
+    let mut embed = CreateEmbed::new()
+        .title("⌛ ของในคลังหมดอายุ")
+        .color(0x9900cc)
+        .field("🛒 สินค้า", &item.item_name, false)
+        .field("💷 คืน coin", format!("**{}** coin", refund), true)
+        .footer(CreateEmbedFooter::new("© powered by TimeSkip"));
+    if gift {
+        embed = embed.field("🎁 ของขวัญให้", format!("<@{}>", item.discord_id), true);
+    }
+    send_dm(http, &item.paid_by, embed).await;
+
+    if gift {
+        let embed = CreateEmbed::new()
+            .title("⌛ ของขวัญในคลังหมดอายุ")
+            .color(0x9900cc)
+            .field("🛒 สินค้า", &item.item_name, false)
+            .field("👤 จาก", format!("<@{}>", item.paid_by), true)
+            .footer(CreateEmbedFooter::new("คืน coin ให้ผู้ส่งแล้ว • © powered by TimeSkip"));
+        send_dm(http, &item.discord_id, embed).await;
+    }
+}
+
+async fn send_dm(http: &Http, discord_id: &str, embed: CreateEmbed) {
+    let user_id = match discord_id.parse::<u64>() {
+        Ok(id) => UserId::new(id),
+        Err(_) => return,
+    };
+
+    if let Ok(dm_channel) = user_id.create_dm_channel(http).await {
+        let _ = dm_channel.send_message(http, CreateMessage::new().embed(embed)).await;
+    }
+}
+
+fn stash_label(shared_state: &SharedState, item: &StashItem) -> String {
+    match find_button(&shared_state.shop_file().shops, &item.trigger) {
+        Some((_, button)) => format!("{} ({})", item.item_name, button.text),
+        None => item.item_name.clone(),
+    }
+}
+
+/// `<t:…:R>` so Discord shows the time in the reader's timezone.
+pub fn discord_time(timestamp: &str) -> String {
+    match NaiveDateTime::parse_from_str(timestamp, DB_TIME_FORMAT) {
+        Ok(time) => format!("<t:{}:R>", time.and_utc().timestamp()),
+        Err(_) => timestamp.to_string(),
+    }
+}
@@ -0,0 +1,328 @@
+//! Button command templates.
+//!
+//! - `{steam_id}`, `{discord_name}`, `{player_name}`: text of the purchase.
+//! - `{quantity}`, `{purchase_id}`: numbers, usable in arithmetic like `{quantity*30}` or
+//!   `{(quantity+1)/2}` (integer math, division by zero gives 0).
+//! - `{repeat EXPR}BODY{end}` around a whole command emits BODY as EXPR separate commands,
+//!   with `{index}` counting from 1.
+//! - `{{` and `}}` are literal braces.
+
+/// Guards against a typo flooding the game chat.
+pub const MAX_REPEAT: i64 = 50;
+
+const TEXT_VARIABLES: [&str; 3] = ["steam_id", "discord_name", "player_name"];
+const NUMBER_VARIABLES: [&str; 3] = ["quantity", "purchase_id", "index"];
+
+/// Values available to a command while it's rendered.
+pub struct TemplateContext<'a> {
+    pub steam_id: &'a str,
+    pub discord_name: &'a str,
+    /// The buyer's (or gift recipient's) display name in the server.
+    pub player_name: &'a str,
+    pub quantity: u32,
+    pub purchase_id: i64,
+}
+
+enum Segment {
+    Text(String),
+    Variable(&'static str),
+    Expr(Expr),
+}
+
+enum Expr {
+    Number(i64),
+    Variable(&'static str),
+    Binary(Box<Expr>, char, Box<Expr>),
+}
+
+struct Template {
+    repeat: Option<Expr>,
+    body: Vec<Segment>,
+}
+
+/// Checks a command template without rendering it, for config loading.
+pub fn validate(source: &str) -> Result<(), String> {
+    parse(source).map(|_| ())
+}
+
+/// Renders `source` into one command, or several for a repeat block. A template that doesn't
+/// parse is returned unchanged; the config loader rejects those up front.
+pub fn render(source: &str, context: &TemplateContext) -> Vec<String> {
+    match parse(source) {
+        Ok(template) => template.render(context),
+        Err(_) => vec![source.to_string()],
+    }
+}
+
+fn parse(source: &str) -> Result<Template, String> {
+    let (repeat, body) = match source.strip_prefix("{repeat ") {
+        Some(rest) => {
+            let close = rest.find('}').ok_or("unclosed {repeat")?;
+            let body = rest[close + 1..].strip_suffix("{end}").ok_or("{repeat} must end the command with {end}")?;
+            (Some(parse_expr(&rest[..close], false)?), body)
+        },
+        None => (None, source),
+    };
+
+    if body.contains("{repeat") || body.contains("{end}") {
+        return Err("{repeat ...}{end} has to wrap the whole command".to_string());
+    }
+
+    Ok(Template { body: parse_body(body, repeat.is_some())?, repeat })
+}
+
+impl Template {
+    fn render(&self, context: &TemplateContext) -> Vec<String> {
+        match &self.repeat {
+            Some(count) => {
+                let count = eval(count, context, 0).clamp(0, MAX_REPEAT);
+                (1..=count).map(|index| self.render_body(context, index)).collect()
+            },
+            None => vec![self.render_body(context, 0)],
+        }
+    }
+
+    fn render_body(&self, context: &TemplateContext, index: i64) -> String {
+        self.body.iter()
+            .map(|segment| match segment {
+                Segment::Text(text) => text.clone(),
+                Segment::Variable(name) => text_variable(name, context),
+                Segment::Expr(expr) => eval(expr, context, index).to_string(),
+            })
+            .collect()
+    }
+}
+
+fn parse_body(body: &str, in_repeat: bool) -> Result<Vec<Segment>, String> {
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    let mut chars = body.char_indices().peekable();
+
+    while let Some((position, c)) = chars.next() {
+        match c {
+            '{' if chars.peek().map(|(_, next)| *next) == Some('{') => {
+                chars.next();
+                text.push('{');
+            },
+            '}' if chars.peek().map(|(_, next)| *next) == Some('}') => {
+                chars.next();
+                text.push('}');
+            },
+            '{' => {
+                let close = body[position..].find('}').ok_or_else(|| format!("unclosed '{{' at {}", position))?;
+                let inner = &body[position + 1..position + close];
+                while chars.peek().is_some_and(|(next, _)| *next <= position + close) {
+                    chars.next();
+                }
+
+                if !text.is_empty() {
+                    segments.push(Segment::Text(std::mem::take(&mut text)));
+                }
+                match TEXT_VARIABLES.iter().find(|name| **name == inner.trim()) {
+                    Some(name) => segments.push(Segment::Variable(name)),
+                    None => segments.push(Segment::Expr(parse_expr(inner, in_repeat)?)),
+                }
+            },
+            '}' => return Err(format!("unmatched '}}' at {}", position)),
+            _ => text.push(c),
+        }
+    }
+
+    if !text.is_empty() {
+        segments.push(Segment::Text(text));
+    }
+    Ok(segments)
+}
+
+fn parse_expr(source: &str, in_repeat: bool) -> Result<Expr, String> {
+    let tokens = tokenize(source)?;
+    let mut parser = Parser { tokens: &tokens, position: 0, in_repeat };
+    let expr = parser.sum()?;
+    match parser.tokens.get(parser.position) {
+        None => Ok(expr),
+        Some(token) => Err(format!("unexpected '{}' in {{{}}}", token, source)),
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_alphanumeric() || c == '_' {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_ascii_alphanumeric() || **c == '_') {
+                word.push(c);
+                chars.next();
+            }
+            tokens.push(word);
+        } else if "+-*/()".contains(c) {
+            tokens.push(c.to_string());
+            chars.next();
+        } else {
+            return Err(format!("unexpected '{}' in {{{}}}", c, source));
+        }
+    }
+
+    if tokens.is_empty() {
+        return Err("empty {}".to_string());
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [String],
+    position: usize,
+    in_repeat: bool,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.position).map(String::as_str)
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut left = self.product()?;
+        while let Some(op @ ("+" | "-")) = self.peek() {
+            let op = op.chars().next().unwrap_or('+');
+            self.position += 1;
+            left = Expr::Binary(Box::new(left), op, Box::new(self.product()?));
+        }
+        Ok(left)
+    }
+
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut left = self.atom()?;
+        while let Some(op @ ("*" | "/")) = self.peek() {
+            let op = op.chars().next().unwrap_or('*');
+            self.position += 1;
+            left = Expr::Binary(Box::new(left), op, Box::new(self.atom()?));
+        }
+        Ok(left)
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+        let token = self.peek().ok_or("expression ends early")?.to_string();
+        self.position += 1;
+
+        if token == "(" {
+            let expr = self.sum()?;
+            if self.peek() != Some(")") {
+                return Err("missing ')'".to_string());
+            }
+            self.position += 1;
+            return Ok(expr);
+        }
+        if let Ok(number) = token.parse::<i64>() {
+            return Ok(Expr::Number(number));
+        }
+        if token == "index" && !self.in_repeat {
+            return Err("{index} only works inside {repeat}".to_string());
+        }
+        if let Some(name) = NUMBER_VARIABLES.iter().find(|name| **name == token) {
+            return Ok(Expr::Variable(name));
+        }
+        if TEXT_VARIABLES.contains(&token.as_str()) {
+            return Err(format!("{} is text and can't be used in arithmetic", token));
+        }
+        Err(format!("unknown variable '{}'", token))
+    }
+}
+
+fn eval(expr: &Expr, context: &TemplateContext, index: i64) -> i64 {
+    match expr {
+        Expr::Number(number) => *number,
+        Expr::Variable("quantity") => context.quantity as i64,
+        Expr::Variable("purchase_id") => context.purchase_id,
+        Expr::Variable("index") => index,
+        Expr::Variable(_) => 0,
+        Expr::Binary(left, op, right) => {
+            let (left, right) = (eval(left, context, index), eval(right, context, index));
+            match op {
+                '+' => left.saturating_add(right),
+                '-' => left.saturating_sub(right),
+                '*' => left.saturating_mul(right),
+                _ => left.checked_div(right).unwrap_or(0),
+            }
+        },
+    }
+}
+
+fn text_variable(name: &str, context: &TemplateContext) -> String {
+    match name {
+        "steam_id" => context.steam_id.to_string(),
+        "discord_name" => context.discord_name.to_string(),
+        "player_name" => context.player_name.to_string(),
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> TemplateContext<'static> {
+        TemplateContext {
+            steam_id: "76561198000000000",
+            discord_name: "buyer",
+            player_name: "Buyer",
+            quantity: 3,
+            purchase_id: 42,
+        }
+    }
+
+    fn render_one(source: &str) -> String {
+        let mut commands = render(source, &context());
+        assert_eq!(commands.len(), 1, "{} rendered {:?}", source, commands);
+        commands.remove(0)
+    }
+
+    #[test]
+    fn arithmetic_follows_precedence_and_parentheses() {
+        assert_eq!(render_one("#SpawnItem Nails {quantity*30}"), "#SpawnItem Nails 90");
+        assert_eq!(render_one("#SpawnItem Nails {(quantity+1)/2}"), "#SpawnItem Nails 2");
+        assert_eq!(render_one("{1+quantity*2}"), "7");
+        assert_eq!(render_one("{steam_id} #{purchase_id}"), "76561198000000000 #42");
+    }
+
+    #[test]
+    fn division_by_zero_gives_zero() {
+        assert_eq!(render_one("{quantity/0}"), "0");
+        assert_eq!(render_one("{quantity/(quantity-3)}"), "0");
+    }
+
+    #[test]
+    fn repeat_emits_one_command_per_index() {
+        let commands = render("{repeat quantity}#SpawnItem Weapon_AK47 1 {index}{end}", &context());
+        assert_eq!(commands, vec![
+            "#SpawnItem Weapon_AK47 1 1",
+            "#SpawnItem Weapon_AK47 1 2",
+            "#SpawnItem Weapon_AK47 1 3",
+        ]);
+
+        assert_eq!(render("{repeat quantity*1000}x{end}", &context()).len(), MAX_REPEAT as usize);
+        assert!(render("{repeat quantity-5}x{end}", &context()).is_empty());
+    }
+
+    #[test]
+    fn index_outside_repeat_is_rejected() {
+        assert!(validate("#SpawnItem Nails {index}").is_err());
+        assert!(validate("{repeat index}x{end}").is_err());
+        assert!(validate("{repeat 2}{index*2}{end}").is_ok());
+    }
+
+    #[test]
+    fn text_variables_are_rejected_in_arithmetic() {
+        assert!(validate("{steam_id+1}").is_err());
+        assert!(validate("{repeat player_name}x{end}").is_err());
+        assert!(validate("{unknown*2}").is_err());
+    }
+
+    #[test]
+    fn doubled_braces_are_literal() {
+        assert_eq!(render_one("#Announce {{quantity}} = {quantity}"), "#Announce {quantity} = 3");
+        assert!(validate("#Announce }").is_err());
+        assert!(validate("#Announce {quantity").is_err());
+    }
+}
@@ -67,24 +67,11 @@ pub async fn send_commands_to_game(commands: Vec<String>, command_type: &str) {
     }
 }
 
-pub fn substitute_steam_id_in_commands(commands: &[String], steam_id: &str) -> Vec<String> {
-    commands.iter()
-        .map(|cmd| cmd.replace("{steam_id}", steam_id))
-        .collect()
-}
-
-pub fn is_special_command(cmd: &str, special_commands: &[String]) -> bool {
-    special_commands.iter().any(|special| cmd.contains(special))
+pub fn format_wait_time(seconds: i64) -> String {
+    let seconds = seconds.max(0);
+    if seconds >= 60 {
+        format!("{} นาที {} วินาที", seconds / 60, seconds % 60)
+    } else {
+        format!("{} วินาที", seconds)
+    }
 }
-
-pub fn calculate_discounted_price(
-    base_price: u32,
-    quantity: u32,
-    discount: f32
-) -> (u32, u32, u32) {
-    let original_total = base_price * quantity;
-    let discounted_total = (original_total as f32 * (1.0 - discount)) as u32;
-    let discount_percent = (discount * 100.0) as u32;
-
-    (original_total, discounted_total, discount_percent)
-}
\ No newline at end of file